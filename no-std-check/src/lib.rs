@@ -0,0 +1,62 @@
+//! `#![no_std]` consumer of `email-encoding`.
+//!
+//! Building this crate on its own checks that `email-encoding` builds
+//! without the `std` feature:
+//!
+//! ```sh
+//! cargo build -p email-encoding-no-std-check
+//! ```
+
+#![no_std]
+#![forbid(unsafe_code)]
+#![deny(rust_2018_idioms)]
+
+extern crate alloc;
+
+use alloc::{borrow::Cow, string::String};
+
+use email_encoding::headers::{rfc2047, rfc2231, writer::EmailWriter};
+
+/// Encode a `Content-Disposition` header with a `filename` parameter.
+pub fn content_disposition(filename: &str) -> Result<String, email_encoding::Error> {
+    let mut output = String::from("Content-Disposition: attachment;");
+    let line_len = output.len();
+    {
+        let mut w = EmailWriter::new(&mut output, line_len, 0, false);
+        w.space();
+        rfc2231::encode("filename", filename, &mut w)?;
+    }
+    Ok(output)
+}
+
+/// Decode an unstructured header value containing encoded-words.
+pub fn decode_subject(subject: &str) -> Result<Cow<'_, str>, email_encoding::decode::Error> {
+    rfc2047::decode(subject)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_error<E: core::error::Error>() {}
+
+    #[test]
+    fn core_error() {
+        assert_error::<email_encoding::Error>();
+        assert_error::<email_encoding::decode::Error>();
+        assert_error::<email_encoding::headers::writer::Error>();
+        assert_error::<email_encoding::headers::rfc2231::Error>();
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(
+            content_disposition("faktúra.pdf").unwrap(),
+            "Content-Disposition: attachment; filename*=utf-8''fakt%C3%BAra.pdf"
+        );
+        assert_eq!(
+            decode_subject("=?utf-8?q?fakt=C3=BAra?=").unwrap(),
+            "faktúra"
+        );
+    }
+}
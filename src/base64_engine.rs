@@ -0,0 +1,320 @@
+//! Base64 primitives used by the encoders and decoders of this crate.
+//!
+//! Backed by the [`base64`] crate when the `base64` feature is enabled,
+//! and by a small built-in implementation otherwise.
+//!
+//! [`base64`]: https://docs.rs/base64
+
+/// Error returned by [`decode_canonical_slice`].
+///
+/// Indexes are offsets into the input.
+#[cfg(feature = "decode")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DecodeError {
+    /// A byte outside of the alphabet, or a last symbol with
+    /// non-zero trailing bits
+    Byte { index: usize, byte: u8 },
+    /// The padding is misplaced, malformed or missing
+    Padding,
+    /// The input ends in the middle of a byte
+    Length,
+}
+
+#[cfg(feature = "decode")]
+pub(crate) use self::imp::decode_canonical_slice;
+pub(crate) use self::imp::{
+    decode_unpadded_slice, encode_slice, encode_url_safe_slice, write_encoded,
+};
+
+#[cfg(feature = "base64")]
+mod imp {
+    use core::fmt::{self, Write};
+
+    use ::base64::{
+        display::Base64Display,
+        engine::{
+            general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+            DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig,
+        },
+        Engine,
+    };
+
+    #[cfg(feature = "decode")]
+    use super::DecodeError;
+
+    /// Engine accepting input with or without padding
+    const LENIENT: GeneralPurpose = GeneralPurpose::new(
+        &::base64::alphabet::STANDARD,
+        GeneralPurposeConfig::new()
+            .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+            .with_decode_allow_trailing_bits(true),
+    );
+
+    pub(crate) fn encode_slice(input: &[u8], output: &mut [u8]) -> Option<usize> {
+        STANDARD.encode_slice(input, output).ok()
+    }
+
+    pub(crate) fn encode_url_safe_slice(input: &[u8], output: &mut [u8]) -> Option<usize> {
+        URL_SAFE_NO_PAD.encode_slice(input, output).ok()
+    }
+
+    pub(crate) fn write_encoded<W: ?Sized + Write>(input: &[u8], w: &mut W) -> fmt::Result {
+        write!(w, "{}", Base64Display::new(input, &STANDARD))
+    }
+
+    pub(crate) fn decode_unpadded_slice(input: &[u8], output: &mut [u8]) -> Option<usize> {
+        LENIENT.decode_slice(input, output).ok()
+    }
+
+    #[cfg(feature = "decode")]
+    pub(crate) fn decode_canonical_slice(
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, DecodeError> {
+        STANDARD
+            .decode_slice(input, output)
+            .map_err(|err| match err {
+                ::base64::DecodeSliceError::DecodeError(
+                    ::base64::DecodeError::InvalidByte(_, b'=')
+                    | ::base64::DecodeError::InvalidPadding,
+                ) => DecodeError::Padding,
+                ::base64::DecodeSliceError::DecodeError(
+                    ::base64::DecodeError::InvalidByte(index, byte)
+                    | ::base64::DecodeError::InvalidLastSymbol(index, byte),
+                ) => DecodeError::Byte { index, byte },
+                ::base64::DecodeSliceError::DecodeError(::base64::DecodeError::InvalidLength(
+                    _,
+                ))
+                | ::base64::DecodeSliceError::OutputSliceTooSmall => DecodeError::Length,
+            })
+    }
+}
+
+#[cfg(not(feature = "base64"))]
+mod imp {
+    use core::{
+        fmt::{self, Write},
+        str,
+    };
+
+    #[cfg(feature = "decode")]
+    use super::DecodeError;
+
+    const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    pub(crate) fn encode_slice(input: &[u8], output: &mut [u8]) -> Option<usize> {
+        encode_with(STANDARD, true, input, output)
+    }
+
+    pub(crate) fn encode_url_safe_slice(input: &[u8], output: &mut [u8]) -> Option<usize> {
+        encode_with(URL_SAFE, false, input, output)
+    }
+
+    pub(crate) fn write_encoded<W: ?Sized + Write>(input: &[u8], w: &mut W) -> fmt::Result {
+        let mut buf = [0; 1024 / 3 * 4];
+        for chunk in input.chunks(1024 / 3 * 3) {
+            let len = encode_slice(chunk, &mut buf).expect("base64 output `buf` is not big enough");
+            w.write_str(str::from_utf8(&buf[..len]).expect("base64 produced an invalid encode"))?;
+        }
+        Ok(())
+    }
+
+    fn encode_with(
+        alphabet: &[u8; 64],
+        pad: bool,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Option<usize> {
+        let len = match (input.len() % 3, pad) {
+            (0, _) => input.len() / 3 * 4,
+            (_, true) => input.len() / 3 * 4 + 4,
+            (rem, false) => input.len() / 3 * 4 + rem + 1,
+        };
+        let output = output.get_mut(..len)?;
+
+        let symbol = |sextet: u32| alphabet[(sextet & 0x3f) as usize];
+        let mut written = 0;
+        for chunk in input.chunks(3) {
+            let mut bytes = [0; 3];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            let bits = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+            let quantum = [
+                symbol(bits >> 18),
+                symbol(bits >> 12),
+                symbol(bits >> 6),
+                symbol(bits),
+            ];
+
+            let symbols = chunk.len() + 1;
+            output[written..written + symbols].copy_from_slice(&quantum[..symbols]);
+            written += symbols;
+        }
+        output[written..].fill(b'=');
+        Some(len)
+    }
+
+    fn decode_symbol(byte: u8) -> Option<u8> {
+        match byte {
+            b'A'..=b'Z' => Some(byte - b'A'),
+            b'a'..=b'z' => Some(byte - b'a' + 26),
+            b'0'..=b'9' => Some(byte - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    /// Decode `symbols`, which must not contain padding, ignoring the
+    /// trailing bits of the last symbol
+    fn decode_symbols(symbols: &[u8], output: &mut [u8]) -> Option<usize> {
+        if symbols.len() % 4 == 1 {
+            return None;
+        }
+        let len = symbols.len() / 4 * 3 + (symbols.len() % 4).saturating_sub(1);
+        let output = output.get_mut(..len)?;
+
+        let mut written = 0;
+        for chunk in symbols.chunks(4) {
+            let mut bits = 0;
+            for (i, &byte) in chunk.iter().enumerate() {
+                bits |= u32::from(decode_symbol(byte)?) << (18 - 6 * i);
+            }
+            let bytes = bits.to_be_bytes();
+            let decoded = chunk.len() - 1;
+            output[written..written + decoded].copy_from_slice(&bytes[1..=decoded]);
+            written += decoded;
+        }
+        Some(written)
+    }
+
+    pub(crate) fn decode_unpadded_slice(input: &[u8], output: &mut [u8]) -> Option<usize> {
+        decode_symbols(input, output)
+    }
+
+    #[cfg(feature = "decode")]
+    pub(crate) fn decode_canonical_slice(
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, DecodeError> {
+        let symbols_len = input
+            .iter()
+            .position(|&byte| byte == b'=')
+            .unwrap_or(input.len());
+        let (symbols, padding) = input.split_at(symbols_len);
+        if let Some((index, &byte)) = symbols
+            .iter()
+            .enumerate()
+            .find(|(_, &byte)| decode_symbol(byte).is_none())
+        {
+            return Err(DecodeError::Byte { index, byte });
+        }
+
+        match (symbols.len() % 4, padding.len()) {
+            (1, _) => return Err(DecodeError::Length),
+            (0, 0) | (2, 2) | (3, 1) if padding.iter().all(|&byte| byte == b'=') => {}
+            _ => return Err(DecodeError::Padding),
+        }
+
+        // The unused bits of the last symbol must be zeros
+        let unused_bits = match symbols.len() % 4 {
+            2 => 0x0f,
+            3 => 0x03,
+            _ => 0,
+        };
+        if let Some(&byte) = symbols.last() {
+            if decode_symbol(byte).unwrap_or(0) & unused_bits != 0 {
+                return Err(DecodeError::Byte {
+                    index: symbols.len() - 1,
+                    byte,
+                });
+            }
+        }
+
+        decode_symbols(symbols, output).ok_or(DecodeError::Length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let mut buf = [0; 8];
+        for (input, expected) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xfb\xff", "+/8="),
+        ] {
+            let len = encode_slice(input, &mut buf).unwrap();
+            assert_eq!(&buf[..len], expected.as_bytes());
+
+            let mut s = String::new();
+            write_encoded(input, &mut s).unwrap();
+            assert_eq!(s, expected);
+        }
+        assert_eq!(encode_slice(b"foobarb", &mut buf), None);
+
+        let len = encode_url_safe_slice(b"\xfb\xff", &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"-_8");
+    }
+
+    #[test]
+    fn write_long() {
+        let input = vec![0xa5; 2000];
+        let mut s = String::new();
+        write_encoded(&input, &mut s).unwrap();
+
+        let mut buf = vec![0; 2000 / 3 * 4 + 4];
+        let len = encode_slice(&input, &mut buf).unwrap();
+        assert_eq!(s.as_bytes(), &buf[..len]);
+    }
+
+    #[test]
+    #[cfg(feature = "decode")]
+    fn decode() {
+        let mut buf = [0; 6];
+        assert_eq!(decode_canonical_slice(b"Zm9vYg==", &mut buf), Ok(4));
+        assert_eq!(&buf[..4], b"foob");
+        assert_eq!(decode_unpadded_slice(b"Zm9vYmE", &mut buf), Some(5));
+        assert_eq!(&buf[..5], b"fooba");
+        // Trailing bits are ignored
+        assert_eq!(decode_unpadded_slice(b"Zh", &mut buf), Some(1));
+        assert_eq!(&buf[..1], b"f");
+
+        assert_eq!(
+            decode_canonical_slice(b"Zm9*", &mut buf),
+            Err(DecodeError::Byte {
+                index: 3,
+                byte: b'*'
+            })
+        );
+        assert_eq!(
+            decode_canonical_slice(b"Zh==", &mut buf),
+            Err(DecodeError::Byte {
+                index: 1,
+                byte: b'h'
+            })
+        );
+        assert_eq!(
+            decode_canonical_slice(b"Zm9vYg", &mut buf),
+            Err(DecodeError::Padding)
+        );
+        assert_eq!(
+            decode_canonical_slice(b"Zm9v=Yg=", &mut buf),
+            Err(DecodeError::Padding)
+        );
+        assert_eq!(
+            decode_canonical_slice(b"Zm9vY", &mut buf),
+            Err(DecodeError::Length)
+        );
+    }
+}
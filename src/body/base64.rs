@@ -1,9 +1,11 @@
-//! Base64 email body encoder.
+//! Base64 email body encoder and decoder.
 
-use std::fmt::{self, Write};
-use std::str;
+#[cfg(feature = "decode")]
+use alloc::{vec, vec::Vec};
+use core::fmt::{self, Write};
+use core::str;
 
-use ::base64::Engine;
+use crate::base64_engine;
 
 const LINE_LEN: usize = 76;
 const CRLF: &str = "\r\n";
@@ -40,13 +42,53 @@ const CRLF: &str = "\r\n";
 /// # }
 /// ```
 pub fn encode(b: &[u8], w: &mut dyn Write) -> fmt::Result {
+    encode_lines(b, w, base64_engine::encode_slice)
+}
+
+/// Base64 encode the provided bytes using `engine`.
+///
+/// Behaves like [`encode`], except that lines are encoded by `engine`,
+/// for applications which already configured a [`base64::Engine`].
+/// `engine` should use the standard alphabet with padding, as required
+/// by RFC 2045.
+///
+/// Requires the `base64` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "base64")] {
+/// let mut output = String::new();
+/// email_encoding::body::base64::encode_with_engine(
+///     b"Hello!",
+///     &base64::engine::general_purpose::STANDARD,
+///     &mut output,
+/// )
+/// .unwrap();
+/// assert_eq!(output, "SGVsbG8h");
+/// # }
+/// ```
+///
+/// [`base64::Engine`]: ::base64::Engine
+#[cfg(feature = "base64")]
+pub fn encode_with_engine<E: ::base64::Engine>(
+    b: &[u8],
+    engine: &E,
+    w: &mut dyn Write,
+) -> fmt::Result {
+    encode_lines(b, w, |chunk, buf| engine.encode_slice(chunk, buf).ok())
+}
+
+fn encode_lines(
+    b: &[u8],
+    w: &mut dyn Write,
+    encode_slice: impl Fn(&[u8], &mut [u8]) -> Option<usize>,
+) -> fmt::Result {
     let mut buf = [0; LINE_LEN];
 
     let mut chunks = b.chunks(LINE_LEN / 4 * 3).peekable();
     while let Some(chunk) = chunks.next() {
-        let len = ::base64::engine::general_purpose::STANDARD
-            .encode_slice(chunk, &mut buf)
-            .expect("base64 output `buf` is not big enough");
+        let len = encode_slice(chunk, &mut buf).expect("base64 output `buf` is not big enough");
 
         w.write_str(str::from_utf8(&buf[..len]).expect("base64 produced an invalid encode"))?;
         if chunks.peek().is_some() {
@@ -59,6 +101,8 @@ pub fn encode(b: &[u8], w: &mut dyn Write) -> fmt::Result {
 
 /// Predict how many bytes [`encode`] is going to write given a `input_len` input length.
 ///
+/// Being a `const fn`, it can be used for sizing buffers at compile time.
+///
 /// # Examples
 ///
 /// ```rust
@@ -66,8 +110,12 @@ pub fn encode(b: &[u8], w: &mut dyn Write) -> fmt::Result {
 /// assert_eq!(encoded_len(0), 0);
 /// assert_eq!(encoded_len(16), 24);
 /// assert_eq!(encoded_len(300), 410);
+///
+/// const MAX_ATTACHMENT_LEN: usize = 4096;
+/// static BUFFER: [u8; encoded_len(MAX_ATTACHMENT_LEN)] = [0; encoded_len(MAX_ATTACHMENT_LEN)];
+/// assert_eq!(BUFFER.len(), 5606);
 /// ```
-pub fn encoded_len(input_len: usize) -> usize {
+pub const fn encoded_len(input_len: usize) -> usize {
     let mut base64_len = input_len / 3 * 4;
     if input_len % 3 != 0 {
         base64_len += 4;
@@ -79,8 +127,171 @@ pub fn encoded_len(input_len: usize) -> usize {
     base64_len + crlf_len
 }
 
+/// Decode a base64 encoded body.
+///
+/// Line breaks and other whitespace are skipped, and the padding
+/// may be omitted.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidByte`] if `input` contains a byte outside of the
+/// base64 alphabet, [`Error::InvalidPadding`] if data follows the padding,
+/// and [`Error::InvalidLength`] if `input` ends in the middle of a byte.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let decoded = email_encoding::body::base64::decode(b"SGVsbG8s\r\nIHdvcmxkIQ==")?;
+/// assert_eq!(decoded, b"Hello, world!");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::InvalidByte`]: crate::decode::Error::InvalidByte
+/// [`Error::InvalidPadding`]: crate::decode::Error::InvalidPadding
+/// [`Error::InvalidLength`]: crate::decode::Error::InvalidLength
+#[cfg(feature = "decode")]
+pub fn decode(input: &[u8]) -> Result<Vec<u8>, crate::decode::Error> {
+    crate::decode::strict(|on_error| crate::decode::base64_lossy(input, on_error))
+}
+
+/// Decode a base64 encoded body, recovering from errors.
+///
+/// Like [`decode`], except that bytes outside of the base64 alphabet
+/// are skipped, data following the padding is decoded on its own and
+/// an incomplete trailing byte is dropped. `on_error` is called with
+/// the position and the nature of every problem found in `input`.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::decode::Error;
+/// let mut errors = Vec::new();
+/// let decoded =
+///     email_encoding::body::base64::decode_lossy(b"SGVs!bG8=LA", |err| errors.push(err));
+/// assert_eq!(decoded, b"Hello,");
+/// assert_eq!(
+///     errors,
+///     [
+///         Error::InvalidByte {
+///             position: 4,
+///             byte: b'!'
+///         },
+///         Error::InvalidPadding { position: 8 },
+///     ]
+/// );
+/// ```
+#[cfg(feature = "decode")]
+pub fn decode_lossy(input: &[u8], mut on_error: impl FnMut(crate::decode::Error)) -> Vec<u8> {
+    crate::decode::base64_lossy(input, &mut on_error)
+}
+
+/// Decode a base64 encoded body, only accepting its canonical encoding.
+///
+/// Unlike [`decode`], this only accepts the output of [`encode`], optionally
+/// followed by `CRLF`, so that every body has a single valid encoding:
+///
+/// * the padding is required;
+/// * lines are separated by `CRLF` and must be 76 chars long, except
+///   for the last one;
+/// * no other whitespace is allowed;
+/// * the unused bits of the last quantum must be zeros.
+///
+/// This is useful where malleable encodings are unacceptable, like
+/// for verifying signatures.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidByte`] if `input` contains a byte outside of the
+/// base64 alphabet or a non-canonical last char, [`Error::InvalidLine`] if
+/// a line has the wrong length, and [`Error::InvalidPadding`] or
+/// [`Error::InvalidLength`] if the padding is missing or misplaced.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::{body::base64::decode_canonical, decode::Error};
+/// assert_eq!(decode_canonical(b"SGVsbG8sIHdvcmxkIQ=="), Ok(b"Hello, world!".to_vec()));
+///
+/// assert_eq!(decode_canonical(b"SGVsbG8sIHdvcmxkIQ"), Err(Error::InvalidLength));
+/// assert_eq!(
+///     decode_canonical(b"SGVsbG8s\r\nIHdvcmxkIQ=="),
+///     Err(Error::InvalidLine { position: 0 })
+/// );
+/// assert_eq!(
+///     decode_canonical(b"SGVsbG8sIHdvcmxkIR=="),
+///     Err(Error::InvalidByte {
+///         position: 17,
+///         byte: b'R'
+///     })
+/// );
+/// ```
+///
+/// [`Error::InvalidByte`]: crate::decode::Error::InvalidByte
+/// [`Error::InvalidLine`]: crate::decode::Error::InvalidLine
+/// [`Error::InvalidPadding`]: crate::decode::Error::InvalidPadding
+/// [`Error::InvalidLength`]: crate::decode::Error::InvalidLength
+#[cfg(feature = "decode")]
+pub fn decode_canonical(input: &[u8]) -> Result<Vec<u8>, crate::decode::Error> {
+    use crate::decode::Error;
+
+    let input = input.strip_suffix(CRLF.as_bytes()).unwrap_or(input);
+    let mut encoded = Vec::with_capacity(input.len());
+    let mut lines = input.split(|&b| b == b'\n').peekable();
+    let mut position = 0;
+    while let Some(line) = lines.next() {
+        let line = match lines.peek() {
+            Some(_) => line.strip_suffix(b"\r").ok_or(Error::InvalidByte {
+                position: position + line.len(),
+                byte: b'\n',
+            })?,
+            None => line,
+        };
+        let is_last = lines.peek().is_none();
+        if line.len() > LINE_LEN || (!is_last && line.len() != LINE_LEN) {
+            return Err(Error::InvalidLine { position });
+        }
+
+        encoded.extend_from_slice(line);
+        position += line.len() + CRLF.len();
+    }
+
+    // Offset of the `i`th encoded char in `input`
+    let input_position = |i: usize| i + i / LINE_LEN * CRLF.len();
+    let mut decoded = vec![0; encoded.len() / 4 * 3];
+    let len = base64_engine::decode_canonical_slice(&encoded, &mut decoded).map_err(|err| {
+        match err {
+            base64_engine::DecodeError::Padding => match memchr::memchr(b'=', &encoded) {
+                Some(i) => Error::InvalidPadding {
+                    position: input_position(i),
+                },
+                // The padding is missing
+                None => Error::InvalidLength,
+            },
+            base64_engine::DecodeError::Byte { index, byte } => Error::InvalidByte {
+                position: input_position(index),
+                byte,
+            },
+            base64_engine::DecodeError::Length => Error::InvalidLength,
+        }
+    })?;
+    decoded.truncate(len);
+    Ok(decoded)
+}
+
 #[cfg(test)]
 mod tests {
+    use alloc::string::String;
+    #[cfg(feature = "decode")]
+    use alloc::{vec, vec::Vec};
+
     use pretty_assertions::assert_eq;
 
     use super::{encode, encoded_len};
@@ -172,4 +383,123 @@ mod tests {
         );
         assert_eq!(output.len(), encoded_len(input.len()));
     }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_round_trip() {
+        let input =
+            b"01234567899876543210012345678998765432100123456789987654321001234567899876543210";
+        let mut output = String::new();
+
+        encode(input, &mut output).unwrap();
+
+        assert_eq!(super::decode(output.as_bytes()).unwrap(), input);
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_invalid() {
+        use crate::decode::Error;
+
+        assert_eq!(
+            super::decode(b"MDEy\r\nM*=="),
+            Err(Error::InvalidByte {
+                position: 7,
+                byte: b'*'
+            })
+        );
+        assert_eq!(
+            super::decode(b"MDEyMw==MDEy"),
+            Err(Error::InvalidPadding { position: 6 })
+        );
+        assert_eq!(super::decode(b"MDEyM"), Err(Error::InvalidLength));
+        assert_eq!(super::decode(b"MDEyMw"), Ok(b"0123".to_vec()));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_lossy() {
+        use crate::decode::Error;
+
+        let mut errors = Vec::new();
+        let decoded = super::decode_lossy(b"MD*Ey\r\nMw=MDEyM", |err| errors.push(err));
+        assert_eq!(decoded, b"0123012");
+        assert_eq!(
+            errors,
+            [
+                Error::InvalidByte {
+                    position: 2,
+                    byte: b'*'
+                },
+                Error::InvalidPadding { position: 9 },
+                Error::InvalidLength,
+            ]
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_canonical() {
+        use crate::decode::Error;
+
+        let input = b"0123456789".repeat(12);
+        let mut output = String::new();
+        encode(&input, &mut output).unwrap();
+        assert_eq!(
+            super::decode_canonical(output.as_bytes()),
+            Ok(input.clone())
+        );
+        output.push_str("\r\n");
+        assert_eq!(super::decode_canonical(output.as_bytes()), Ok(input));
+        output.push_str("\r\n");
+        assert_eq!(
+            super::decode_canonical(output.as_bytes()),
+            Err(Error::InvalidLine { position: 156 })
+        );
+
+        assert_eq!(super::decode_canonical(b""), Ok(vec![]));
+        assert_eq!(
+            super::decode_canonical(b"MDEy\nMw=="),
+            Err(Error::InvalidByte {
+                position: 4,
+                byte: b'\n'
+            })
+        );
+        assert_eq!(
+            super::decode_canonical(b"MDEy Mw=="),
+            Err(Error::InvalidByte {
+                position: 4,
+                byte: b' '
+            })
+        );
+        assert_eq!(
+            super::decode_canonical(b"MDEyMw=a"),
+            Err(Error::InvalidPadding { position: 6 })
+        );
+        assert_eq!(
+            super::decode_canonical(b"MD=yMw=="),
+            Err(Error::InvalidPadding { position: 2 })
+        );
+        assert_eq!(
+            super::decode_canonical(&[b'A'; 80]),
+            Err(Error::InvalidLine { position: 0 })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn with_engine() {
+        let input = [0xa5; 200];
+        let mut expected = String::new();
+        encode(&input, &mut expected).unwrap();
+
+        let mut output = String::new();
+        super::encode_with_engine(
+            &input,
+            &::base64::engine::general_purpose::STANDARD,
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(output, expected);
+    }
 }
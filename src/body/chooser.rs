@@ -1,7 +1,8 @@
-use std::mem;
+use core::mem;
 
 use super::{Encoding, StrOrBytes};
 
+#[derive(Debug, Copy, Clone)]
 enum InputKind {
     Ascii,
     Utf8,
@@ -21,6 +22,27 @@ impl<'a> StrOrBytes<'a> {
     }
 }
 
+/// Options for [`Encoding::choose_with_options`].
+///
+/// New options may be added in minor releases, so the struct can only be
+/// constructed via [`ChooseOptions::default`] and the builder methods.
+#[derive(Debug, Copy, Clone, Default)]
+#[non_exhaustive]
+pub struct ChooseOptions {
+    /// Whether the SMTP server supports the `SMTPUTF8` extension.
+    pub supports_utf8: bool,
+}
+
+impl ChooseOptions {
+    /// Set whether the SMTP server supports the `SMTPUTF8` extension.
+    ///
+    /// Defaults to `false`.
+    pub fn supports_utf8(mut self, supports_utf8: bool) -> Self {
+        self.supports_utf8 = supports_utf8;
+        self
+    }
+}
+
 impl Encoding {
     /// Choose the most efficient `Encoding` for `input`
     ///
@@ -77,10 +99,33 @@ impl Encoding {
         Self::choose_impl(input, supports_utf8)
     }
 
+    /// Choose the most efficient `Encoding` for `input`, as configured
+    /// by `options`.
+    ///
+    /// See [`Encoding::choose`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::{ChooseOptions, Encoding};
+    /// let options = ChooseOptions::default().supports_utf8(true);
+    /// assert_eq!(
+    ///     Encoding::choose_with_options("Hello, World! 📬", options),
+    ///     Encoding::EightBit
+    /// );
+    /// ```
+    pub fn choose_with_options<'a>(
+        input: impl Into<StrOrBytes<'a>>,
+        options: ChooseOptions,
+    ) -> Self {
+        Self::choose_impl(input.into(), options.supports_utf8)
+    }
+
     fn choose_impl(input: StrOrBytes<'_>, supports_utf8: bool) -> Self {
         let line_too_long = line_too_long(&input);
+        let kind = input.kind();
 
-        match (input.kind(), line_too_long, supports_utf8) {
+        let encoding = match (kind, line_too_long, supports_utf8) {
             (InputKind::Ascii, false, _) => {
                 // Input is ascii and fits the maximum line length
                 Self::SevenBit
@@ -105,7 +150,15 @@ impl Encoding {
                 // Input is binary
                 Self::Base64
             }
-        }
+        };
+        debug!(
+            ?kind,
+            line_too_long,
+            supports_utf8,
+            ?encoding,
+            "chose body encoding"
+        );
+        encoding
     }
 }
 
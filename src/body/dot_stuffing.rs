@@ -0,0 +1,133 @@
+//! SMTP dot-stuffing decoder.
+//!
+//! Requires the `decode` feature.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+/// Remove the dot-stuffing of an SMTP `DATA` payload.
+///
+/// The first `.` of every line starting with a `.` is removed,
+/// as described by [RFC 5321]. Lines may end with `CRLF` or `LF`.
+/// `input` must not contain the `.` line terminating the payload.
+///
+/// The input is borrowed if no line needs to be modified.
+///
+/// This is the same as transforming the whole input with a [`Decoder`].
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// let decoded = email_encoding::body::dot_stuffing::decode(b"Hello\r\n..\r\n...world\r\n");
+/// assert_eq!(*decoded, *b"Hello\r\n.\r\n..world\r\n");
+/// ```
+///
+/// [RFC 5321]: https://datatracker.ietf.org/doc/html/rfc5321#section-4.5.2
+pub fn decode(input: &[u8]) -> Cow<'_, [u8]> {
+    let stuffed = input.starts_with(b".")
+        || memchr::memchr_iter(b'\n', input).any(|i| input.get(i + 1) == Some(&b'.'));
+    if !stuffed {
+        return Cow::Borrowed(input);
+    }
+
+    let mut decoded = Vec::with_capacity(input.len());
+    Decoder::new().decode_chunk(input, &mut decoded);
+    Cow::Owned(decoded)
+}
+
+/// Incremental dot-stuffing decoder.
+///
+/// The input can be split into chunks anywhere.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::body::dot_stuffing::Decoder;
+/// let mut decoded = Vec::new();
+/// let mut decoder = Decoder::new();
+/// for chunk in [&b"Hello\r\n"[..], b".", b".world\r"] {
+///     decoder.decode_chunk(chunk, &mut decoded);
+/// }
+///
+/// assert_eq!(decoded, b"Hello\r\n.world\r");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    /// Whether the next byte starts a line
+    line_start: bool,
+}
+
+impl Decoder {
+    /// Construct a new `Decoder`.
+    pub fn new() -> Self {
+        Self { line_start: true }
+    }
+
+    /// Decode `chunk`, appending the decoded bytes to `output`.
+    pub fn decode_chunk(&mut self, chunk: &[u8], output: &mut Vec<u8>) {
+        let mut rest = chunk;
+        while !rest.is_empty() {
+            if self.line_start && rest[0] == b'.' {
+                rest = &rest[1..];
+            }
+            self.line_start = false;
+
+            match memchr::memchr(b'\n', rest) {
+                Some(i) => {
+                    output.extend_from_slice(&rest[..=i]);
+                    rest = &rest[i + 1..];
+                    self.line_start = true;
+                }
+                None => {
+                    output.extend_from_slice(rest);
+                    rest = &[];
+                }
+            }
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn borrowed() {
+        let input = b"Hello\r\nworld. Bye\n";
+        assert!(matches!(decode(input), Cow::Borrowed(b) if b == input));
+        assert!(matches!(decode(b""), Cow::Borrowed(b"")));
+    }
+
+    #[test]
+    fn unstuffed() {
+        assert_eq!(*decode(b"."), *b"");
+        assert_eq!(*decode(b".a\n.b\r\n..c\n\n.\r\n"), *b"a\nb\r\n.c\n\n\r\n");
+    }
+
+    #[test]
+    fn chunks() {
+        let input = b"..a\r\n.b\nc.\r\n..\r\n";
+        let expected = decode(input);
+        assert_eq!(*expected, *b".a\r\nb\nc.\r\n.\r\n");
+
+        for chunk_len in 1..input.len() {
+            let mut decoded = Vec::new();
+            let mut decoder = Decoder::new();
+            for chunk in input.chunks(chunk_len) {
+                decoder.decode_chunk(chunk, &mut decoded);
+            }
+            assert_eq!(decoded, *expected, "chunks of {} bytes", chunk_len);
+        }
+    }
+}
@@ -1,9 +1,21 @@
 //! Email body encoding algorithms.
 
-use std::ops::Deref;
+#[cfg(feature = "decode")]
+use alloc::borrow::Cow;
+use core::ops::Deref;
 
 pub mod base64;
 mod chooser;
+#[cfg(feature = "decode")]
+pub mod dot_stuffing;
+#[cfg(feature = "decode")]
+pub mod quoted_printable;
+#[cfg(feature = "decode")]
+pub mod transcode;
+#[cfg(feature = "decode")]
+pub mod uuencode;
+
+pub use self::chooser::ChooseOptions;
 
 /// A possible email `Content-Transfer-Encoding`
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -18,6 +30,271 @@ pub enum Encoding {
     Base64,
 }
 
+impl Encoding {
+    /// Get the `Content-Transfer-Encoding` token of this encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::Encoding;
+    /// assert_eq!(Encoding::SevenBit.as_str(), "7bit");
+    /// assert_eq!(Encoding::QuotedPrintable.as_str(), "quoted-printable");
+    /// ```
+    #[doc(alias = "canonical_token")]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::SevenBit => "7bit",
+            Self::EightBit => "8bit",
+            Self::QuotedPrintable => "quoted-printable",
+            Self::Base64 => "base64",
+        }
+    }
+
+    /// Parse a `Content-Transfer-Encoding` token, ignoring case and
+    /// surrounding whitespace.
+    ///
+    /// Returns `None` for `binary` and for unknown tokens.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::Encoding;
+    /// assert_eq!(Encoding::from_token("Quoted-Printable"), Some(Encoding::QuotedPrintable));
+    /// assert_eq!(Encoding::from_token(" base64 "), Some(Encoding::Base64));
+    /// assert_eq!(Encoding::from_token("x-uuencode"), None);
+    /// ```
+    pub fn from_token(token: &str) -> Option<Self> {
+        let token = token.trim();
+        [
+            Self::SevenBit,
+            Self::EightBit,
+            Self::QuotedPrintable,
+            Self::Base64,
+        ]
+        .into_iter()
+        .find(|encoding| encoding.as_str().eq_ignore_ascii_case(token))
+    }
+
+    /// Find out whether this encoding can carry any sequence of bytes.
+    ///
+    /// `7bit` and `8bit` bodies can't contain `NUL`s, lone `CR`s and `LF`s,
+    /// or lines longer than [`HARD_LINE_LIMIT`] bytes, and `7bit` bodies are also
+    /// limited to US-ASCII.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::Encoding;
+    /// assert!(Encoding::Base64.is_binary_safe());
+    /// assert!(!Encoding::EightBit.is_binary_safe());
+    /// ```
+    ///
+    /// [`HARD_LINE_LIMIT`]: crate::headers::HARD_LINE_LIMIT
+    pub const fn is_binary_safe(self) -> bool {
+        matches!(self, Self::QuotedPrintable | Self::Base64)
+    }
+
+    /// Find out whether bodies encoded with this encoding only contain
+    /// US-ASCII, making them safe to send to servers which don't
+    /// support the `8BITMIME` SMTP extension.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::Encoding;
+    /// assert!(Encoding::QuotedPrintable.is_seven_bit());
+    /// assert!(!Encoding::EightBit.is_seven_bit());
+    /// ```
+    pub const fn is_seven_bit(self) -> bool {
+        !matches!(self, Self::EightBit)
+    }
+
+    /// Get the worst-case ratio between the length of an encoded body
+    /// and the length of the input, line breaks included.
+    ///
+    /// `quoted-printable` triples the length of bytes which need to be
+    /// escaped, and `base64` encodes 3 bytes into 4 chars, with
+    /// 76 chars lines.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::Encoding;
+    /// assert_eq!(Encoding::EightBit.expansion_factor(), 1.0);
+    /// assert!(Encoding::Base64.expansion_factor() < 1.4);
+    /// ```
+    pub fn expansion_factor(self) -> f64 {
+        match self {
+            Self::SevenBit | Self::EightBit => 1.0,
+            // 25 escapes and a soft line break on every line
+            Self::QuotedPrintable => 78.0 / 25.0,
+            // 57 bytes on every line
+            Self::Base64 => 78.0 / 57.0,
+        }
+    }
+
+    /// Decode a body encoded with this encoding.
+    ///
+    /// `7bit` and `8bit` bodies are returned as they are, while `quoted-printable`
+    /// and `base64` bodies are decoded via [`quoted_printable::decode`] and
+    /// [`base64::decode`].
+    ///
+    /// Requires the `decode` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the decoder of this encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::Encoding;
+    /// # fn main() -> Result<(), email_encoding::decode::Error> {
+    /// assert_eq!(*Encoding::Base64.decode(b"SGVsbG8h")?, *b"Hello!");
+    /// assert_eq!(*Encoding::SevenBit.decode(b"Hello!")?, *b"Hello!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode(self, input: &[u8]) -> Result<Cow<'_, [u8]>, crate::decode::Error> {
+        match self {
+            Self::SevenBit | Self::EightBit => Ok(Cow::Borrowed(input)),
+            Self::QuotedPrintable => quoted_printable::decode(input),
+            Self::Base64 => base64::decode(input).map(Cow::Owned),
+        }
+    }
+
+    /// Decode a body encoded with this encoding, enforcing `limits`.
+    ///
+    /// Like [`Encoding::decode`], but fails if the decoded body is longer
+    /// than [`Limits::max_output_len`]. Inputs too short to exceed the limit
+    /// once decoded aren't checked.
+    ///
+    /// Requires the `decode` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LimitExceeded`] if the decoded body would be too
+    /// long, otherwise the errors of the decoder of this encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::{
+    /// #     body::Encoding,
+    /// #     decode::{Error, Limit, Limits},
+    /// # };
+    /// let limits = Limits {
+    ///     max_output_len: 6,
+    ///     ..Limits::default()
+    /// };
+    /// assert_eq!(
+    ///     *Encoding::Base64.decode_with_limits(b"SGVsbG8h", limits).unwrap(),
+    ///     *b"Hello!"
+    /// );
+    /// assert_eq!(
+    ///     Encoding::SevenBit.decode_with_limits(b"Hello, world!", limits),
+    ///     Err(Error::LimitExceeded(Limit::OutputLen))
+    /// );
+    /// ```
+    ///
+    /// [`Limits::max_output_len`]: crate::decode::Limits::max_output_len
+    /// [`Error::LimitExceeded`]: crate::decode::Error::LimitExceeded
+    #[cfg(feature = "decode")]
+    pub fn decode_with_limits(
+        self,
+        input: &[u8],
+        limits: crate::decode::Limits,
+    ) -> Result<Cow<'_, [u8]>, crate::decode::Error> {
+        let exceeded = crate::decode::Error::LimitExceeded(crate::decode::Limit::OutputLen);
+
+        let max_decoded_len = match self {
+            Self::SevenBit | Self::EightBit | Self::QuotedPrintable => input.len(),
+            Self::Base64 => input.len() / 4 * 3 + 2,
+        };
+        if max_decoded_len <= limits.max_output_len {
+            return self.decode(input);
+        }
+        if self == Self::SevenBit || self == Self::EightBit {
+            return Err(exceeded);
+        }
+
+        let decoded = self.decode(input)?;
+        if decoded.len() > limits.max_output_len {
+            return Err(exceeded);
+        }
+        Ok(decoded)
+    }
+
+    /// Decode a body encoded with this encoding, recovering from errors.
+    ///
+    /// Like [`Encoding::decode`], but via [`quoted_printable::decode_lossy`]
+    /// and [`base64::decode_lossy`]. `on_error` is called with the position
+    /// and the nature of every problem found in `input`.
+    ///
+    /// Requires the `decode` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::{body::Encoding, decode::Error};
+    /// let mut errors = Vec::new();
+    /// let decoded = Encoding::Base64.decode_lossy(b"SGVsbG8h!", |err| errors.push(err));
+    /// assert_eq!(*decoded, *b"Hello!");
+    /// assert_eq!(
+    ///     errors,
+    ///     [Error::InvalidByte {
+    ///         position: 8,
+    ///         byte: b'!'
+    ///     }]
+    /// );
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_lossy(
+        self,
+        input: &[u8],
+        on_error: impl FnMut(crate::decode::Error),
+    ) -> Cow<'_, [u8]> {
+        match self {
+            Self::SevenBit | Self::EightBit => Cow::Borrowed(input),
+            Self::QuotedPrintable => quoted_printable::decode_lossy(input, on_error),
+            Self::Base64 => Cow::Owned(base64::decode_lossy(input, on_error)),
+        }
+    }
+}
+
+/// Decode a body given the value of its `Content-Transfer-Encoding` header.
+///
+/// `binary` bodies are returned as they are, like `7bit` and `8bit` ones.
+/// See [`Encoding::decode`].
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::UnsupportedEncoding`] if `token` isn't a known
+/// encoding, otherwise the errors of the decoder of the encoding.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let decoded = email_encoding::body::decode("Quoted-Printable", b"caf=C3=A9")?;
+/// assert_eq!(*decoded, *"café".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::UnsupportedEncoding`]: crate::decode::Error::UnsupportedEncoding
+#[cfg(feature = "decode")]
+pub fn decode<'a>(token: &str, input: &'a [u8]) -> Result<Cow<'a, [u8]>, crate::decode::Error> {
+    match Encoding::from_token(token) {
+        Some(encoding) => encoding.decode(input),
+        None if token.trim().eq_ignore_ascii_case("binary") => Ok(Cow::Borrowed(input)),
+        None => Err(crate::decode::Error::UnsupportedEncoding),
+    }
+}
+
 /// A borrowed `str` or `[u8]`
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum StrOrBytes<'a> {
@@ -0,0 +1,417 @@
+//! Quoted-printable email body decoder.
+//!
+//! Requires the `decode` feature.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use crate::decode::{self, Error};
+
+/// Decode a quoted-printable encoded body.
+///
+/// `=XX` escapes are decoded, in either case, and soft line breaks
+/// (`=` at the end of a line) are removed. Whitespace at the end of
+/// lines, which may have been added during transport, is removed too.
+/// Hard line breaks are kept as they are, whether `CRLF` or `LF`.
+///
+/// The input is borrowed if it doesn't need to be modified.
+///
+/// This is the same as decoding the whole input with a [`Decoder`]
+/// in [`Mode::Strict`].
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidEscape`] if an `=` isn't followed
+/// by two hexadecimal digits or by a line break, and [`Error::InvalidByte`]
+/// if `input` contains control chars other than tab and line breaks.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let decoded = email_encoding::body::quoted_printable::decode(
+///     b"Adri=C3=A1n, this line is so long that it had to be wrapped by a soft line=\r\n break.",
+/// )?;
+/// assert_eq!(
+///     *decoded,
+///     *"Adrián, this line is so long that it had to be wrapped by a soft line break.".as_bytes()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode(input: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    if is_unchanged(input) {
+        return Ok(Cow::Borrowed(input));
+    }
+
+    let mut decoded = Vec::with_capacity(input.len());
+    let mut decoder = Decoder::new(Mode::Strict);
+    decoder.decode_chunk(input, &mut decoded)?;
+    decoder.finish(&mut decoded)?;
+    Ok(Cow::Owned(decoded))
+}
+
+/// Decode a quoted-printable encoded body, recovering from errors.
+///
+/// Like [`decode()`], except that invalid escapes and control chars are
+/// kept as they are, like [`Mode::Lenient`] does. `on_error` is called
+/// with the position and the nature of every problem found in `input`.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::decode::Error;
+/// let mut errors = Vec::new();
+/// let decoded = email_encoding::body::quoted_printable::decode_lossy(
+///     b"100=25 =ZZ",
+///     |err| errors.push(err),
+/// );
+/// assert_eq!(*decoded, *b"100% =ZZ");
+/// assert_eq!(errors, [Error::InvalidEscape { position: 7 }]);
+/// ```
+pub fn decode_lossy(input: &[u8], mut on_error: impl FnMut(Error)) -> Cow<'_, [u8]> {
+    if is_unchanged(input) {
+        return Cow::Borrowed(input);
+    }
+
+    let mut decoded = Vec::with_capacity(input.len());
+    let mut decoder = Decoder::new(Mode::Lenient);
+    decoder
+        .step(input, true, &mut decoded, &mut on_error)
+        .expect("lenient decoding can't fail");
+    Cow::Owned(decoded)
+}
+
+/// Find out whether decoding `input` would leave it unchanged
+///
+/// That's the case if it doesn't contain any `=`, control char or 8-bit
+/// byte, and if no line ends with whitespace.
+fn is_unchanged(input: &[u8]) -> bool {
+    let ends_with_whitespace = |line: &[u8]| matches!(line.last(), Some(b' ' | b'\t'));
+
+    input
+        .iter()
+        .all(|&b| b != b'=' && (b.is_ascii_graphic() || matches!(b, b' ' | b'\t' | b'\r' | b'\n')))
+        && !ends_with_whitespace(input)
+        && memchr::memchr_iter(b'\n', input).all(|i| {
+            let line = &input[..i];
+            !ends_with_whitespace(line.strip_suffix(b"\r").unwrap_or(line))
+        })
+}
+
+/// How a [`Decoder`] handles invalid input.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Return an error
+    #[default]
+    Strict,
+    /// Keep invalid escapes and control chars as they are,
+    /// like most email clients do
+    ///
+    /// See [`decode_lossy`] for being notified about them.
+    Lenient,
+}
+
+/// Incremental quoted-printable decoder.
+///
+/// The input can be split into chunks anywhere, including in the
+/// middle of `=XX` escapes, soft line breaks and trailing whitespace:
+/// the bytes which can't be decoded yet are kept until the next chunk.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::body::quoted_printable::{Decoder, Mode};
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let mut decoded = Vec::new();
+/// let mut decoder = Decoder::new(Mode::Strict);
+/// for chunk in [&b"Adri=C"[..], b"3=A1n, soft =", b"\r", b"\nbreak"] {
+///     decoder.decode_chunk(chunk, &mut decoded)?;
+/// }
+/// decoder.finish(&mut decoded)?;
+///
+/// assert_eq!(decoded, "Adrián, soft break".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    mode: Mode,
+    /// Bytes which couldn't be decoded yet
+    pending: Vec<u8>,
+    /// Offset of `pending` in the whole input
+    position: usize,
+}
+
+impl Decoder {
+    /// Construct a new `Decoder`.
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Decode `chunk`, appending the decoded bytes to `output`.
+    ///
+    /// # Errors
+    ///
+    /// See [`decode()`]. Errors are only returned in [`Mode::Strict`],
+    /// and their positions are relative to the start of the whole input.
+    pub fn decode_chunk(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            let consumed = self.step(chunk, false, output, &mut |_| {})?;
+            self.pending.extend_from_slice(&chunk[consumed..]);
+        } else {
+            let mut input = core::mem::take(&mut self.pending);
+            input.extend_from_slice(chunk);
+            let consumed = self.step(&input, false, output, &mut |_| {})?;
+            input.drain(..consumed);
+            self.pending = input;
+        }
+
+        Ok(())
+    }
+
+    /// Decode the bytes left by the last chunk, which ends the input.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidEscape`] if the input ends with an
+    /// incomplete `=XX` escape, in [`Mode::Strict`].
+    pub fn finish(mut self, output: &mut Vec<u8>) -> Result<(), Error> {
+        let pending = core::mem::take(&mut self.pending);
+        self.step(&pending, true, output, &mut |_| {})?;
+        Ok(())
+    }
+
+    /// Decode as much of `input` as possible, returning how many bytes were consumed
+    ///
+    /// Errors are returned in [`Mode::Strict`], and passed to `on_error`
+    /// in [`Mode::Lenient`].
+    fn step(
+        &mut self,
+        input: &[u8],
+        at_end: bool,
+        output: &mut Vec<u8>,
+        on_error: &mut dyn FnMut(Error),
+    ) -> Result<usize, Error> {
+        let strict = self.mode == Mode::Strict;
+
+        let mut i = 0;
+        while i < input.len() {
+            match input[i] {
+                b'=' => {
+                    let rest = &input[i + 1..];
+                    match whitespace_then_line_break(rest, at_end) {
+                        Some(Some(len)) => {
+                            // Soft line break
+                            i += 1 + len;
+                            continue;
+                        }
+                        Some(None) => break,
+                        None => {}
+                    }
+
+                    if !at_end && rest.len() < 2 && rest.iter().all(u8::is_ascii_hexdigit) {
+                        // Incomplete escape
+                        break;
+                    }
+                    match decode::hex_pair(rest) {
+                        Some(byte) => {
+                            output.push(byte);
+                            i += 3;
+                        }
+                        None => {
+                            let err = Error::InvalidEscape {
+                                position: self.position + i,
+                            };
+                            if strict {
+                                return Err(err);
+                            }
+                            on_error(err);
+                            output.push(b'=');
+                            i += 1;
+                        }
+                    }
+                }
+                b' ' | b'\t' => {
+                    let rest = &input[i..];
+                    let whitespace_len = rest
+                        .iter()
+                        .take_while(|&&b| b == b' ' || b == b'\t')
+                        .count();
+                    match whitespace_then_line_break(rest, at_end) {
+                        // Drop the trailing whitespace, keep the line break
+                        Some(Some(_)) => i += whitespace_len,
+                        Some(None) => break,
+                        None => {
+                            output.extend_from_slice(&rest[..whitespace_len]);
+                            i += whitespace_len;
+                        }
+                    }
+                }
+                byte @ (b'\r' | b'\n') => {
+                    output.push(byte);
+                    i += 1;
+                }
+                byte if byte.is_ascii_control() || !byte.is_ascii() => {
+                    let err = Error::InvalidByte {
+                        position: self.position + i,
+                        byte,
+                    };
+                    if strict {
+                        return Err(err);
+                    }
+                    on_error(err);
+                    output.push(byte);
+                    i += 1;
+                }
+                byte => {
+                    output.push(byte);
+                    i += 1;
+                }
+            }
+        }
+
+        self.position += i;
+        Ok(i)
+    }
+}
+
+/// If `s` starts with optional whitespace followed by a line break or
+/// by the end of the input, get the length of both.
+///
+/// Returns `Some(None)` if that can't be known before the next chunk.
+fn whitespace_then_line_break(s: &[u8], at_end: bool) -> Option<Option<usize>> {
+    let whitespace_len = s.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+    let rest = &s[whitespace_len..];
+    if rest.is_empty() || rest == b"\r" {
+        if at_end {
+            // A trailing `\r` is kept as is
+            Some(Some(whitespace_len)).filter(|_| rest.is_empty())
+        } else {
+            Some(None)
+        }
+    } else if rest.starts_with(b"\r\n") {
+        Some(Some(whitespace_len + 2))
+    } else if rest.starts_with(b"\n") {
+        Some(Some(whitespace_len + 1))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        assert_eq!(*decode(b"a=3Db=3db").unwrap(), *b"a=b=b");
+        assert_eq!(*decode(b"=E2=82=AC").unwrap(), *"€".as_bytes());
+    }
+
+    #[test]
+    fn line_breaks() {
+        assert_eq!(
+            *decode(b"soft=\r\nbreak =  \r\nhard \t\r\nbreak\nlf  ").unwrap(),
+            *b"softbreak hard\r\nbreak\nlf"
+        );
+        assert_eq!(*decode(b"end=").unwrap(), *b"end");
+    }
+
+    #[test]
+    fn borrowed() {
+        for input in [&b""[..], b"Hello,\r\n world!\r\n", b"a\tb\nc \r", b"\r\n\n"] {
+            assert!(matches!(decode(input), Ok(Cow::Borrowed(b)) if b == input));
+        }
+        for input in [&b"a =\r\n"[..], b"a \r\n", b"a\t\n", b"a ", b"\x7f"] {
+            assert!(!matches!(decode(input), Ok(Cow::Borrowed(_))));
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(decode(b"a=ZZ"), Err(Error::InvalidEscape { position: 1 }));
+        assert_eq!(decode(b"a=A"), Err(Error::InvalidEscape { position: 1 }));
+        assert_eq!(decode(b"a= b"), Err(Error::InvalidEscape { position: 1 }));
+    }
+
+    #[test]
+    fn lenient() {
+        let mut decoded = Vec::new();
+        let mut decoder = Decoder::new(Mode::Lenient);
+        decoder
+            .decode_chunk(b"a=ZZ b= c \x01=", &mut decoded)
+            .unwrap();
+        decoder.finish(&mut decoded).unwrap();
+        assert_eq!(decoded, b"a=ZZ b= c \x01");
+    }
+
+    #[test]
+    fn chunks() {
+        let input = b"Adri=C3=A1n   \r\nsoft =  \r\nbreak=3D =\nend  ";
+        let expected = decode(input).unwrap();
+        assert_eq!(*expected, *"Adrián\r\nsoft break= end".as_bytes());
+
+        for chunk_len in 1..input.len() {
+            let mut decoded = Vec::new();
+            let mut decoder = Decoder::new(Mode::Strict);
+            for chunk in input.chunks(chunk_len) {
+                decoder.decode_chunk(chunk, &mut decoded).unwrap();
+            }
+            decoder.finish(&mut decoded).unwrap();
+            assert_eq!(decoded, *expected, "chunks of {} bytes", chunk_len);
+        }
+    }
+
+    #[test]
+    fn chunk_error_position() {
+        let mut decoded = Vec::new();
+        let mut decoder = Decoder::new(Mode::Strict);
+        decoder.decode_chunk(b"abc=4", &mut decoded).unwrap();
+        assert_eq!(
+            decoder.decode_chunk(b"Z", &mut decoded),
+            Err(Error::InvalidEscape { position: 3 })
+        );
+
+        let mut decoder = Decoder::new(Mode::Strict);
+        decoder.decode_chunk(b"abc=4", &mut decoded).unwrap();
+        assert_eq!(
+            decoder.finish(&mut decoded),
+            Err(Error::InvalidEscape { position: 3 })
+        );
+    }
+
+    #[test]
+    fn lossy() {
+        let mut errors = Vec::new();
+        let decoded = decode_lossy(b"a=ZZ b=\x01 c\xff=4", |err| errors.push(err));
+        assert_eq!(*decoded, *b"a=ZZ b=\x01 c\xff=4");
+        assert_eq!(
+            errors,
+            [
+                Error::InvalidEscape { position: 1 },
+                Error::InvalidEscape { position: 6 },
+                Error::InvalidByte {
+                    position: 7,
+                    byte: 0x01
+                },
+                Error::InvalidByte {
+                    position: 10,
+                    byte: 0xff
+                },
+                Error::InvalidEscape { position: 11 },
+            ]
+        );
+    }
+}
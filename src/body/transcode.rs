@@ -0,0 +1,782 @@
+//! Streaming `Content-Transfer-Encoding` transcoder.
+//!
+//! Requires the `decode` feature.
+
+use alloc::vec::Vec;
+use core::{fmt, mem};
+
+use super::{quoted_printable, Encoding};
+use crate::{base64_engine, decode, headers::HARD_LINE_LIMIT};
+
+/// Maximum length of a quoted-printable line, without its line break
+const QUOTED_PRINTABLE_LINE_LEN: usize = 76;
+/// Number of bytes encoded into a full base64 line
+const BASE64_LINE_INPUT_LEN: usize = 76 / 4 * 3;
+
+/// Transcode a whole body from `from` to `to`.
+///
+/// This is the same as transforming the whole input with a [`Transcoder`].
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// See [`Transcoder::transcode_chunk`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::body::{transcode::transcode, Encoding};
+/// # fn main() -> Result<(), email_encoding::body::transcode::Error> {
+/// let transcoded = transcode(
+///     "Adrián\r\n".as_bytes(),
+///     Encoding::EightBit,
+///     Encoding::QuotedPrintable,
+/// )?;
+/// assert_eq!(transcoded, b"Adri=C3=A1n\r\n");
+///
+/// let transcoded = transcode(b"QWRyacOhbg==", Encoding::Base64, Encoding::QuotedPrintable)?;
+/// assert_eq!(transcoded, b"Adri=C3=A1n");
+/// # Ok(())
+/// # }
+/// ```
+pub fn transcode(input: &[u8], from: Encoding, to: Encoding) -> Result<Vec<u8>, Error> {
+    let mut transcoded = Vec::with_capacity(input.len());
+    let mut transcoder = Transcoder::new(from, to);
+    transcoder.transcode_chunk(input, &mut transcoded)?;
+    transcoder.finish(&mut transcoded)?;
+    Ok(transcoded)
+}
+
+/// Incremental `Content-Transfer-Encoding` transcoder.
+///
+/// Decodes a body encoded with one [`Encoding`] and encodes it with
+/// another one in a single pass, without buffering the whole body.
+/// The input can be split into chunks anywhere.
+///
+/// Bodies are decoded like [`Encoding::decode`] does, with
+/// [`quoted_printable::Mode::Strict`]. When encoding to `quoted-printable`,
+/// `CRLF` line breaks are kept as hard line breaks, while lone `CR` and
+/// `LF` are escaped, so that the decoded body doesn't change, unless
+/// [strict mode](Transcoder::set_strict) is enabled. Lines longer
+/// than 76 chars are wrapped with soft line breaks. When encoding to
+/// `base64`, the output is split into lines of 76 chars, like
+/// [`base64::encode`] does.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::body::{transcode::Transcoder, Encoding};
+/// # fn main() -> Result<(), email_encoding::body::transcode::Error> {
+/// let mut transcoded = Vec::new();
+/// let mut transcoder = Transcoder::new(Encoding::QuotedPrintable, Encoding::Base64);
+/// for chunk in [&b"Hello=2"[..], b"C world=", b"\r\n!"] {
+///     transcoder.transcode_chunk(chunk, &mut transcoded)?;
+/// }
+/// transcoder.finish(&mut transcoded)?;
+///
+/// assert_eq!(transcoded, b"SGVsbG8sIHdvcmxkIQ==");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`base64::encode`]: super::base64::encode
+#[derive(Debug, Clone)]
+pub struct Transcoder {
+    decoder: Decoder,
+    encoder: Encoder,
+    /// Decoded bytes, waiting to be encoded
+    scratch: Vec<u8>,
+}
+
+impl Transcoder {
+    /// Construct a new `Transcoder` from `from` to `to`.
+    pub fn new(from: Encoding, to: Encoding) -> Self {
+        let decoder = match from {
+            Encoding::SevenBit | Encoding::EightBit => Decoder::Identity,
+            Encoding::QuotedPrintable => Decoder::QuotedPrintable(quoted_printable::Decoder::new(
+                quoted_printable::Mode::Strict,
+            )),
+            Encoding::Base64 => Decoder::Base64(Base64Decoder::default()),
+        };
+        let kind = match to {
+            Encoding::SevenBit => EncoderKind::SevenBit,
+            Encoding::EightBit => EncoderKind::EightBit,
+            Encoding::QuotedPrintable => {
+                EncoderKind::QuotedPrintable(QuotedPrintableEncoder::default())
+            }
+            Encoding::Base64 => EncoderKind::Base64(Base64Encoder::default()),
+        };
+
+        Self {
+            decoder,
+            encoder: Encoder {
+                kind,
+                position: 0,
+                lines: None,
+            },
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Set whether the decoded body must be canonical.
+    ///
+    /// By default lone `CR`s and `LF`s are kept as they are, or escaped
+    /// when encoding to `quoted-printable`. When `strict` is `true`,
+    /// they make transcoding fail with [`Error::LineBreak`], unless
+    /// encoding to `base64`, and lines longer than [`HARD_LINE_LIMIT`]
+    /// bytes make encoding to `7bit` or `8bit` fail with
+    /// [`Error::LineTooLong`].
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::body::{transcode::{Error, Transcoder}, Encoding};
+    /// let mut transcoded = Vec::new();
+    /// let mut transcoder = Transcoder::new(Encoding::Base64, Encoding::QuotedPrintable);
+    /// transcoder.set_strict(true);
+    ///
+    /// // `Hello\nworld`
+    /// assert_eq!(
+    ///     transcoder.transcode_chunk(b"SGVsbG8Kd29ybGQ=", &mut transcoded),
+    ///     Err(Error::LineBreak { position: 5 })
+    /// );
+    /// ```
+    ///
+    /// [`HARD_LINE_LIMIT`]: crate::headers::HARD_LINE_LIMIT
+    pub fn set_strict(&mut self, strict: bool) {
+        self.encoder.lines = match (strict, &self.encoder.kind) {
+            (false, _) | (true, EncoderKind::Base64(_)) => None,
+            (true, kind) => Some(LineChecker {
+                limit_line_len: !matches!(kind, EncoderKind::QuotedPrintable(_)),
+                line_len: 0,
+                pending_cr: false,
+            }),
+        };
+    }
+
+    /// Transcode `chunk`, appending the encoded bytes to `output`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if `chunk` can't be decoded, and
+    /// [`Error::Unencodable`] if the decoded body contains a byte which
+    /// can't be represented by a `7bit` or `8bit` body. In strict mode,
+    /// also returns [`Error::LineBreak`] and [`Error::LineTooLong`], see
+    /// [`Transcoder::set_strict`].
+    pub fn transcode_chunk(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        match &mut self.decoder {
+            Decoder::Identity => return self.encoder.encode(chunk, output),
+            Decoder::QuotedPrintable(decoder) => decoder.decode_chunk(chunk, &mut self.scratch)?,
+            Decoder::Base64(decoder) => decoder.decode_chunk(chunk, &mut self.scratch)?,
+        }
+
+        let result = self.encoder.encode(&self.scratch, output);
+        self.scratch.clear();
+        result
+    }
+
+    /// Transcode the rest of the input, appending the encoded bytes to
+    /// `output`.
+    ///
+    /// # Errors
+    ///
+    /// See [`Transcoder::transcode_chunk`].
+    pub fn finish(mut self, output: &mut Vec<u8>) -> Result<(), Error> {
+        match self.decoder {
+            Decoder::Identity => {}
+            Decoder::QuotedPrintable(decoder) => decoder.finish(&mut self.scratch)?,
+            Decoder::Base64(decoder) => decoder.finish(&mut self.scratch)?,
+        }
+
+        self.encoder.encode(&self.scratch, output)?;
+        self.encoder.finish(output)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Decoder {
+    /// `7bit` and `8bit` bodies
+    Identity,
+    QuotedPrintable(quoted_printable::Decoder),
+    Base64(Base64Decoder),
+}
+
+/// Incremental base64 decoder, accepting the same input as
+/// [`base64::decode`](super::base64::decode)
+#[derive(Debug, Clone, Default)]
+struct Base64Decoder {
+    /// Sextets of the current quantum
+    quantum: [u8; 4],
+    len: usize,
+    /// Offset of the first `=`, if the padding was reached
+    padding: Option<usize>,
+    /// Offset of the next byte in the whole input
+    position: usize,
+}
+
+impl Base64Decoder {
+    fn decode_chunk(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> Result<(), decode::Error> {
+        for &byte in chunk {
+            let position = self.position;
+            self.position += 1;
+
+            match (byte, self.padding) {
+                (b' ' | b'\t' | b'\r' | b'\n', _) => {}
+                (b'=', Some(_)) => {}
+                (b'=', None) => {
+                    if self.len < 2 {
+                        return Err(decode::Error::InvalidPadding { position });
+                    }
+                    self.padding = Some(position);
+                    self.flush(output);
+                }
+                (_, Some(padding)) if base64_sextet(byte).is_some() => {
+                    return Err(decode::Error::InvalidPadding { position: padding });
+                }
+                _ => {
+                    let sextet =
+                        base64_sextet(byte).ok_or(decode::Error::InvalidByte { position, byte })?;
+                    self.quantum[self.len] = sextet;
+                    self.len += 1;
+                    if self.len == 4 {
+                        self.flush(output);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self, output: &mut Vec<u8>) -> Result<(), decode::Error> {
+        if self.len == 1 {
+            return Err(decode::Error::InvalidLength);
+        }
+        self.flush(output);
+        Ok(())
+    }
+
+    /// Decode the current, possibly incomplete, quantum
+    fn flush(&mut self, output: &mut Vec<u8>) {
+        let [a, b, c, d] = self.quantum;
+        let bytes = [a << 2 | b >> 4, b << 4 | c >> 2, c << 6 | d];
+        output.extend_from_slice(&bytes[..self.len.saturating_sub(1)]);
+        self.quantum = [0; 4];
+        self.len = 0;
+    }
+}
+
+fn base64_sextet(byte: u8) -> Option<u8> {
+    match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Encoder {
+    kind: EncoderKind,
+    /// Offset of the next byte in the whole decoded body
+    position: usize,
+    /// Set in strict mode
+    lines: Option<LineChecker>,
+}
+
+#[derive(Debug, Clone)]
+enum EncoderKind {
+    SevenBit,
+    EightBit,
+    QuotedPrintable(QuotedPrintableEncoder),
+    Base64(Base64Encoder),
+}
+
+impl Encoder {
+    fn encode(&mut self, decoded: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        let position = self.position;
+        self.position += decoded.len();
+        if let Some(lines) = &mut self.lines {
+            lines.check(decoded, position)?;
+        }
+
+        match &mut self.kind {
+            EncoderKind::SevenBit | EncoderKind::EightBit => {
+                let seven_bit = matches!(self.kind, EncoderKind::SevenBit);
+                if let Some(i) = decoded
+                    .iter()
+                    .position(|&b| b == b'\0' || (seven_bit && !b.is_ascii()))
+                {
+                    return Err(Error::Unencodable {
+                        position: position + i,
+                        byte: decoded[i],
+                    });
+                }
+                output.extend_from_slice(decoded);
+            }
+            EncoderKind::QuotedPrintable(encoder) => {
+                for &byte in decoded {
+                    encoder.push(byte, output);
+                }
+            }
+            EncoderKind::Base64(encoder) => encoder.encode(decoded, output),
+        }
+
+        Ok(())
+    }
+
+    fn finish(self, output: &mut Vec<u8>) -> Result<(), Error> {
+        if let Some(lines) = &self.lines {
+            lines.finish(self.position)?;
+        }
+
+        match self.kind {
+            EncoderKind::SevenBit | EncoderKind::EightBit => {}
+            EncoderKind::QuotedPrintable(encoder) => encoder.finish(output),
+            EncoderKind::Base64(encoder) => encoder.finish(output),
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the decoded body only contains `CRLF` line breaks, and
+/// optionally that its lines aren't longer than [`HARD_LINE_LIMIT`] bytes
+#[derive(Debug, Clone)]
+struct LineChecker {
+    limit_line_len: bool,
+    line_len: usize,
+    /// Whether a `CR`, which must start a line break, was checked last
+    pending_cr: bool,
+}
+
+impl LineChecker {
+    fn check(&mut self, decoded: &[u8], position: usize) -> Result<(), Error> {
+        for (i, &byte) in decoded.iter().enumerate() {
+            let position = position + i;
+            match (byte, mem::take(&mut self.pending_cr)) {
+                (b'\n', true) => self.line_len = 0,
+                (_, true) => {
+                    return Err(Error::LineBreak {
+                        position: position - 1,
+                    })
+                }
+                (b'\r', false) => self.pending_cr = true,
+                (b'\n', false) => return Err(Error::LineBreak { position }),
+                _ => {
+                    self.line_len += 1;
+                    if self.limit_line_len && self.line_len > HARD_LINE_LIMIT {
+                        return Err(Error::LineTooLong {
+                            position: position + 1 - self.line_len,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&self, position: usize) -> Result<(), Error> {
+        if self.pending_cr {
+            return Err(Error::LineBreak {
+                position: position - 1,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Incremental quoted-printable encoder
+#[derive(Debug, Clone, Default)]
+struct QuotedPrintableEncoder {
+    line_len: usize,
+    /// Whitespace which must be escaped if it ends the line
+    pending_whitespace: Option<u8>,
+    /// Whether a `CR`, which may start a line break, was pushed last
+    pending_cr: bool,
+}
+
+impl QuotedPrintableEncoder {
+    fn push(&mut self, byte: u8, output: &mut Vec<u8>) {
+        if mem::take(&mut self.pending_cr) {
+            if byte == b'\n' {
+                // Hard line break
+                if let Some(whitespace) = self.pending_whitespace.take() {
+                    self.write_escaped(whitespace, output);
+                }
+                output.extend_from_slice(b"\r\n");
+                self.line_len = 0;
+                return;
+            }
+
+            self.flush_whitespace(output);
+            self.write_escaped(b'\r', output);
+        }
+
+        match byte {
+            b'\r' => self.pending_cr = true,
+            b' ' | b'\t' => {
+                self.flush_whitespace(output);
+                self.pending_whitespace = Some(byte);
+            }
+            b'!'..=b'<' | b'>'..=b'~' => {
+                self.flush_whitespace(output);
+                self.write(&[byte], output);
+            }
+            _ => {
+                self.flush_whitespace(output);
+                self.write_escaped(byte, output);
+            }
+        }
+    }
+
+    fn finish(mut self, output: &mut Vec<u8>) {
+        if self.pending_cr {
+            self.flush_whitespace(output);
+            self.write_escaped(b'\r', output);
+        } else if let Some(whitespace) = self.pending_whitespace.take() {
+            // Whitespace ending the body
+            self.write_escaped(whitespace, output);
+        }
+    }
+
+    /// Write the pending whitespace as is, since it doesn't end the line
+    fn flush_whitespace(&mut self, output: &mut Vec<u8>) {
+        if let Some(whitespace) = self.pending_whitespace.take() {
+            self.write(&[whitespace], output);
+        }
+    }
+
+    fn write_escaped(&mut self, byte: u8, output: &mut Vec<u8>) {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+        self.write(
+            &[
+                b'=',
+                HEX[usize::from(byte >> 4)],
+                HEX[usize::from(byte & 0xf)],
+            ],
+            output,
+        );
+    }
+
+    fn write(&mut self, token: &[u8], output: &mut Vec<u8>) {
+        // Keep room for the `=` of the soft line break
+        if self.line_len + token.len() > QUOTED_PRINTABLE_LINE_LEN - 1 {
+            output.extend_from_slice(b"=\r\n");
+            self.line_len = 0;
+        }
+        output.extend_from_slice(token);
+        self.line_len += token.len();
+    }
+}
+
+/// Incremental base64 encoder, producing the same output as
+/// [`base64::encode`](super::base64::encode)
+#[derive(Debug, Clone, Default)]
+struct Base64Encoder {
+    /// Bytes which don't fill a line yet
+    pending: Vec<u8>,
+    wrote_line: bool,
+}
+
+impl Base64Encoder {
+    fn encode(&mut self, mut decoded: &[u8], output: &mut Vec<u8>) {
+        while !decoded.is_empty() {
+            let len = (BASE64_LINE_INPUT_LEN - self.pending.len()).min(decoded.len());
+            self.pending.extend_from_slice(&decoded[..len]);
+            decoded = &decoded[len..];
+
+            // The last line is only written once the input is over,
+            // since it mustn't be followed by a line break
+            if self.pending.len() == BASE64_LINE_INPUT_LEN && !decoded.is_empty() {
+                self.write_line(output);
+            }
+        }
+    }
+
+    fn finish(mut self, output: &mut Vec<u8>) {
+        if !self.pending.is_empty() {
+            self.write_line(output);
+        }
+    }
+
+    fn write_line(&mut self, output: &mut Vec<u8>) {
+        if self.wrote_line {
+            output.extend_from_slice(b"\r\n");
+        }
+
+        let mut buf = [0; BASE64_LINE_INPUT_LEN / 3 * 4];
+        let len = base64_engine::encode_slice(&self.pending, &mut buf)
+            .expect("base64 output `buf` is not big enough");
+        output.extend_from_slice(&buf[..len]);
+
+        self.pending.clear();
+        self.wrote_line = true;
+    }
+}
+
+/// Error returned by [`Transcoder`] and [`transcode`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The input couldn't be decoded
+    Decode(decode::Error),
+    /// The decoded body contains a byte which can't be represented by the
+    /// target encoding: `NUL`, or an 8-bit byte in a `7bit` body
+    Unencodable {
+        /// Byte offset of the byte in the decoded body
+        position: usize,
+        /// The byte
+        byte: u8,
+    },
+    /// The decoded body contains a `CR` or `LF` which isn't part of a
+    /// `CRLF` line break, in strict mode
+    LineBreak {
+        /// Byte offset of the `CR` or `LF` in the decoded body
+        position: usize,
+    },
+    /// The decoded body contains a line longer than [`HARD_LINE_LIMIT`]
+    /// bytes, which can't be represented by a `7bit` or `8bit` body,
+    /// in strict mode
+    ///
+    /// [`HARD_LINE_LIMIT`]: crate::headers::HARD_LINE_LIMIT
+    LineTooLong {
+        /// Byte offset of the start of the line in the decoded body
+        position: usize,
+    },
+}
+
+impl From<decode::Error> for Error {
+    fn from(err: decode::Error) -> Self {
+        Self::Decode(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(err) => err.fmt(f),
+            Self::Unencodable { position, byte } => write!(
+                f,
+                "byte {:#04x} at byte {} of the decoded body can't be encoded",
+                byte, position
+            ),
+            Self::LineBreak { position } => write!(
+                f,
+                "line break at byte {} of the decoded body isn't CRLF",
+                position
+            ),
+            Self::LineTooLong { position } => write!(
+                f,
+                "line at byte {} of the decoded body is longer than {} bytes",
+                position, HARD_LINE_LIMIT
+            ),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String};
+
+    use pretty_assertions::assert_eq;
+
+    use core::str;
+
+    use super::*;
+    use crate::body::base64;
+
+    #[test]
+    fn quoted_printable() {
+        let transcoded = transcode(
+            "tab\t \r\nend \r\ncr\r lf\n=".as_bytes(),
+            Encoding::EightBit,
+            Encoding::QuotedPrintable,
+        )
+        .unwrap();
+        assert_eq!(
+            str::from_utf8(&transcoded).unwrap(),
+            "tab\t=20\r\nend=20\r\ncr=0D lf=0A=3D"
+        );
+
+        let transcoded = transcode(b"a ", Encoding::SevenBit, Encoding::QuotedPrintable).unwrap();
+        assert_eq!(transcoded, b"a=20");
+    }
+
+    #[test]
+    fn quoted_printable_long_lines() {
+        let input = format!("{}\r\n{}", "a".repeat(80), "é".repeat(40));
+        let transcoded = transcode(
+            input.as_bytes(),
+            Encoding::EightBit,
+            Encoding::QuotedPrintable,
+        )
+        .unwrap();
+        let transcoded = str::from_utf8(&transcoded).unwrap();
+        assert!(transcoded.split("\r\n").all(|line| line.len() <= 76));
+        assert_eq!(
+            quoted_printable::decode(transcoded.as_bytes()).unwrap(),
+            input.as_bytes()
+        );
+    }
+
+    #[test]
+    fn base64() {
+        let input = (0..=255).cycle().take(500).collect::<Vec<u8>>();
+        let mut expected = String::new();
+        base64::encode(&input, &mut expected).unwrap();
+
+        let transcoded = transcode(&input, Encoding::EightBit, Encoding::Base64).unwrap();
+        assert_eq!(str::from_utf8(&transcoded).unwrap(), expected);
+
+        let transcoded = transcode(&transcoded, Encoding::Base64, Encoding::EightBit).unwrap_err();
+        assert_eq!(
+            transcoded,
+            Error::Unencodable {
+                position: 0,
+                byte: 0
+            }
+        );
+        let transcoded = transcode(
+            expected.as_bytes(),
+            Encoding::Base64,
+            Encoding::QuotedPrintable,
+        )
+        .unwrap();
+        assert_eq!(quoted_printable::decode(&transcoded).unwrap(), input);
+    }
+
+    #[test]
+    fn chunks() {
+        let input = b"Adri=C3=A1n  \r\nsoft =\r\nbreak=3D\r\nend \r\n";
+        for (from, to) in [
+            (Encoding::QuotedPrintable, Encoding::Base64),
+            (Encoding::QuotedPrintable, Encoding::QuotedPrintable),
+            (Encoding::QuotedPrintable, Encoding::EightBit),
+        ] {
+            let expected = transcode(input, from, to).unwrap();
+            for chunk_len in 1..input.len() {
+                let mut transcoded = Vec::new();
+                let mut transcoder = Transcoder::new(from, to);
+                for chunk in input.chunks(chunk_len) {
+                    transcoder.transcode_chunk(chunk, &mut transcoded).unwrap();
+                }
+                transcoder.finish(&mut transcoded).unwrap();
+                assert_eq!(transcoded, expected, "chunks of {} bytes", chunk_len);
+            }
+        }
+
+        let base64 = transcode(input, Encoding::QuotedPrintable, Encoding::Base64).unwrap();
+        for chunk_len in 1..base64.len() {
+            let mut transcoded = Vec::new();
+            let mut transcoder = Transcoder::new(Encoding::Base64, Encoding::EightBit);
+            for chunk in base64.chunks(chunk_len) {
+                transcoder.transcode_chunk(chunk, &mut transcoded).unwrap();
+            }
+            transcoder.finish(&mut transcoded).unwrap();
+            assert_eq!(
+                transcoded,
+                "Adrián\r\nsoft break=\r\nend\r\n".as_bytes(),
+                "chunks of {} bytes",
+                chunk_len
+            );
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            transcode(b"a=ZZ", Encoding::QuotedPrintable, Encoding::Base64),
+            Err(Error::Decode(decode::Error::InvalidEscape { position: 1 }))
+        );
+        assert_eq!(
+            transcode(b"QUJD\r\nR!", Encoding::Base64, Encoding::QuotedPrintable),
+            Err(Error::Decode(decode::Error::InvalidByte {
+                position: 7,
+                byte: b'!'
+            }))
+        );
+        assert_eq!(
+            transcode(b"QQ==QQ", Encoding::Base64, Encoding::QuotedPrintable),
+            Err(Error::Decode(decode::Error::InvalidPadding { position: 2 }))
+        );
+        assert_eq!(
+            transcode(b"QUJDR", Encoding::Base64, Encoding::QuotedPrintable),
+            Err(Error::Decode(decode::Error::InvalidLength))
+        );
+        assert_eq!(
+            transcode("aé".as_bytes(), Encoding::EightBit, Encoding::SevenBit),
+            Err(Error::Unencodable {
+                position: 1,
+                byte: 0xc3
+            })
+        );
+    }
+
+    fn transcode_strict(input: &[u8], from: Encoding, to: Encoding) -> Result<Vec<u8>, Error> {
+        let mut transcoded = Vec::new();
+        let mut transcoder = Transcoder::new(from, to);
+        transcoder.set_strict(true);
+        for chunk in input.chunks(7) {
+            transcoder.transcode_chunk(chunk, &mut transcoded)?;
+        }
+        transcoder.finish(&mut transcoded)?;
+        Ok(transcoded)
+    }
+
+    #[test]
+    fn strict() {
+        let input = b"a=\r\nb\r\nc\r\n";
+        assert_eq!(
+            transcode_strict(input, Encoding::QuotedPrintable, Encoding::SevenBit),
+            Ok(b"ab\r\nc\r\n".to_vec())
+        );
+
+        for to in [Encoding::SevenBit, Encoding::QuotedPrintable] {
+            assert_eq!(
+                transcode_strict(b"ab\r\nc\nd", Encoding::EightBit, to),
+                Err(Error::LineBreak { position: 5 })
+            );
+            assert_eq!(
+                transcode_strict(b"ab\r\rc", Encoding::EightBit, to),
+                Err(Error::LineBreak { position: 2 })
+            );
+            assert_eq!(
+                transcode_strict(b"ab\r", Encoding::EightBit, to),
+                Err(Error::LineBreak { position: 2 })
+            );
+        }
+        assert_eq!(
+            transcode_strict(b"ab\r", Encoding::EightBit, Encoding::Base64),
+            Ok(b"YWIN".to_vec())
+        );
+
+        let long = format!("ab\r\n{}", "a".repeat(HARD_LINE_LIMIT + 1));
+        assert_eq!(
+            transcode_strict(long.as_bytes(), Encoding::EightBit, Encoding::EightBit),
+            Err(Error::LineTooLong { position: 4 })
+        );
+        assert!(transcode_strict(
+            long.as_bytes(),
+            Encoding::EightBit,
+            Encoding::QuotedPrintable
+        )
+        .is_ok());
+        assert!(transcode_strict(
+            &long.as_bytes()[..long.len() - 1],
+            Encoding::EightBit,
+            Encoding::EightBit
+        )
+        .is_ok());
+    }
+}
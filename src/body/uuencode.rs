@@ -0,0 +1,242 @@
+//! uuencode decoder.
+//!
+//! Requires the `decode` feature.
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::str;
+
+use crate::decode::Error;
+
+/// A file decoded by [`decode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    /// Unix permissions of the file, from the `begin` line
+    pub mode: u32,
+    /// Name of the file, from the `begin` line
+    pub name: String,
+    /// Content of the file
+    pub data: Vec<u8>,
+}
+
+/// Decode a uuencoded body.
+///
+/// The text preceding the `begin <mode> <name>` line is skipped, and so
+/// is the text following the `end` line. Lines may end with `CRLF` or `LF`.
+/// Trailing whitespace is ignored: lines shortened by the removal of their
+/// trailing spaces, which encode zeros, are accepted, and so are the
+/// extra chars some encoders add at the end of lines. Both `` ` `` and space
+/// are accepted as zeros.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidLine`] if the `begin` line is missing or invalid,
+/// or if the data isn't followed by an empty line and the `end` line, and
+/// [`Error::InvalidByte`] if a line contains a char which isn't part
+/// of the encoding.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let file = email_encoding::body::uuencode::decode(
+///     b"begin 644 hello.txt\r\n.2&5L;&\\L('=O<FQD(0H`\r\n`\r\nend\r\n",
+/// )?;
+/// assert_eq!(file.mode, 0o644);
+/// assert_eq!(file.name, "hello.txt");
+/// assert_eq!(file.data, b"Hello, world!\n");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::InvalidLine`]: crate::decode::Error::InvalidLine
+/// [`Error::InvalidByte`]: crate::decode::Error::InvalidByte
+pub fn decode(input: &[u8]) -> Result<File, Error> {
+    let mut lines = Lines { input, pos: 0 };
+
+    let (mode, name) = loop {
+        let (position, line) = lines.next().ok_or(Error::InvalidLine {
+            position: input.len(),
+        })?;
+        if let Some(begin) = line.strip_prefix(b"begin ") {
+            break parse_begin(begin).ok_or(Error::InvalidLine { position })?;
+        }
+    };
+
+    let mut data = Vec::new();
+    loop {
+        let (position, line) = lines.next().ok_or(Error::InvalidLine {
+            position: input.len(),
+        })?;
+        let (&len, chars) = match trim_end(line).split_first() {
+            Some(split) => split,
+            // The space encoding the length of the last line was removed
+            None => break,
+        };
+        let len = usize::from(decode_char(len).ok_or(Error::InvalidByte {
+            position,
+            byte: len,
+        })?);
+        if len == 0 {
+            break;
+        }
+
+        let start = data.len();
+        for (i, quantum) in chars.chunks(4).take((len + 2) / 3).enumerate() {
+            let mut sextets = [0; 4];
+            for (j, (sextet, &c)) in sextets.iter_mut().zip(quantum).enumerate() {
+                *sextet = decode_char(c).ok_or(Error::InvalidByte {
+                    position: position + 1 + i * 4 + j,
+                    byte: c,
+                })?;
+            }
+            data.extend_from_slice(&[
+                sextets[0] << 2 | sextets[1] >> 4,
+                sextets[1] << 4 | sextets[2] >> 2,
+                sextets[2] << 6 | sextets[3],
+            ]);
+        }
+        // Lines shortened by the removal of their trailing spaces
+        data.resize(start + len, 0);
+    }
+
+    match lines.next() {
+        Some((_, line)) if trim_end(line) == b"end" => Ok(File { mode, name, data }),
+        Some((position, _)) => Err(Error::InvalidLine { position }),
+        None => Err(Error::InvalidLine {
+            position: input.len(),
+        }),
+    }
+}
+
+/// Parse the `<mode> <name>` following `begin `
+fn parse_begin(begin: &[u8]) -> Option<(u32, String)> {
+    let begin = str::from_utf8(begin).ok()?.trim();
+    let (mode, name) = begin.split_once(' ')?;
+    let mode = u32::from_str_radix(mode, 8).ok()?;
+    let name = name.trim_start();
+    if name.is_empty() {
+        return None;
+    }
+    Some((mode, name.to_owned()))
+}
+
+/// Decode a char of the encoding, either `` ` `` or a char between
+/// space and `_`, into 6 bits
+fn decode_char(c: u8) -> Option<u8> {
+    match c {
+        b' '..=b'`' => Some((c - b' ') & 0x3f),
+        _ => None,
+    }
+}
+
+fn trim_end(line: &[u8]) -> &[u8] {
+    let len = line
+        .iter()
+        .rev()
+        .take_while(|b| b.is_ascii_whitespace())
+        .count();
+    &line[..line.len() - len]
+}
+
+/// Iterator over the lines of the input and their offsets, without
+/// their line breaks
+struct Lines<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (usize, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.pos;
+        let rest = &self.input[position..];
+        if rest.is_empty() {
+            return None;
+        }
+
+        let line = match memchr::memchr(b'\n', rest) {
+            Some(i) => {
+                self.pos += i + 1;
+                &rest[..i]
+            }
+            None => {
+                self.pos = self.input.len();
+                rest
+            }
+        };
+        Some((position, line.strip_suffix(b"\r").unwrap_or(line)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn full_lines() {
+        let input = concat!(
+            "Some text before\n",
+            "\n",
+            "begin 600 my file.bin\n",
+            "M,#$R,S0U-C<X.3`Q,C,T-38W.#DP,3(S-#4V-S@Y,#$R,S0U-C<X.3`Q,C,T\n",
+            "%-38W.#D`\n",
+            "`\n",
+            "end\n",
+            "Some text after\n",
+        );
+        let file = decode(input.as_bytes()).unwrap();
+        assert_eq!(file.mode, 0o600);
+        assert_eq!(file.name, "my file.bin");
+        assert_eq!(
+            file.data,
+            b"01234567890123456789012345678901234567890123456789".to_vec()
+        );
+    }
+
+    #[test]
+    fn whitespace() {
+        // Trailing spaces, encoding zeros, were removed
+        let file = decode(b"begin 644 a\r\n#80  \r\n \r\nend").unwrap();
+        assert_eq!(file.data, b"a\0\0");
+        let file = decode(b"begin 644 a\r\n#80\t\r\n`\r\nend \r\n").unwrap();
+        assert_eq!(file.data, b"a\0\0");
+    }
+
+    #[test]
+    fn empty() {
+        let file = decode(b"begin 644 empty\n`\nend\n").unwrap();
+        assert_eq!(file.data, b"");
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            decode(b"no begin\n"),
+            Err(Error::InvalidLine { position: 9 })
+        );
+        assert_eq!(
+            decode(b"begin 999 a\n`\nend\n"),
+            Err(Error::InvalidLine { position: 0 })
+        );
+        assert_eq!(
+            decode(b"begin 644 a\n!8~\n`\nend\n"),
+            Err(Error::InvalidByte {
+                position: 14,
+                byte: b'~'
+            })
+        );
+        assert_eq!(
+            decode(b"begin 644 a\n!80\n`\n"),
+            Err(Error::InvalidLine { position: 18 })
+        );
+        assert_eq!(
+            decode(b"begin 644 a\n!80\n`\nnot end\n"),
+            Err(Error::InvalidLine { position: 18 })
+        );
+    }
+}
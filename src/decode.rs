@@ -0,0 +1,66 @@
+//! Shared decoding utilities and error type.
+//!
+//! The decoders live next to their encoders:
+//!
+//! * [`body::base64::decode`](crate::body::base64::decode)
+//! * [`body::quoted_printable::decode`](crate::body::quoted_printable::decode)
+//! * [`body::uuencode::decode`](crate::body::uuencode::decode)
+//! * [`headers::rfc2047::decode`](crate::headers::rfc2047::decode)
+//! * [`headers::rfc2047::decode_q`](crate::headers::rfc2047::decode_q)
+//! * [`headers::rfc2231::decode`](crate::headers::rfc2231::decode)
+//! * [`headers::quoted_string::decode`](crate::headers::quoted_string::decode)
+//!
+//! [`headers::block::split`](crate::headers::block::split) splits the
+//! header section of a message into fields to be fed to them, and
+//! [`headers::params::parse`](crate::headers::params::parse) splits
+//! parameterized values into their parameters.
+//!
+//! They all return the same [`Error`] type. Each of them has a lossy
+//! counterpart, such as [`rfc2047::decode_lossy`](crate::headers::rfc2047::decode_lossy),
+//! which recovers from invalid input and reports every problem it found
+//! to a callback instead.
+//!
+//! [`Limits`] bound the resources spent by some decoders on untrusted
+//! input.
+//!
+//! Requires the `decode` feature.
+
+pub(crate) use crate::headers::encoded_words::{
+    base64_lossy, charset_to_string, hex_pair, q_lossy, resolve_charset, strict,
+};
+pub use crate::headers::encoded_words::{canonical_charset, CharsetAlias, Error, Limit, Limits};
+
+/// Guess the charset of `bytes`, text lacking a declared charset.
+///
+/// This is meant for bodies and headers which contain 8-bit text without
+/// declaring its charset, as legacy mail often does. The guess is made by
+/// [`chardetng`], like web browsers do for pages without a declared
+/// charset, and is `UTF-8` if `bytes` are valid UTF-8 containing non-ASCII
+/// chars. `tld`, the top-level domain of the sender like `jp` or `ru`,
+/// may improve the guess.
+///
+/// The returned label is the name of an encoding known to
+/// [`encoding_rs`], like `windows-1252` or `Shift_JIS`, which the decoders
+/// of this crate and [`encoding_rs::Encoding::for_label`] accept.
+///
+/// Requires the `chardetng` feature, which enables the `decode` and
+/// `encoding_rs` features.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "chardetng")] {
+/// # use email_encoding::decode::sniff_charset;
+/// assert_eq!(sniff_charset("Adrián".as_bytes(), None), "UTF-8");
+/// assert_eq!(sniff_charset(b"Adri\xe1n", None), "windows-1252");
+/// # }
+/// ```
+///
+/// [`chardetng`]: https://docs.rs/chardetng
+/// [`encoding_rs`]: https://docs.rs/encoding_rs
+#[cfg(feature = "chardetng")]
+pub fn sniff_charset(bytes: &[u8], tld: Option<&str>) -> &'static str {
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(tld.map(str::as_bytes), true).name()
+}
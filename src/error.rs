@@ -0,0 +1,45 @@
+//! Crate-wide error type.
+
+use core::fmt;
+
+/// The error trait implemented by the error types of this crate:
+/// `core::error::Error` with the `core-error` feature, which requires
+/// Rust 1.81, and `std::error::Error` otherwise.
+#[cfg(feature = "core-error")]
+pub(crate) use core::error::Error as StdError;
+#[cfg(all(feature = "std", not(feature = "core-error")))]
+pub(crate) use std::error::Error as StdError;
+
+/// Error returned by the encoders which validate their input.
+///
+/// Inputs which can't be encoded, like RFC 2231 parameter keys
+/// containing non alphanumeric chars, are reported through this type
+/// instead of panicking.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// A parameter key isn't only composed of ascii alphanumeric chars
+    InvalidKey,
+    /// A parameter key is too long to fit the max line length
+    KeyTooLong,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::InvalidKey => f.write_str("parameter key must be ascii alphanumeric"),
+            Self::KeyTooLong => f.write_str("parameter key doesn't fit the max line length"),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
@@ -0,0 +1,631 @@
+//! [RFC 5322] mailbox encoder.
+//!
+//! [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.4
+
+use alloc::{borrow::Cow, string::String};
+use core::fmt::{self, Write};
+
+#[cfg(feature = "parse")]
+pub use self::parse::{parse_mailbox, ParsedMailbox};
+use super::{
+    quoted_string, utils,
+    writer::{CountingWriter, EmailWriter},
+};
+
+#[cfg(feature = "parse")]
+mod parse;
+
+/// Encode a mailbox, such as `"John Smith" <john@example.com>`.
+///
+/// * `display_name` is encoded via [`quoted_string::encode_phrase`].
+///   The address is written without angle brackets if there's no display name.
+/// * `local_part` is encoded via [`quoted_string::encode_local_part`].
+/// * `domain` must be a dot-atom or a domain literal (`[192.0.2.1]`).
+///   Non-ASCII domains are converted to A-labels if the `idna` feature
+///   is enabled, otherwise they are rejected.
+///
+/// The address is never folded, but it's moved to a new line
+/// if it doesn't fit the current one.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidLocalPart`] if `local_part` can't be encoded,
+/// and [`Error::EmptyDomain`], [`Error::InvalidDomain`] or [`Error::Idna`]
+/// if `domain` isn't valid. Nothing is written in this case.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::address::Error> {
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::address::encode_mailbox(
+///             Some("Adrián Smith"),
+///             "adrian.smith",
+///             "example.com",
+///             &mut writer,
+///         )?;
+///     }
+///     assert_eq!(
+///         output,
+///         "=?utf-8?b?QWRyacOhbg==?= Smith <adrian.smith@example.com>"
+///     );
+/// }
+///
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::address::encode_mailbox(
+///             None,
+///             "john smith",
+///             "example.com",
+///             &mut writer,
+///         )?;
+///     }
+///     assert_eq!(output, "\"john smith\"@example.com");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_mailbox<W: ?Sized + Write>(
+    display_name: Option<&str>,
+    local_part: &str,
+    domain: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    encode_mailbox_with_options(display_name, local_part, domain, Options::default(), w)
+}
+
+/// Options for [`encode_mailbox_with_options`] and [`encode_mailbox_list_with_options`].
+///
+/// New options may be added in minor releases, so the struct can only be
+/// constructed via [`Options::default`] and the builder methods.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Options {
+    /// Whether non-ASCII chars may be written as raw UTF-8.
+    ///
+    /// Only enable this if the message is going to be sent to a server
+    /// supporting the `SMTPUTF8` extension, as defined by [RFC 6532].
+    /// Display names and local parts are then written without being
+    /// encoded via RFC 2047, and domains are written as U-labels.
+    ///
+    /// Always enabled if [`EmailWriter::allows_utf8`].
+    ///
+    /// [RFC 6532]: https://datatracker.ietf.org/doc/html/rfc6532
+    pub allow_utf8: bool,
+}
+
+impl Options {
+    /// Set whether non-ASCII chars may be written as raw UTF-8.
+    ///
+    /// Defaults to `false`. See [`Options::allow_utf8`](#structfield.allow_utf8).
+    pub fn allow_utf8(mut self, allow_utf8: bool) -> Self {
+        self.allow_utf8 = allow_utf8;
+        self
+    }
+}
+
+/// Encode a mailbox, constrained by `options`.
+///
+/// See [`encode_mailbox`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{address::Options, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::address::Error> {
+/// let options = Options::default().allow_utf8(true);
+///
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     email_encoding::headers::address::encode_mailbox_with_options(
+///         Some("Adrián"),
+///         "adrián",
+///         "bücher.example",
+///         options,
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(output, "Adrián <adrián@bücher.example>");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_mailbox_with_options<W: ?Sized + Write>(
+    display_name: Option<&str>,
+    local_part: &str,
+    domain: &str,
+    options: Options,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    let display_name = display_name.filter(|display_name| !display_name.is_empty());
+    let options = Options {
+        allow_utf8: options.allow_utf8 || w.allows_utf8(),
+    };
+
+    let mut addr_spec = String::new();
+    if display_name.is_some() {
+        addr_spec.push('<');
+    }
+    {
+        let mut addr_spec_w = EmailWriter::new(&mut addr_spec, 0, 0, false);
+        let local_part_options = quoted_string::Options {
+            context: quoted_string::Context::DotAtom,
+            max_strategy: quoted_string::Strategy::QuotedEscaped,
+            allow_utf8: options.allow_utf8,
+            ..quoted_string::Options::default()
+        };
+        quoted_string::encode_with_options(local_part, local_part_options, &mut addr_spec_w)
+            .map_err(|err| match err {
+                quoted_string::Error::Fmt(err) => Error::Fmt(err),
+                err => Error::InvalidLocalPart(err),
+            })?;
+    }
+    let domain = encode_domain(domain, options)?;
+    addr_spec.push('@');
+    addr_spec.push_str(&domain);
+    if display_name.is_some() {
+        addr_spec.push('>');
+    }
+
+    if let Some(display_name) = display_name {
+        if options.allow_utf8 {
+            let display_name_options = quoted_string::Options {
+                allow_utf8: true,
+                ..quoted_string::Options::default()
+            };
+            quoted_string::encode_with_options(display_name, display_name_options, w).map_err(
+                |err| match err {
+                    quoted_string::Error::Fmt(err) => Error::Fmt(err),
+                    err => Error::InvalidDisplayName(err),
+                },
+            )?;
+        } else {
+            quoted_string::encode_phrase(display_name, w)?;
+        }
+        w.space();
+    }
+
+    utils::write_unfolded(&addr_spec, w)?;
+    Ok(())
+}
+
+/// Convert `domain` to the form required by `options` and validate it
+fn encode_domain(domain: &str, options: Options) -> Result<Cow<'_, str>, Error> {
+    let domain = if domain.is_ascii() || options.allow_utf8 {
+        Cow::Borrowed(domain)
+    } else {
+        domain_to_ascii(domain)?
+    };
+
+    validate_domain(&domain, options.allow_utf8)?;
+    Ok(domain)
+}
+
+#[cfg(feature = "idna")]
+fn domain_to_ascii(domain: &str) -> Result<Cow<'_, str>, Error> {
+    idna::domain_to_ascii(domain)
+        .map(Cow::Owned)
+        .map_err(|_| Error::Idna)
+}
+
+#[cfg(not(feature = "idna"))]
+fn domain_to_ascii(domain: &str) -> Result<Cow<'_, str>, Error> {
+    // Rejected by `validate_domain`
+    Ok(Cow::Borrowed(domain))
+}
+
+/// A mailbox, as accepted by [`encode_mailbox_list`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Mailbox<'a> {
+    /// The display name, if any
+    pub display_name: Option<&'a str>,
+    /// The part of the address before the `@`
+    pub local_part: &'a str,
+    /// The part of the address after the `@`
+    pub domain: &'a str,
+}
+
+impl<'a> Mailbox<'a> {
+    /// Construct a new `Mailbox`.
+    pub fn new(display_name: Option<&'a str>, local_part: &'a str, domain: &'a str) -> Self {
+        Self {
+            display_name,
+            local_part,
+            domain,
+        }
+    }
+
+    fn encode<W: ?Sized + Write>(
+        &self,
+        options: Options,
+        w: &mut EmailWriter<'_, W>,
+    ) -> Result<(), Error> {
+        encode_mailbox_with_options(self.display_name, self.local_part, self.domain, options, w)
+    }
+
+    /// The length of the mailbox when it isn't folded
+    fn unfolded_len(&self, options: Options) -> Result<usize, Error> {
+        let mut counter = CountingWriter::new(0);
+        {
+            let mut w = EmailWriter::builder()
+                .max_line_len(usize::MAX)
+                .build(&mut counter);
+            self.encode(options, &mut w)?;
+        }
+        Ok(counter.len())
+    }
+}
+
+/// Encode a comma separated list of mailboxes, such as the value of
+/// the `To`, `Cc` or `Bcc` headers.
+///
+/// Each mailbox is encoded via [`encode_mailbox`]. Lines are only folded
+/// between mailboxes, right after the commas, unless a mailbox is too long
+/// to fit a line on its own, in which case its display name gets folded too.
+///
+/// # Errors
+///
+/// Stops at the first mailbox which can't be encoded, returning the
+/// same errors as [`encode_mailbox`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{address::Mailbox, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::address::Error> {
+/// let mailboxes = [
+///     Mailbox::new(Some("John Smith"), "john.smith", "example.com"),
+///     Mailbox::new(Some("Adrián"), "adrian", "example.com"),
+///     Mailbox::new(None, "jane", "example.com"),
+/// ];
+///
+/// let mut output = String::from("To: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::address::encode_mailbox_list(mailboxes, &mut writer)?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "To: John Smith <john.smith@example.com>,\r\n",
+///         " =?utf-8?b?QWRyacOhbg==?= <adrian@example.com>, jane@example.com"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_mailbox_list<'a, I, W>(mailboxes: I, w: &mut EmailWriter<'_, W>) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Mailbox<'a>>,
+    W: ?Sized + Write,
+{
+    encode_mailbox_list_with_options(mailboxes, Options::default(), w)
+}
+
+/// Encode a comma separated list of mailboxes, constrained by `options`.
+///
+/// See [`encode_mailbox_list`] and [`encode_mailbox_with_options`].
+pub fn encode_mailbox_list_with_options<'a, I, W>(
+    mailboxes: I,
+    options: Options,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Mailbox<'a>>,
+    W: ?Sized + Write,
+{
+    let options = Options {
+        allow_utf8: options.allow_utf8 || w.allows_utf8(),
+    };
+
+    for (i, mailbox) in mailboxes.into_iter().enumerate() {
+        let len = mailbox.unfolded_len(options)?;
+
+        if i > 0 {
+            w.write_char(',')?;
+            w.space();
+        }
+
+        if w.can_go_to_new_line_now() && w.projected_line_len() + len > w.max_line_len() {
+            w.new_line()?;
+            if !w.has_spaces() {
+                w.space();
+            }
+        }
+
+        if " ".len() + len <= w.max_line_len() {
+            w.no_fold(|w| mailbox.encode(options, w))?;
+        } else {
+            mailbox.encode(options, w)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_domain(domain: &str, allow_utf8: bool) -> Result<(), Error> {
+    if domain.is_empty() {
+        return Err(Error::EmptyDomain);
+    }
+
+    match utils::find_invalid_domain_char(domain, allow_utf8) {
+        Some((position, c)) => Err(Error::InvalidDomain { position, c }),
+        None => Ok(()),
+    }
+}
+
+/// Error returned by [`encode_mailbox`] and [`encode_mailbox_list`]
+/// and their variants.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The display name can't be encoded
+    InvalidDisplayName(quoted_string::Error),
+    /// The local part can't be encoded
+    InvalidLocalPart(quoted_string::Error),
+    /// The domain is empty
+    EmptyDomain,
+    /// The domain contains a char which isn't allowed
+    InvalidDomain {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The domain can't be converted to ASCII via IDNA
+    ///
+    /// Only returned if the `idna` feature is enabled.
+    Idna,
+    /// The mailbox can't be parsed
+    ///
+    /// Only returned by `parse_mailbox`, if the `parse` feature is enabled.
+    Parse {
+        /// Byte offset at which parsing failed
+        position: usize,
+        /// What was expected at `position`
+        expected: &'static str,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::InvalidDisplayName(err) => write!(f, "invalid display name: {}", err),
+            Self::InvalidLocalPart(err) => write!(f, "invalid local part: {}", err),
+            Self::EmptyDomain => f.write_str("domain is empty"),
+            Self::Idna => f.write_str("domain can't be converted to ASCII"),
+            Self::InvalidDomain { position, c } => {
+                write!(f, "invalid char {:?} in domain at byte {}", c, position)
+            }
+            Self::Parse { position, expected } => {
+                write!(f, "expected {} at byte {}", expected, position)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn mailbox_moved_to_new_line() {
+        let mut s = String::new();
+        let line_len = "From: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_mailbox(
+                Some("Dr. Lorem Ipsum Dolor Sit Amet"),
+                "lorem.ipsum.dolor.sit.amet",
+                "example.com",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "\"Dr.\" Lorem Ipsum Dolor Sit Amet\r\n",
+                " <lorem.ipsum.dolor.sit.amet@example.com>"
+            )
+        );
+    }
+
+    #[test]
+    fn mailbox_domain_literal() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_mailbox(Some(""), "postmaster", "[192.0.2.1]", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "postmaster@[192.0.2.1]");
+    }
+
+    #[test]
+    fn mailbox_invalid() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(
+            encode_mailbox(Some("John"), "john\r\n", "example.com", &mut w),
+            Err(Error::InvalidLocalPart(quoted_string::Error::InvalidChar {
+                position: 4,
+                c: '\r'
+            }))
+        );
+        assert_eq!(
+            encode_mailbox(Some("John"), "john", "", &mut w),
+            Err(Error::EmptyDomain)
+        );
+        assert_eq!(
+            encode_mailbox(Some("John"), "john", "example..com", &mut w),
+            Err(Error::InvalidDomain {
+                position: 8,
+                c: '.'
+            })
+        );
+        assert_eq!(
+            encode_mailbox(Some("John"), "john", "[192.0.2.1\\]", &mut w),
+            Err(Error::InvalidDomain {
+                position: 10,
+                c: '\\'
+            })
+        );
+        drop(w);
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn mailbox_list_long_display_name() {
+        let mut s = String::new();
+        let line_len = "Cc: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_mailbox_list(
+                [
+                    Mailbox::new(None, "a", "example.com"),
+                    Mailbox::new(
+                        Some(
+                            "Lorem ipsum dolor sit amet consectetur adipiscing elit sed do eiusmod",
+                        ),
+                        "lorem",
+                        "example.com",
+                    ),
+                    Mailbox::new(Some("B"), "b", "example.com"),
+                ],
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "a@example.com,\r\n",
+                " Lorem ipsum dolor sit amet consectetur adipiscing elit sed do eiusmod\r\n",
+                " <lorem@example.com>, B <b@example.com>"
+            )
+        );
+    }
+
+    #[test]
+    fn mailbox_list_empty() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_mailbox_list([], &mut w).unwrap();
+        }
+
+        assert_eq!(s, "");
+    }
+
+    #[cfg(not(feature = "idna"))]
+    #[test]
+    fn mailbox_non_ascii_domain() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(
+            encode_mailbox(Some("John"), "john", "exämple.com", &mut w),
+            Err(Error::InvalidDomain {
+                position: 2,
+                c: 'ä'
+            })
+        );
+    }
+
+    #[cfg(feature = "idna")]
+    #[test]
+    fn mailbox_idna() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_mailbox(Some("Bücher"), "info", "bücher.example", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "=?utf-8?b?QsO8Y2hlcg==?= <info@xn--bcher-kva.example>");
+    }
+
+    #[cfg(feature = "idna")]
+    #[test]
+    fn mailbox_idna_invalid() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(
+            encode_mailbox(None, "info", "bücher\u{200d}.example", &mut w),
+            Err(Error::Idna)
+        );
+    }
+
+    #[test]
+    fn mailbox_list_utf8() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_mailbox_list_with_options(
+                [
+                    Mailbox::new(Some("Adrián García"), "adrián", "example.com"),
+                    Mailbox::new(None, "info", "bücher.example"),
+                ],
+                Options { allow_utf8: true },
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "\"Adrián García\" <adrián@example.com>, info@bücher.example"
+        );
+    }
+
+    #[test]
+    fn mailbox_writer_utf8() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::builder()
+                .line_len(line_len)
+                .allow_utf8(true)
+                .build(&mut s);
+            encode_mailbox(Some("Adrián"), "adrián", "bücher.example", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "Adrián <adrián@bücher.example>");
+    }
+}
@@ -0,0 +1,372 @@
+//! [RFC 5322] mailbox parser.
+//!
+//! [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.4
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::{Error, Mailbox};
+use crate::headers::{encoded_words, utils};
+
+/// A mailbox parsed by [`parse_mailbox`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParsedMailbox {
+    /// The decoded display name, if any
+    pub display_name: Option<String>,
+    /// The part of the address before the `@`, without quotes
+    pub local_part: String,
+    /// The part of the address after the `@`
+    pub domain: String,
+}
+
+impl ParsedMailbox {
+    /// Borrow as a [`Mailbox`], which can be encoded again.
+    pub fn as_mailbox(&self) -> Mailbox<'_> {
+        Mailbox::new(self.display_name.as_deref(), &self.local_part, &self.domain)
+    }
+}
+
+/// Parse a mailbox, such as `"John Smith" <john@example.com>`.
+///
+/// This is the inverse of [`encode_mailbox`](super::encode_mailbox):
+///
+/// * the display name may be made out of atoms, quoted-strings and
+///   encoded-words, which get decoded;
+/// * quoted local parts get unquoted;
+/// * comments and folding whitespace are skipped.
+///
+/// Encoded-words using charsets other than `utf-8`, `us-ascii` and
+/// `iso-8859-1` are left as they are, unless the `encoding_rs` feature
+/// is enabled.
+///
+/// Requires the `parse` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::Parse`] if `s` isn't a single mailbox.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::address::parse_mailbox;
+/// # fn main() -> Result<(), email_encoding::headers::address::Error> {
+/// let mailbox = parse_mailbox("=?utf-8?b?QWRyacOhbg==?= Smith <adrian.smith@example.com>")?;
+/// assert_eq!(mailbox.display_name.as_deref(), Some("Adrián Smith"));
+/// assert_eq!(mailbox.local_part, "adrian.smith");
+/// assert_eq!(mailbox.domain, "example.com");
+///
+/// let mailbox = parse_mailbox("\"john smith\"@example.com (John)")?;
+/// assert_eq!(mailbox.display_name, None);
+/// assert_eq!(mailbox.local_part, "john smith");
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_mailbox(s: &str) -> Result<ParsedMailbox, Error> {
+    let mut parser = Parser { s, pos: 0 };
+
+    parser.skip_cfws()?;
+    let start = parser.pos;
+    let phrase = parser.phrase()?;
+
+    let (display_name, local_part, domain) = if parser.peek() == Some('<') {
+        parser.pos += 1;
+        parser.skip_cfws()?;
+        let (local_part, domain) = parser.addr_spec()?;
+        parser.expect('>', "`>`")?;
+        parser.skip_cfws()?;
+
+        let display_name = Some(display_name(&phrase)).filter(|name| !name.is_empty());
+        (display_name, local_part, domain)
+    } else {
+        parser.pos = start;
+        let (local_part, domain) = parser.addr_spec()?;
+        (None, local_part, domain)
+    };
+
+    if parser.pos != s.len() {
+        return Err(parser.error("end of mailbox"));
+    }
+
+    Ok(ParsedMailbox {
+        display_name,
+        local_part,
+        domain,
+    })
+}
+
+enum Word<'a> {
+    Atom(&'a str),
+    Quoted(String),
+}
+
+/// Join the words of a phrase, decoding its encoded-words
+fn display_name(phrase: &[Word<'_>]) -> String {
+    let mut name = String::new();
+    let mut after_encoded_word = false;
+    for (i, word) in phrase.iter().enumerate() {
+        let (text, is_encoded_word) = match word {
+            Word::Atom(atom) if atom.starts_with("=?") && atom.ends_with("?=") => {
+                match encoded_words::decode_words(atom) {
+                    Ok(decoded) => (decoded.into_owned(), true),
+                    Err(_) => (atom.to_string(), false),
+                }
+            }
+            Word::Atom(atom) => (atom.to_string(), false),
+            Word::Quoted(quoted) => (quoted.clone(), false),
+        };
+
+        // Whitespace between adjacent encoded-words is ignored
+        if i > 0 && !(after_encoded_word && is_encoded_word) {
+            name.push(' ');
+        }
+        name.push_str(&text);
+        after_encoded_word = is_encoded_word;
+    }
+    name
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), Error> {
+        if self.peek() != Some(c) {
+            return Err(self.error(expected));
+        }
+        self.pos += c.len_utf8();
+        Ok(())
+    }
+
+    /// Skip whitespace, line breaks and comments
+    fn skip_cfws(&mut self) -> Result<(), Error> {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => self.pos += 1,
+                Some('(') => self.skip_comment()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) -> Result<(), Error> {
+        let mut depth = 0_usize;
+        while let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                '\\' => {
+                    if let Some(c) = self.peek() {
+                        self.pos += c.len_utf8();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Err(self.error("`)`"))
+    }
+
+    /// Parse the words of a phrase, accepting the obsolete `.` in atoms
+    fn phrase(&mut self) -> Result<Vec<Word<'a>>, Error> {
+        let mut words = Vec::new();
+        loop {
+            match self.peek() {
+                Some('"') => words.push(Word::Quoted(self.quoted_string()?)),
+                Some(c) if is_atom_char(c) || c == '.' => {
+                    words.push(Word::Atom(self.take_while(|c| is_atom_char(c) || c == '.')));
+                }
+                _ => return Ok(words),
+            }
+            self.skip_cfws()?;
+        }
+    }
+
+    fn quoted_string(&mut self) -> Result<String, Error> {
+        self.expect('"', "`\"`")?;
+
+        let mut unquoted = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(unquoted),
+                '\\' => match self.peek() {
+                    Some(c) => {
+                        self.pos += c.len_utf8();
+                        unquoted.push(c);
+                    }
+                    None => break,
+                },
+                // Unfold
+                '\r' | '\n' => {}
+                c => unquoted.push(c),
+            }
+        }
+
+        Err(self.error("`\"`"))
+    }
+
+    fn addr_spec(&mut self) -> Result<(String, String), Error> {
+        let local_part = if self.peek() == Some('"') {
+            self.quoted_string()?
+        } else {
+            self.dot_atom("local part")?
+        };
+        self.skip_cfws()?;
+        self.expect('@', "`@`")?;
+        self.skip_cfws()?;
+
+        let domain = if self.peek() == Some('[') {
+            let start = self.pos;
+            self.take_while(|c| c != ']');
+            self.expect(']', "`]`")?;
+            self.s[start..self.pos].to_string()
+        } else {
+            self.dot_atom("domain")?
+        };
+        self.skip_cfws()?;
+
+        Ok((local_part, domain))
+    }
+
+    fn dot_atom(&mut self, expected: &'static str) -> Result<String, Error> {
+        let mut dot_atom = String::new();
+        loop {
+            let atom = self.take_while(is_atom_char);
+            if atom.is_empty() {
+                return Err(self.error(expected));
+            }
+            dot_atom.push_str(atom);
+
+            self.skip_cfws()?;
+            if self.peek() != Some('.') {
+                return Ok(dot_atom);
+            }
+            self.pos += 1;
+            dot_atom.push('.');
+            self.skip_cfws()?;
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        let len = self.s[start..]
+            .find(|c| !f(c))
+            .unwrap_or(self.s.len() - start);
+        self.pos += len;
+        &self.s[start..self.pos]
+    }
+
+    fn error(&self, expected: &'static str) -> Error {
+        Error::Parse {
+            position: self.pos,
+            expected,
+        }
+    }
+}
+
+/// `atext`, extended with the non-ASCII chars allowed by RFC 6532
+fn is_atom_char(c: char) -> bool {
+    utils::char_is_atext(c) || !c.is_ascii()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::headers::{address::encode_mailbox_list, writer::EmailWriter};
+
+    fn round_trip(mailbox: Mailbox<'_>) {
+        let mut s = String::new();
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            encode_mailbox_list([mailbox], &mut w).unwrap();
+        }
+
+        assert_eq!(parse_mailbox(&s).unwrap().as_mailbox(), mailbox, "{}", s);
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(Mailbox::new(None, "john", "example.com"));
+        round_trip(Mailbox::new(
+            Some("John Smith"),
+            "john.smith",
+            "example.com",
+        ));
+        round_trip(Mailbox::new(Some("Smith, John"), "john", "example.com"));
+        round_trip(Mailbox::new(
+            Some("John \"Johnny\" Smith"),
+            "john",
+            "example.com",
+        ));
+        round_trip(Mailbox::new(Some("Adrián Pérez"), "adrian", "example.com"));
+        round_trip(Mailbox::new(None, "john smith", "[192.0.2.1]"));
+        round_trip(Mailbox::new(
+            Some("Adrián Pérez de la Cruz, Departamento de Atención al Cliente"),
+            "atencion.al.cliente",
+            "example.com",
+        ));
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            parse_mailbox(
+                "(a (nested) comment) John (middle) Smith <john (x) @ example.com> (end)"
+            )
+            .unwrap(),
+            ParsedMailbox {
+                display_name: Some("John Smith".to_string()),
+                local_part: "john".to_string(),
+                domain: "example.com".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            parse_mailbox("John Smith"),
+            Err(Error::Parse {
+                position: 5,
+                expected: "`@`"
+            })
+        );
+        assert_eq!(
+            parse_mailbox("<john@example.com"),
+            Err(Error::Parse {
+                position: 17,
+                expected: "`>`"
+            })
+        );
+        assert_eq!(
+            parse_mailbox("john@example.com, jane@example.com"),
+            Err(Error::Parse {
+                position: 16,
+                expected: "end of mailbox"
+            })
+        );
+        assert_eq!(
+            parse_mailbox("\"john@example.com"),
+            Err(Error::Parse {
+                position: 17,
+                expected: "`\"`"
+            })
+        );
+    }
+}
@@ -0,0 +1,372 @@
+//! [RFC 8601] `Authentication-Results` header encoder.
+//!
+//! [RFC 8601]: https://datatracker.ietf.org/doc/html/rfc8601#section-2.2
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use super::{utils, writer::EmailWriter};
+
+/// Builder for the value of an `Authentication-Results` header.
+///
+/// The `authserv-id` is followed by one `resinfo` per [`MethodResult`],
+/// each preceded by `;`, or by `none` if no method was run. Lines are
+/// folded between `resinfo`s, and inside of a `resinfo` only if it doesn't
+/// fit a line on its own.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{
+/// #     authentication_results::{AuthenticationResults, MethodResult},
+/// #     writer::EmailWriter,
+/// # };
+/// # fn main() -> Result<(), email_encoding::headers::authentication_results::Error> {
+/// let mut output = String::from("Authentication-Results: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     AuthenticationResults::new("mx.example.com")
+///         .result(MethodResult::new("spf", "pass").property("smtp", "mailfrom", "example.net"))
+///         .result(
+///             MethodResult::new("dkim", "fail")
+///                 .reason("signature verification failed")
+///                 .property("header", "d", "example.net"),
+///         )
+///         .encode(&mut writer)?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "Authentication-Results: mx.example.com; spf=pass smtp.mailfrom=example.net;\r\n",
+///         " dkim=fail reason=\"signature verification failed\" header.d=example.net"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticationResults<'a> {
+    authserv_id: &'a str,
+    version: Option<u32>,
+    results: Vec<MethodResult<'a>>,
+}
+
+impl<'a> AuthenticationResults<'a> {
+    /// Construct a new `AuthenticationResults` builder, without any result.
+    ///
+    /// `authserv_id` identifies the host which performed the checks,
+    /// and is usually its domain name.
+    pub fn new(authserv_id: &'a str) -> Self {
+        Self {
+            authserv_id,
+            version: None,
+            results: Vec::new(),
+        }
+    }
+
+    /// Set the version of the header syntax, written after the `authserv-id`.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Add the result of an authentication method.
+    pub fn result(mut self, result: MethodResult<'a>) -> Self {
+        self.results.push(result);
+        self
+    }
+
+    /// Encode the header value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Empty`] or [`Error::InvalidChar`] if the
+    /// `authserv-id`, a method, a result or a property isn't a valid
+    /// keyword, or if a reason or a property value contains a control char.
+    /// Nothing is written in this case.
+    pub fn encode<W: ?Sized + Write>(&self, w: &mut EmailWriter<'_, W>) -> Result<(), Error> {
+        if self.authserv_id.is_empty() {
+            return Err(Error::Empty {
+                field: "authserv-id",
+            });
+        }
+        validate_value("authserv-id", self.authserv_id)?;
+        let resinfos = self
+            .results
+            .iter()
+            .map(MethodResult::to_resinfo)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut authserv_id = String::new();
+        push_value(&mut authserv_id, self.authserv_id);
+        if let Some(version) = self.version {
+            write!(authserv_id, " {}", version)?;
+        }
+        if resinfos.is_empty() {
+            authserv_id.push_str("; none");
+        }
+        utils::write_folded(&authserv_id, true, w)?;
+
+        for resinfo in &resinfos {
+            w.write_char(';')?;
+            utils::write_folded(resinfo, false, w)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of an authentication method, for [`AuthenticationResults`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::authentication_results::MethodResult;
+/// let result = MethodResult::new("dkim", "pass")
+///     .property("header", "d", "example.com")
+///     .property("header", "s", "selector1");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodResult<'a> {
+    method: &'a str,
+    version: Option<u32>,
+    result: &'a str,
+    reason: Option<&'a str>,
+    properties: Vec<(&'a str, &'a str, &'a str)>,
+}
+
+impl<'a> MethodResult<'a> {
+    /// Construct a new `MethodResult`, such as `spf` with `pass` as its result.
+    pub fn new(method: &'a str, result: &'a str) -> Self {
+        Self {
+            method,
+            version: None,
+            result,
+            reason: None,
+            properties: Vec::new(),
+        }
+    }
+
+    /// Set the version of the method.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Set the human-readable reason of the result.
+    pub fn reason(mut self, reason: &'a str) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    /// Add a property, such as `smtp.mailfrom=example.com`.
+    pub fn property(mut self, ptype: &'a str, property: &'a str, value: &'a str) -> Self {
+        self.properties.push((ptype, property, value));
+        self
+    }
+
+    fn to_resinfo(&self) -> Result<String, Error> {
+        validate_keyword("method", self.method)?;
+        validate_keyword("result", self.result)?;
+
+        let mut resinfo = String::from(self.method);
+        if let Some(version) = self.version {
+            write!(resinfo, "/{}", version)?;
+        }
+        resinfo.push('=');
+        resinfo.push_str(self.result);
+
+        if let Some(reason) = self.reason {
+            validate_value("reason", reason)?;
+            resinfo.push_str(" reason=");
+            push_value(&mut resinfo, reason);
+        }
+
+        for &(ptype, property, value) in &self.properties {
+            validate_keyword("ptype", ptype)?;
+            validate_keyword("property", property)?;
+            validate_value("pvalue", value)?;
+
+            write!(resinfo, " {}.{}=", ptype, property)?;
+            if value.chars().all(|c| c == '@' || utils::char_is_token(c)) {
+                // Addresses and domains are allowed unquoted
+                resinfo.push_str(value);
+            } else {
+                push_value(&mut resinfo, value);
+            }
+        }
+
+        Ok(resinfo)
+    }
+}
+
+/// Write `value` as a token, or as a quoted-string if it isn't one.
+fn push_value(s: &mut String, value: &str) {
+    if !value.is_empty() && value.chars().all(utils::char_is_token) {
+        s.push_str(value);
+        return;
+    }
+
+    s.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s.push('"');
+}
+
+/// Validate an RFC 8601 `Keyword`, made out of letters, digits and hyphens.
+fn validate_keyword(field: &'static str, keyword: &str) -> Result<(), Error> {
+    if keyword.is_empty() {
+        return Err(Error::Empty { field });
+    }
+    match keyword
+        .char_indices()
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '-'))
+    {
+        Some((position, c)) => Err(Error::InvalidChar { field, position, c }),
+        None => Ok(()),
+    }
+}
+
+fn validate_value(field: &'static str, value: &str) -> Result<(), Error> {
+    match value
+        .char_indices()
+        .find(|&(_, c)| c.is_control() && c != '\t')
+    {
+        Some((position, c)) => Err(Error::InvalidChar { field, position, c }),
+        None => Ok(()),
+    }
+}
+
+/// Error returned by [`AuthenticationResults::encode`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// A field is empty
+    Empty {
+        /// The name of the field, such as `method` or `ptype`
+        field: &'static str,
+    },
+    /// A field contains a char which isn't allowed
+    InvalidChar {
+        /// The name of the field, such as `method` or `ptype`
+        field: &'static str,
+        /// Byte offset of the char in the field
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::Empty { field } => write!(f, "`{}` is empty", field),
+            Self::InvalidChar { field, position, c } => write!(
+                f,
+                "invalid char {:?} in `{}` at byte {}",
+                c, field, position
+            ),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn none() {
+        let mut s = "Authentication-Results: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            AuthenticationResults::new("mx.example.com")
+                .version(1)
+                .encode(&mut w)
+                .unwrap();
+        }
+
+        assert_eq!(s, "Authentication-Results: mx.example.com 1; none");
+    }
+
+    #[test]
+    fn versions_and_addresses() {
+        let mut s = "Authentication-Results: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            AuthenticationResults::new("mx.example.com")
+                .result(
+                    MethodResult::new("auth", "pass")
+                        .version(1)
+                        .property("smtp", "auth", "john@example.com")
+                        .property("policy", "note", "a \"quoted\" note"),
+                )
+                .encode(&mut w)
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Authentication-Results: mx.example.com;\r\n",
+                " auth/1=pass smtp.auth=john@example.com policy.note=\"a \\\"quoted\\\" note\""
+            )
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(
+            AuthenticationResults::new("").encode(&mut w),
+            Err(Error::Empty {
+                field: "authserv-id"
+            })
+        );
+        assert_eq!(
+            AuthenticationResults::new("mx.example.com")
+                .result(MethodResult::new("spf", "pass").property("smtp", "mail from", "a"))
+                .encode(&mut w),
+            Err(Error::InvalidChar {
+                field: "property",
+                position: 4,
+                c: ' '
+            })
+        );
+        assert_eq!(
+            AuthenticationResults::new("mx.example.com")
+                .result(MethodResult::new("spf", "pass").reason("a\r\nb"))
+                .encode(&mut w),
+            Err(Error::InvalidChar {
+                field: "reason",
+                position: 1,
+                c: '\r'
+            })
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Raw header section splitter.
+//!
+//! Requires the `decode` feature.
+
+use alloc::borrow::Cow;
+use core::str;
+
+use crate::decode::Error;
+
+/// Split the header section of a message into its fields.
+///
+/// `input` is the raw message, or just its header section. Fields are
+/// split at line breaks, `CRLF` or `LF`, which aren't followed by
+/// whitespace, and end at the first empty line, which separates them from
+/// the body. See [`Fields::rest`] for getting the body.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::block::split;
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let message = b"Subject: This is a long\r\n subject\r\nFrom: john@example.com\r\n\r\nHello!";
+///
+/// let mut fields = split(message);
+/// let subject = fields.next().unwrap()?;
+/// assert_eq!(subject.name, "Subject");
+/// assert_eq!(*subject.value, *b"This is a long subject");
+/// let from = fields.next().unwrap()?;
+/// assert_eq!(from.name, "From");
+/// assert_eq!(*from.value, *b"john@example.com");
+/// assert!(fields.next().is_none());
+///
+/// assert_eq!(fields.rest(), b"Hello!");
+/// # Ok(())
+/// # }
+/// ```
+pub fn split(input: &[u8]) -> Fields<'_> {
+    Fields {
+        input,
+        pos: 0,
+        done: false,
+    }
+}
+
+/// A header field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field<'a> {
+    /// The name of the field
+    pub name: &'a str,
+    /// The unfolded value of the field
+    ///
+    /// Leading and trailing whitespace is removed. The value is only
+    /// allocated if it was folded.
+    pub value: Cow<'a, [u8]>,
+}
+
+/// Iterator over the fields of a header section, returned by [`split`].
+///
+/// Malformed lines, which neither contain a field name followed by `:`
+/// nor continue a folded field, are returned as [`Error::InvalidField`]
+/// and can be skipped. The obsolete syntax, which allows whitespace
+/// before the `:` and lines made only out of whitespace inside of
+/// folded fields, is accepted.
+///
+/// [`Error::InvalidField`]: crate::decode::Error::InvalidField
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    input: &'a [u8],
+    pos: usize,
+    done: bool,
+}
+
+impl<'a> Fields<'a> {
+    /// Get the part of the input which hasn't been split yet.
+    ///
+    /// Once the iterator is exhausted, this is the body of the message.
+    pub fn rest(&self) -> &'a [u8] {
+        &self.input[self.pos..]
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<Field<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.pos == self.input.len() {
+            self.done = true;
+            return None;
+        }
+
+        let start = self.pos;
+        let (first_line_len, mut end) = line(&self.input[start..]);
+        if first_line_len == 0 {
+            // Empty line separating the header section from the body
+            self.pos += end;
+            self.done = true;
+            return None;
+        }
+
+        let mut content_end = first_line_len;
+        while matches!(self.input.get(start + end), Some(b' ' | b'\t')) {
+            let (line_len, next) = line(&self.input[start + end..]);
+            content_end = end + line_len;
+            end += next;
+        }
+        self.pos += end;
+
+        Some(
+            parse_field(&self.input[start..start + content_end])
+                .ok_or(Error::InvalidField { position: start }),
+        )
+    }
+}
+
+/// Get the length of the first line of `s` without its line break,
+/// and the offset of the next line
+fn line(s: &[u8]) -> (usize, usize) {
+    match memchr::memchr(b'\n', s) {
+        Some(i) if i > 0 && s[i - 1] == b'\r' => (i - 1, i + 1),
+        Some(i) => (i, i + 1),
+        None => (s.len(), s.len()),
+    }
+}
+
+/// Parse the possibly folded `field`, without its final line break
+fn parse_field(field: &[u8]) -> Option<Field<'_>> {
+    let colon = memchr::memchr(b':', field)?;
+    let name = str::from_utf8(trim_end(&field[..colon])).ok()?;
+    if !super::is_valid_field_name(name) {
+        return None;
+    }
+
+    let value = trim_end(trim_start(&field[colon + 1..]));
+    let value = if value.contains(&b'\n') {
+        Cow::Owned(
+            value
+                .iter()
+                .copied()
+                .filter(|&b| b != b'\r' && b != b'\n')
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(value)
+    };
+    Some(Field { name, value })
+}
+
+fn trim_start(s: &[u8]) -> &[u8] {
+    let len = s.iter().take_while(|b| b.is_ascii_whitespace()).count();
+    &s[len..]
+}
+
+fn trim_end(s: &[u8]) -> &[u8] {
+    let len = s
+        .iter()
+        .rev()
+        .take_while(|b| b.is_ascii_whitespace())
+        .count();
+    &s[..s.len() - len]
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::headers::{rfc2047, unstructured, writer::EmailWriter, MAX_LINE_LEN};
+
+    fn field<'a>(name: &'a str, value: &'a str) -> Result<Field<'a>, Error> {
+        Ok(Field {
+            name,
+            value: Cow::Borrowed(value.as_bytes()),
+        })
+    }
+
+    #[test]
+    fn fields() {
+        let message = b"A: 1\r\nB:2 \r\n  \r\n 3\nC : \t\r\n\r\nD: body";
+        let mut fields = split(message);
+        assert_eq!(fields.next(), Some(field("A", "1")));
+        assert_eq!(fields.next(), Some(field("B", "2    3")));
+        assert_eq!(fields.next(), Some(field("C", "")));
+        assert_eq!(fields.next(), None);
+        assert_eq!(fields.next(), None);
+        assert_eq!(fields.rest(), b"D: body");
+    }
+
+    #[test]
+    fn header_section_only() {
+        let mut fields = split(b"A: 1");
+        assert_eq!(fields.next(), Some(field("A", "1")));
+        assert_eq!(fields.next(), None);
+        assert_eq!(fields.rest(), b"");
+
+        assert_eq!(split(b"").next(), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let message = b" A: 1\r\nB 2\r\nC D: 3\r\n: 4\r\nE: 5\r\n";
+        assert_eq!(
+            split(message).collect::<Vec<_>>(),
+            [
+                Err(Error::InvalidField { position: 0 }),
+                Err(Error::InvalidField { position: 7 }),
+                Err(Error::InvalidField { position: 12 }),
+                Err(Error::InvalidField { position: 20 }),
+                field("E", "5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn own_output() {
+        let subject = "Hello, this is a subject long enough to be folded, with some àccents in it";
+        let mut s = "Subject: ".to_string();
+        {
+            let mut w = EmailWriter::new(&mut s, "Subject: ".len(), 0, false);
+            unstructured::encode(subject, &mut w).unwrap();
+        }
+        assert!(s.lines().all(|line| line.len() <= MAX_LINE_LEN));
+        s.push_str("\r\n\r\n");
+
+        let fields = split(s.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].name, "Subject");
+        let value = str::from_utf8(&fields[0].value).unwrap();
+        assert_eq!(rfc2047::decode(value), Ok(subject.into()));
+    }
+}
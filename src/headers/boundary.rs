@@ -0,0 +1,387 @@
+//! [RFC 2046] multipart boundary encoder.
+//!
+//! [RFC 2046]: https://datatracker.ietf.org/doc/html/rfc2046#section-5.1.1
+
+use alloc::string::String;
+use core::{
+    fmt::{self, Write},
+    str,
+};
+
+use super::{utils, writer::EmailWriter};
+use crate::base64_engine;
+
+const MAX_BOUNDARY_LEN: usize = 70;
+/// Prefix of generated boundaries.
+///
+/// `=_` can't appear in base64 nor in quoted-printable encoded content.
+const GENERATED_PREFIX: &str = "=_";
+#[cfg(feature = "rand")]
+const RANDOM_LEN: usize = 24;
+
+/// Generate a new random boundary.
+///
+/// The boundary is made out of 192 random bits, obtained from the
+/// operating system, making collisions with the content of the parts
+/// negligible. See [`generate_with_entropy`] for the format of the boundary,
+/// and [`collides`] for checking the content of the parts anyway.
+///
+/// Requires the `rand` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::Random`] if the operating system couldn't provide
+/// random bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::headers::boundary::Error> {
+/// let boundary = email_encoding::headers::boundary::generate()?;
+/// assert_eq!(email_encoding::headers::boundary::validate(&boundary), Ok(()));
+/// assert_eq!(boundary.len(), 34);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "rand")]
+pub fn generate() -> Result<String, Error> {
+    let mut entropy = [0; RANDOM_LEN];
+    getrandom::getrandom(&mut entropy).map_err(|_| Error::Random)?;
+    generate_with_entropy(&entropy)
+}
+
+/// Generate a new boundary derived from `entropy`.
+///
+/// The boundary is `=_` followed by `entropy` encoded with the
+/// URL-safe base64 alphabet, all of which are `bchars`. Since `=_` can't
+/// appear in base64 nor in quoted-printable encoded content, the boundary
+/// can't collide with parts using either encoding.
+///
+/// # Errors
+///
+/// Returns [`Error::Empty`] if `entropy` is empty, and [`Error::TooLong`]
+/// if it's longer than 51 bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::headers::boundary::Error> {
+/// let boundary =
+///     email_encoding::headers::boundary::generate_with_entropy(&[0xDE, 0xAD, 0xBE, 0xEF])?;
+/// assert_eq!(boundary, "=_3q2-7w");
+/// # Ok(())
+/// # }
+/// ```
+pub fn generate_with_entropy(entropy: &[u8]) -> Result<String, Error> {
+    if entropy.is_empty() {
+        return Err(Error::Empty);
+    }
+
+    let mut buf = [0; MAX_BOUNDARY_LEN - GENERATED_PREFIX.len()];
+    let len = base64_engine::encode_url_safe_slice(entropy, &mut buf).ok_or(Error::TooLong)?;
+
+    let mut boundary = String::with_capacity(GENERATED_PREFIX.len() + len);
+    boundary.push_str(GENERATED_PREFIX);
+    boundary.push_str(str::from_utf8(&buf[..len]).expect("base64 produced an invalid encode"));
+    Ok(boundary)
+}
+
+/// Find out whether `boundary` collides with `content`.
+///
+/// `content` is the encoded body of a part. The boundary collides if
+/// a delimiter line, `--` followed by `boundary`, could be found in it.
+/// The check is conservative: any occurrence of the delimiter counts,
+/// even when it isn't at the start of a line.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::boundary::collides;
+/// assert!(!collides("=_3q2-7w", b"Hello, world!\r\n"));
+/// assert!(collides("simple", b"Hello\r\n--simple boundary\r\n"));
+/// ```
+pub fn collides(boundary: &str, content: &[u8]) -> bool {
+    let boundary = boundary.as_bytes();
+    content
+        .windows("--".len() + boundary.len())
+        .any(|window| window.starts_with(b"--") && &window[2..] == boundary)
+}
+
+/// Encode the `boundary` parameter of a multipart `Content-Type`.
+///
+/// `boundary` is validated via [`validate`] and quoted only if it
+/// contains chars which aren't allowed in a MIME token.
+/// The parameter is moved to a new line if it doesn't fit the
+/// current one.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::boundary::Error> {
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::boundary::encode("mLrtXwLTJ2Bq", &mut writer)?;
+///     }
+///     assert_eq!(output, "boundary=mLrtXwLTJ2Bq");
+/// }
+///
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::boundary::encode("simple boundary", &mut writer)?;
+///     }
+///     assert_eq!(output, "boundary=\"simple boundary\"");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode<W: ?Sized + Write>(boundary: &str, w: &mut EmailWriter<'_, W>) -> Result<(), Error> {
+    validate(boundary)?;
+
+    let needs_quoting = !boundary.chars().all(utils::char_is_token);
+
+    let len = "boundary=".len() + boundary.len() + if needs_quoting { 2 } else { 0 };
+    if w.projected_line_len() + len > w.max_line_len() && " ".len() + len <= w.max_line_len() {
+        // Fits a new line, but not the current one
+        w.new_line()?;
+        if !w.has_spaces() {
+            w.space();
+        }
+    }
+
+    w.write_str("boundary=")?;
+    if needs_quoting {
+        w.write_char('"')?;
+        w.write_str(boundary)?;
+        w.write_char('"')?;
+    } else {
+        w.write_str(boundary)?;
+    }
+
+    Ok(())
+}
+
+/// Validate a multipart boundary against the RFC 2046 grammar.
+///
+/// A valid boundary is made out of 1 to 70 `bchars`, the last
+/// of which must not be a space.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::boundary::{validate, Error};
+/// assert_eq!(validate("mLrtXwLTJ2Bq"), Ok(()));
+/// assert_eq!(validate("simple boundary"), Ok(()));
+///
+/// assert_eq!(validate(""), Err(Error::Empty));
+/// assert_eq!(validate("boundary "), Err(Error::TrailingSpace));
+/// assert_eq!(
+///     validate("bound@ry"),
+///     Err(Error::InvalidChar {
+///         position: 5,
+///         c: '@'
+///     })
+/// );
+/// ```
+pub fn validate(boundary: &str) -> Result<(), Error> {
+    if boundary.is_empty() {
+        return Err(Error::Empty);
+    }
+    if let Some((position, c)) = boundary.char_indices().find(|&(_, c)| !char_is_bchar(c)) {
+        return Err(Error::InvalidChar { position, c });
+    }
+    if boundary.len() > MAX_BOUNDARY_LEN {
+        return Err(Error::TooLong);
+    }
+    if boundary.ends_with(' ') {
+        return Err(Error::TrailingSpace);
+    }
+
+    Ok(())
+}
+
+/// `bchars` as defined by RFC 2046
+const fn char_is_bchar(c: char) -> bool {
+    c == ' ' || char_is_bcharnospace(c)
+}
+
+/// `bcharsnospace` as defined by RFC 2046
+const fn char_is_bcharnospace(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '\'' | '(' | ')' | '+' | '_' | ',' | '-' | '.' | '/' | ':' | '=' | '?'
+        )
+}
+
+/// Error returned by [`encode`], [`validate`], [`generate_with_entropy`]
+/// and, with the `rand` feature, `generate`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The boundary is empty
+    Empty,
+    /// The boundary is longer than 70 chars
+    TooLong,
+    /// The boundary contains a char which isn't a `bchar`
+    InvalidChar {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The boundary ends with a space
+    TrailingSpace,
+    /// The operating system couldn't provide random bytes
+    ///
+    /// Only returned if the `rand` feature is enabled.
+    Random,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::Empty => f.write_str("boundary is empty"),
+            Self::TooLong => write!(f, "boundary is longer than {} chars", MAX_BOUNDARY_LEN),
+            Self::InvalidChar { position, c } => {
+                write!(f, "invalid char {:?} in boundary at byte {}", c, position)
+            }
+            Self::TrailingSpace => f.write_str("boundary ends with a space"),
+            Self::Random => f.write_str("couldn't obtain random bytes"),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn token() {
+        let mut s = "Content-Type: multipart/mixed;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode("0123456789abcdef", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "Content-Type: multipart/mixed; boundary=0123456789abcdef"
+        );
+    }
+
+    #[test]
+    fn quoted() {
+        let mut s = "Content-Type: multipart/mixed;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode("=_part:1/2?", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "Content-Type: multipart/mixed; boundary=\"=_part:1/2?\"");
+    }
+
+    #[test]
+    fn long() {
+        let mut s = "Content-Type: multipart/alternative;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode(&"0123456789".repeat(6), &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Type: multipart/alternative;\r\n",
+                " boundary=012345678901234567890123456789012345678901234567890123456789"
+            )
+        );
+    }
+
+    #[test]
+    fn too_long() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, true);
+
+        assert_eq!(encode(&"0".repeat(71), &mut w), Err(Error::TooLong));
+    }
+
+    #[test]
+    fn longest() {
+        assert_eq!(validate(&"0".repeat(70)), Ok(()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            validate("bound\"ary"),
+            Err(Error::InvalidChar {
+                position: 5,
+                c: '"'
+            })
+        );
+        assert_eq!(
+            validate("boundàry"),
+            Err(Error::InvalidChar {
+                position: 5,
+                c: 'à'
+            })
+        );
+        assert_eq!(validate(" "), Err(Error::TrailingSpace));
+    }
+
+    #[test]
+    fn generated() {
+        let boundary = generate_with_entropy(&[0xff; 51]).unwrap();
+        assert_eq!(boundary.len(), MAX_BOUNDARY_LEN);
+        assert_eq!(validate(&boundary), Ok(()));
+
+        assert_eq!(generate_with_entropy(&[0xff; 52]), Err(Error::TooLong));
+        assert_eq!(generate_with_entropy(&[]), Err(Error::Empty));
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random() {
+        let a = generate().unwrap();
+        let b = generate().unwrap();
+        assert_ne!(a, b);
+        assert_eq!(validate(&a), Ok(()));
+    }
+
+    #[test]
+    fn collision() {
+        assert!(collides("abc", b"--abc"));
+        assert!(collides("abc", b"x--abcx"));
+        assert!(!collides("abc", b"-abc--ab"));
+        assert!(!collides("abc", b""));
+    }
+}
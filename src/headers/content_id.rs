@@ -0,0 +1,256 @@
+//! [RFC 2392] Content-ID encoder.
+//!
+//! The `Content-ID` header identifies a body part, so that it can be
+//! referenced by `cid:` URLs, such as inline images of an HTML body.
+//! Its value has the same syntax as a [`message_id`].
+//!
+//! The functions of this module work with the id without its angle
+//! brackets, which is what `cid:` URLs contain. They get added
+//! by [`encode`].
+//!
+//! [RFC 2392]: https://datatracker.ietf.org/doc/html/rfc2392
+
+use alloc::{format, string::String};
+use core::fmt::{self, Write};
+
+use super::{message_id, utils, writer::EmailWriter};
+
+/// Generate a new content-id, with a random left part.
+///
+/// The left part is made out of 128 random bits, obtained from the
+/// operating system, and `domain` becomes the right part.
+///
+/// Requires the `rand` feature. See [`generate_with_entropy`]
+/// for supplying the randomness yourself.
+///
+/// # Errors
+///
+/// Returns [`Error::EmptyIdRight`] or [`Error::InvalidChar`] if `domain`
+/// isn't a valid `id-right`, and [`Error::Random`] if the operating
+/// system couldn't provide random bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::headers::content_id::Error> {
+/// let content_id = email_encoding::headers::content_id::generate("example.com")?;
+/// assert!(content_id.ends_with("@example.com"));
+/// let html = format!("<img src=\"cid:{}\">", content_id);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "rand")]
+pub fn generate(domain: &str) -> Result<String, Error> {
+    let msg_id = message_id::generate(domain).map_err(from_msg_id_error)?;
+    Ok(strip_angle_brackets(msg_id))
+}
+
+/// Generate a new content-id, with a left part derived from `entropy`.
+///
+/// See [`message_id::generate_with_entropy`].
+///
+/// # Errors
+///
+/// Returns [`Error::EmptyIdLeft`] if `entropy` is empty, and
+/// [`Error::EmptyIdRight`] or [`Error::InvalidChar`] if `domain`
+/// isn't a valid `id-right`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::headers::content_id::Error> {
+/// let content_id = email_encoding::headers::content_id::generate_with_entropy(
+///     &[0xDE, 0xAD, 0xBE, 0xEF],
+///     "example.com",
+/// )?;
+/// assert_eq!(content_id, "DEADBEEF@example.com");
+/// # Ok(())
+/// # }
+/// ```
+pub fn generate_with_entropy(entropy: &[u8], domain: &str) -> Result<String, Error> {
+    let msg_id = message_id::generate_with_entropy(entropy, domain).map_err(from_msg_id_error)?;
+    Ok(strip_angle_brackets(msg_id))
+}
+
+/// Validate a content-id, without its angle brackets.
+///
+/// A valid content-id is made out of a `dot-atom-text` left part and
+/// a `dot-atom-text` or domain literal right part, separated by `@`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::content_id::{validate, Error};
+/// assert_eq!(validate("logo.png@example.com"), Ok(()));
+///
+/// assert_eq!(validate("logo.png"), Err(Error::MissingAt));
+/// assert_eq!(
+///     validate("<logo.png@example.com>"),
+///     Err(Error::InvalidChar {
+///         position: 0,
+///         c: '<'
+///     })
+/// );
+/// ```
+pub fn validate(content_id: &str) -> Result<(), Error> {
+    message_id::validate(&format!("<{}>", content_id)).map_err(from_msg_id_error)
+}
+
+/// Encode a content-id, enclosing it in angle brackets.
+///
+/// `content_id` is validated via [`validate`]. It's moved to a new line
+/// if it fits there, but not on the current one.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::content_id::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     writer.start_header("Content-ID").map_err(|_| std::fmt::Error)?;
+///     email_encoding::headers::content_id::encode("logo.png@example.com", &mut writer)?;
+/// }
+/// assert_eq!(output, "Content-ID: <logo.png@example.com>");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode<W: ?Sized + Write>(
+    content_id: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    validate(content_id)?;
+
+    utils::write_unfolded(&format!("<{}>", content_id), w)?;
+    Ok(())
+}
+
+/// Convert an error about the msg-id `<content_id>`
+fn from_msg_id_error(err: message_id::Error) -> Error {
+    match err {
+        message_id::Error::Fmt(err) => Error::Fmt(err),
+        message_id::Error::MissingAt => Error::MissingAt,
+        message_id::Error::EmptyIdLeft => Error::EmptyIdLeft,
+        message_id::Error::EmptyIdRight => Error::EmptyIdRight,
+        message_id::Error::InvalidChar { position, c } => Error::InvalidChar {
+            position: position - "<".len(),
+            c,
+        },
+        message_id::Error::Random => Error::Random,
+        message_id::Error::MissingAngleBrackets => {
+            unreachable!("angle brackets are always added")
+        }
+    }
+}
+
+fn strip_angle_brackets(mut msg_id: String) -> String {
+    msg_id.pop();
+    msg_id.remove(0);
+    msg_id
+}
+
+/// Error returned by the functions of this module.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The content-id doesn't contain an `@`
+    MissingAt,
+    /// The left part of the content-id is empty
+    EmptyIdLeft,
+    /// The right part of the content-id is empty
+    EmptyIdRight,
+    /// The content-id contains a char which isn't allowed
+    InvalidChar {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The operating system couldn't provide random bytes
+    ///
+    /// Only returned if the `rand` feature is enabled.
+    Random,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::MissingAt => f.write_str("content-id doesn't contain an `@`"),
+            Self::EmptyIdLeft => f.write_str("left part of content-id is empty"),
+            Self::EmptyIdRight => f.write_str("right part of content-id is empty"),
+            Self::InvalidChar { position, c } => {
+                write!(f, "invalid char {:?} in content-id at byte {}", c, position)
+            }
+            Self::Random => f.write_str("couldn't obtain random bytes"),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn invalid() {
+        assert_eq!(validate("@example.com"), Err(Error::EmptyIdLeft));
+        assert_eq!(validate("logo@"), Err(Error::EmptyIdRight));
+        assert_eq!(
+            validate("logo@exa mple.com"),
+            Err(Error::InvalidChar {
+                position: 8,
+                c: ' '
+            })
+        );
+        assert_eq!(
+            generate_with_entropy(&[1], "example com"),
+            Err(Error::InvalidChar {
+                position: 10,
+                c: ' '
+            })
+        );
+    }
+
+    #[test]
+    fn unfolded() {
+        let mut s = "Content-ID:".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode(&format!("{}@example.com", "0123456789".repeat(6)), &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-ID:\r\n",
+                " <012345678901234567890123456789012345678901234567890123456789@example.com>"
+            )
+        );
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random() {
+        let content_id = generate("example.com").unwrap();
+        assert_eq!(validate(&content_id), Ok(()));
+        assert_eq!(content_id.len(), 32 + "@example.com".len());
+    }
+}
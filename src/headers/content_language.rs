@@ -0,0 +1,239 @@
+//! [RFC 3282] `Content-Language` header encoder.
+//!
+//! [RFC 3282]: https://datatracker.ietf.org/doc/html/rfc3282#section-2
+
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use super::writer::EmailWriter;
+
+const MAX_SUBTAG_LEN: usize = 8;
+
+/// Encode a list of language tags, as found in `Content-Language`.
+///
+/// Language tags are separated by commas, and lines are only folded
+/// between tags. Every tag is validated via [`validate`] before
+/// anything gets written.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::content_language::Error> {
+/// let mut output = String::from("Content-Language: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::content_language::encode(["en-US", "es-419"], &mut writer)?;
+/// }
+/// assert_eq!(output, "Content-Language: en-US, es-419");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode<'a, I, W>(tags: I, w: &mut EmailWriter<'_, W>) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a str>,
+    W: ?Sized + Write,
+{
+    let tags = tags.into_iter().collect::<Vec<_>>();
+    if tags.is_empty() {
+        return Err(Error::NoTags);
+    }
+    for (index, tag) in tags.iter().enumerate() {
+        validate(tag).map_err(|err| match err {
+            Error::InvalidTag { position, .. } => Error::InvalidTag { index, position },
+            err => err,
+        })?;
+    }
+
+    for (i, tag) in tags.into_iter().enumerate() {
+        if i > 0 {
+            w.write_char(',')?;
+            w.space();
+            if w.projected_line_len() + tag.len() + ",".len() > w.max_line_len() {
+                w.new_line()?;
+            }
+        }
+
+        w.write_str(tag)?;
+    }
+
+    Ok(())
+}
+
+/// Validate a language tag against the basic [RFC 5646] syntax.
+///
+/// A valid tag is made out of subtags of 1 to 8 ASCII letters and
+/// digits, separated by hyphens. The first subtag must be a language
+/// made out of 2 to 8 letters, or one of the `x` (private use) and `i`
+/// (grandfathered) singletons. The subtags aren't checked against
+/// the IANA registry.
+///
+/// `index` is always `0` in the returned errors.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::content_language::{validate, Error};
+/// assert_eq!(validate("en"), Ok(()));
+/// assert_eq!(validate("zh-Hant-TW"), Ok(()));
+/// assert_eq!(validate("x-klingon"), Ok(()));
+///
+/// assert_eq!(
+///     validate("en_US"),
+///     Err(Error::InvalidTag {
+///         index: 0,
+///         position: 2
+///     })
+/// );
+/// ```
+///
+/// [RFC 5646]: https://datatracker.ietf.org/doc/html/rfc5646#section-2.1
+pub fn validate(tag: &str) -> Result<(), Error> {
+    let mut position = 0;
+    for (i, subtag) in tag.split('-').enumerate() {
+        let invalid = Error::InvalidTag { index: 0, position };
+
+        if subtag.is_empty() || subtag.len() > MAX_SUBTAG_LEN {
+            return Err(invalid);
+        }
+        if let Some(offset) = subtag.find(|c: char| !c.is_ascii_alphanumeric()) {
+            return Err(Error::InvalidTag {
+                index: 0,
+                position: position + offset,
+            });
+        }
+        if i == 0 {
+            let is_singleton = subtag.eq_ignore_ascii_case("x") || subtag.eq_ignore_ascii_case("i");
+            let is_language = subtag.len() >= 2 && subtag.bytes().all(|b| b.is_ascii_alphabetic());
+            if !is_singleton && !is_language {
+                return Err(invalid);
+            }
+        }
+
+        position += subtag.len() + "-".len();
+    }
+
+    Ok(())
+}
+
+/// Error returned by [`encode`] and [`validate`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The list of language tags is empty
+    NoTags,
+    /// A language tag isn't valid
+    InvalidTag {
+        /// Index of the tag in the list
+        index: usize,
+        /// Byte offset of the invalid subtag or char in the tag
+        position: usize,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::NoTags => f.write_str("list of language tags is empty"),
+            Self::InvalidTag { index, position } => {
+                write!(f, "invalid language tag {} at byte {}", index, position)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn folded() {
+        let mut s = "Content-Language: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode(
+                [
+                    "en-US",
+                    "en-GB",
+                    "fr-FR",
+                    "fr-CA",
+                    "de-DE",
+                    "de-AT",
+                    "es-ES",
+                    "es-419",
+                    "pt-BR",
+                    "zh-Hans-CN",
+                    "x-private",
+                ],
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Language: en-US, en-GB, fr-FR, fr-CA, de-DE, de-AT, es-ES, es-419,\r\n",
+                " pt-BR, zh-Hans-CN, x-private"
+            )
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(encode([], &mut w), Err(Error::NoTags));
+            assert_eq!(
+                encode(["en", "de--DE"], &mut w),
+                Err(Error::InvalidTag {
+                    index: 1,
+                    position: 3
+                })
+            );
+        }
+
+        assert_eq!(s, "");
+        assert_eq!(
+            validate("e"),
+            Err(Error::InvalidTag {
+                index: 0,
+                position: 0
+            })
+        );
+        assert_eq!(
+            validate("en-abcdefghi"),
+            Err(Error::InvalidTag {
+                index: 0,
+                position: 3
+            })
+        );
+        assert_eq!(
+            validate("1en"),
+            Err(Error::InvalidTag {
+                index: 0,
+                position: 0
+            })
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! [RFC 2183] Content-Disposition parameters.
+//!
+//! Encoders for the `size` and date parameters of the `Content-Disposition`
+//! header. See [`rfc2231`](super::rfc2231) for the `filename` parameter.
+//!
+//! [RFC 2183]: https://datatracker.ietf.org/doc/html/rfc2183#section-2
+
+use alloc::format;
+use core::fmt::{self, Write};
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{utils, writer::EmailWriter};
+
+#[cfg(feature = "std")]
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+#[cfg(feature = "std")]
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+#[cfg(feature = "std")]
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A date parameter of the `Content-Disposition` header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DateParam {
+    /// `creation-date`
+    Creation,
+    /// `modification-date`
+    Modification,
+    /// `read-date`
+    Read,
+}
+
+impl DateParam {
+    /// Get the name of the parameter.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Creation => "creation-date",
+            Self::Modification => "modification-date",
+            Self::Read => "read-date",
+        }
+    }
+}
+
+/// Encode the `size` parameter, preceded by a `;`.
+///
+/// `size` is the approximate size of the file, in bytes.
+/// The parameter is moved to a new line if it doesn't fit the current one.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> std::fmt::Result {
+/// let mut output = "Content-Disposition: attachment".to_owned();
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, true);
+///     email_encoding::headers::disposition::encode_size(4096, &mut writer)?;
+/// }
+/// assert_eq!(output, "Content-Disposition: attachment; size=4096");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_size<W: ?Sized + Write>(size: u64, w: &mut EmailWriter<'_, W>) -> fmt::Result {
+    w.write_char(';')?;
+    w.space();
+    utils::write_unfolded(&format!("size={}", size), w)
+}
+
+/// Encode a date parameter, preceded by a `;`.
+///
+/// `time` is formatted via [`format_date`] into a quoted-string.
+/// The parameter is moved to a new line if it doesn't fit the current one.
+///
+/// Requires the `std` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::DateOutOfRange`] if `time` can't be represented
+/// as an RFC 5322 date.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # use email_encoding::headers::{disposition::DateParam, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::disposition::Error> {
+/// let mut output = "Content-Disposition: attachment".to_owned();
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, true);
+///     email_encoding::headers::disposition::encode_date(
+///         DateParam::Modification,
+///         UNIX_EPOCH + Duration::from_secs(855_764_991),
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "Content-Disposition: attachment;\r\n",
+///         " modification-date=\"Wed, 12 Feb 1997 16:29:51 +0000\""
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn encode_date<W: ?Sized + Write>(
+    param: DateParam,
+    time: SystemTime,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    let date = format_date(time)?;
+
+    w.write_char(';')?;
+    w.space();
+    utils::write_unfolded(&format!("{}=\"{}\"", param.as_str(), date), w)?;
+    Ok(())
+}
+
+/// Format `time` as an [RFC 5322] date, in UTC.
+///
+/// Requires the `std` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::DateOutOfRange`] if `time` is before the year 1900,
+/// which RFC 5322 doesn't allow, or after the year 9999.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # fn main() -> Result<(), email_encoding::headers::disposition::Error> {
+/// let date = email_encoding::headers::disposition::format_date(
+///     UNIX_EPOCH + Duration::from_secs(855_764_991),
+/// )?;
+/// assert_eq!(date, "Wed, 12 Feb 1997 16:29:51 +0000");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.3
+#[cfg(feature = "std")]
+pub fn format_date(time: SystemTime) -> Result<String, Error> {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => i64::try_from(duration.as_secs()).map_err(|_| Error::DateOutOfRange)?,
+        Err(err) => {
+            let duration = err.duration();
+            // Round towards the past
+            let secs = i64::try_from(duration.as_secs()).map_err(|_| Error::DateOutOfRange)?;
+            -secs - i64::from(duration.subsec_nanos() > 0)
+        }
+    };
+
+    let days = secs.div_euclid(SECS_PER_DAY);
+    let secs_of_day = secs.rem_euclid(SECS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    if !(1900..=9999).contains(&year) {
+        return Err(Error::DateOutOfRange);
+    }
+
+    Ok(format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    ))
+}
+
+/// Convert days since the unix epoch into a `(year, month, day)` date
+/// of the proleptic Gregorian calendar.
+#[cfg(feature = "std")]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Error returned by [`encode_date`] and [`format_date`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The date can't be represented as an RFC 5322 date
+    DateOutOfRange,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::DateOutOfRange => f.write_str("date is out of the range of RFC 5322 dates"),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            format_date(UNIX_EPOCH).unwrap(),
+            "Thu, 01 Jan 1970 00:00:00 +0000"
+        );
+        assert_eq!(
+            format_date(UNIX_EPOCH + Duration::from_secs(951_782_400)).unwrap(),
+            "Tue, 29 Feb 2000 00:00:00 +0000"
+        );
+        assert_eq!(
+            format_date(UNIX_EPOCH - Duration::from_millis(500)).unwrap(),
+            "Wed, 31 Dec 1969 23:59:59 +0000"
+        );
+        assert_eq!(
+            format_date(UNIX_EPOCH - Duration::from_secs(2_208_988_801)),
+            Err(Error::DateOutOfRange)
+        );
+    }
+
+    #[test]
+    fn params() {
+        let mut s = "Content-Disposition: attachment".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            encode_size(123_456, &mut w).unwrap();
+            encode_date(DateParam::Creation, UNIX_EPOCH, &mut w).unwrap();
+            encode_date(DateParam::Read, UNIX_EPOCH, &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Disposition: attachment; size=123456;\r\n",
+                " creation-date=\"Thu, 01 Jan 1970 00:00:00 +0000\";\r\n",
+                " read-date=\"Thu, 01 Jan 1970 00:00:00 +0000\""
+            )
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! [RFC 6376] `DKIM-Signature` tag-list encoder.
+//!
+//! [RFC 6376]: https://datatracker.ietf.org/doc/html/rfc6376#section-3.2
+
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use super::writer::EmailWriter;
+
+/// The minimum number of chars of a `b=` or `bh=` value worth writing
+/// at the end of a line, instead of moving the tag to a new line.
+const MIN_BASE64_CHUNK_LEN: usize = 8;
+
+/// Encode a `DKIM-Signature` tag-list.
+///
+/// Tags are separated by `; ` and lines are folded between tags.
+/// Tags which don't fit a line on their own are folded inside of their
+/// value where [RFC 6376] allows whitespace to be ignored:
+/// anywhere inside of the base64 `b=` and `bh=` values, and after the
+/// colons of the `h=` value. Other values are never folded.
+///
+/// Folding whitespace inside of `b=` and `bh=` is ignored by verifiers,
+/// and the relaxed header canonicalization reduces any folding to a
+/// single space, so signatures computed over the encoded header stay
+/// valid.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidTagName`] if a tag name isn't made out of
+/// alphanumeric chars and `_`, starting with a letter, and
+/// [`Error::InvalidChar`] if a tag value contains whitespace, `;`
+/// or chars outside of printable US-ASCII. All tags are validated
+/// before anything gets written.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::dkim::Error> {
+/// let mut output = String::from("DKIM-Signature: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::dkim::encode_tag_list(
+///         [
+///             ("v", "1"),
+///             ("a", "rsa-sha256"),
+///             ("d", "example.com"),
+///             ("s", "selector"),
+///             ("h", "From:To:Subject:Date:Message-ID"),
+///             ("bh", "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="),
+///             ("b", ""),
+///         ],
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "DKIM-Signature: v=1; a=rsa-sha256; d=example.com; s=selector;\r\n",
+///         " h=From:To:Subject:Date:Message-ID;\r\n",
+///         " bh=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=; b="
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [RFC 6376]: https://datatracker.ietf.org/doc/html/rfc6376#section-3.5
+pub fn encode_tag_list<'a, I, W>(tags: I, w: &mut EmailWriter<'_, W>) -> Result<(), Error>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+    W: ?Sized + Write,
+{
+    let tags = tags.into_iter().collect::<Vec<_>>();
+    for (index, &(name, value)) in tags.iter().enumerate() {
+        validate_tag(index, name, value)?;
+    }
+
+    let len = tags.len();
+    for (i, (name, value)) in tags.into_iter().enumerate() {
+        let first = i == 0;
+        let last = i + 1 == len;
+        if !first {
+            w.space();
+        }
+
+        let tag_len = name.len() + "=".len() + value.len() + if last { 0 } else { ";".len() };
+        let fits_current_line = w.projected_line_len() + tag_len <= w.max_line_len();
+        let fits_new_line = " ".len() + tag_len <= w.max_line_len();
+        let can_fold = !first || w.can_go_to_new_line_now();
+
+        match name {
+            "b" | "bh" if !fits_current_line && !fits_new_line => {
+                let head_len = name.len() + "=".len() + MIN_BASE64_CHUNK_LEN;
+                if can_fold && w.projected_line_len() + head_len > w.max_line_len() {
+                    new_line(w)?;
+                }
+
+                write!(w, "{}=", name)?;
+                write_base64(value, last, w)?;
+            }
+            "h" if !fits_current_line && !fits_new_line => {
+                write!(w, "{}=", name)?;
+                write_header_names(value, last, w)?;
+            }
+            _ => {
+                if can_fold && !fits_current_line && fits_new_line {
+                    new_line(w)?;
+                }
+
+                write!(w, "{}={}", name, value)?;
+            }
+        }
+
+        if !last {
+            w.write_char(';')?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Go to a new line, making sure it starts with whitespace
+fn new_line<W: ?Sized + Write>(w: &mut EmailWriter<'_, W>) -> fmt::Result {
+    w.new_line()?;
+    if !w.has_spaces() {
+        w.space();
+    }
+    Ok(())
+}
+
+/// Write a base64 `value`, folding it anywhere
+fn write_base64<W: ?Sized + Write>(
+    mut value: &str,
+    last: bool,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    let reserved = if last { 0 } else { ";".len() };
+    loop {
+        if w.projected_line_len() + value.len() + reserved <= w.max_line_len() {
+            return w.write_str(value);
+        }
+
+        // Always leave at least one char for the next line, which
+        // is going to have room for the `;`
+        let chunk_len = w
+            .max_line_len()
+            .saturating_sub(w.projected_line_len())
+            .clamp(1, value.len().max(2) - 1);
+        // `value` was validated to be ASCII
+        let (chunk, rest) = value.split_at(chunk_len);
+        w.write_str(chunk)?;
+        value = rest;
+        new_line(w)?;
+    }
+}
+
+/// Write a colon separated list of header names, folding it after colons
+fn write_header_names<W: ?Sized + Write>(
+    value: &str,
+    last: bool,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    let mut names = value.split(':').peekable();
+    let mut first = true;
+    while let Some(name) = names.next() {
+        let is_last_name = names.peek().is_none();
+        let len = name.len()
+            + if !is_last_name {
+                ":".len()
+            } else if !last {
+                ";".len()
+            } else {
+                0
+            };
+        if !first && w.projected_line_len() + len > w.max_line_len() {
+            new_line(w)?;
+        }
+
+        w.write_str(name)?;
+        if !is_last_name {
+            w.write_char(':')?;
+        }
+        first = false;
+    }
+
+    Ok(())
+}
+
+fn validate_tag(index: usize, name: &str, value: &str) -> Result<(), Error> {
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        return Err(Error::InvalidTagName { index });
+    }
+
+    match value
+        .char_indices()
+        .find(|&(_, c)| !matches!(c, '!'..=':' | '<'..='~'))
+    {
+        Some((position, c)) => Err(Error::InvalidChar { index, position, c }),
+        None => Ok(()),
+    }
+}
+
+/// Error returned by [`encode_tag_list`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// A tag name is empty or contains a char which isn't allowed
+    InvalidTagName {
+        /// Index of the tag
+        index: usize,
+    },
+    /// A tag value contains a char which isn't allowed
+    InvalidChar {
+        /// Index of the tag
+        index: usize,
+        /// Byte offset of the char in the value
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::InvalidTagName { index } => write!(f, "invalid name for tag {}", index),
+            Self::InvalidChar { index, position, c } => write!(
+                f,
+                "invalid char {:?} in value of tag {} at byte {}",
+                c, index, position
+            ),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const SIGNATURE: &str = concat!(
+        "dzdVyOfAKCdLXdJOc9G2q8LoXSlEniSbav+yuU4zGeeruD00lszZVoG4ZHRNiYzR",
+        "NNOtWb0a6Uj2lo0I6BuxiQcpcfI1gDJ0kNjKJb4U9hJOQ2eiW4sG0ZXtDlHP8HqW"
+    );
+
+    #[test]
+    fn long_signature() {
+        let mut s = "DKIM-Signature: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_tag_list(
+                [
+                    ("v", "1"),
+                    ("a", "rsa-sha256"),
+                    ("c", "relaxed/relaxed"),
+                    ("d", "example.com"),
+                    ("b", SIGNATURE),
+                ],
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "DKIM-Signature: v=1; a=rsa-sha256; c=relaxed/relaxed; d=example.com;\r\n",
+                " b=dzdVyOfAKCdLXdJOc9G2q8LoXSlEniSbav+yuU4zGeeruD00lszZVoG4ZHRNiYzRNNOtWb0a6\r\n",
+                " Uj2lo0I6BuxiQcpcfI1gDJ0kNjKJb4U9hJOQ2eiW4sG0ZXtDlHP8HqW"
+            )
+        );
+        assert_eq!(
+            s.replace("\r\n ", "")
+                .split_once("b=")
+                .map(|(_, signature)| signature),
+            Some(SIGNATURE)
+        );
+    }
+
+    #[test]
+    fn base64_end_of_line() {
+        let mut s = "DKIM-Signature: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_tag_list([("v", "1"), ("bh", SIGNATURE), ("b", SIGNATURE)], &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "DKIM-Signature: v=1; bh=dzdVyOfAKCdLXdJOc9G2q8LoXSlEniSbav+yuU4zGeeruD00lszZ\r\n",
+                " VoG4ZHRNiYzRNNOtWb0a6Uj2lo0I6BuxiQcpcfI1gDJ0kNjKJb4U9hJOQ2eiW4sG0ZXtDlHP8Hq\r\n",
+                " W; b=dzdVyOfAKCdLXdJOc9G2q8LoXSlEniSbav+yuU4zGeeruD00lszZVoG4ZHRNiYzRNNOtWb\r\n",
+                " 0a6Uj2lo0I6BuxiQcpcfI1gDJ0kNjKJb4U9hJOQ2eiW4sG0ZXtDlHP8HqW"
+            )
+        );
+    }
+
+    #[test]
+    fn long_header_names() {
+        let mut s = "DKIM-Signature: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_tag_list(
+                [
+                    ("v", "1"),
+                    (
+                        "h",
+                        "From:To:Cc:Subject:Date:Message-ID:In-Reply-To:References:MIME-Version:Content-Type",
+                    ),
+                    ("b", ""),
+                ],
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "DKIM-Signature: v=1; h=From:To:Cc:Subject:Date:Message-ID:In-Reply-To:\r\n",
+                " References:MIME-Version:Content-Type; b="
+            )
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                encode_tag_list([("v", "1"), ("1a", "x")], &mut w),
+                Err(Error::InvalidTagName { index: 1 })
+            );
+            assert_eq!(
+                encode_tag_list([("v", "1"), ("d", "example.com; x=y")], &mut w),
+                Err(Error::InvalidChar {
+                    index: 1,
+                    position: 11,
+                    c: ';'
+                })
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+}
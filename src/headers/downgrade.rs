@@ -0,0 +1,441 @@
+//! [RFC 6857] downgrading of internationalized headers.
+//!
+//! Messages using [RFC 6532] internationalized headers can only be
+//! relayed to servers supporting the `SMTPUTF8` extension. The functions
+//! of this module rewrite such headers into ASCII-only equivalents,
+//! for when the message has to be relayed to a server which doesn't.
+//!
+//! [RFC 6857]: https://datatracker.ietf.org/doc/html/rfc6857
+//! [RFC 6532]: https://datatracker.ietf.org/doc/html/rfc6532
+
+use alloc::{format, string::String};
+use core::fmt::{self, Write};
+
+use super::{
+    address::{self, Mailbox},
+    rfc2047, unstructured, utils,
+    writer::{self, EmailWriter},
+};
+
+/// Headers whose values are lists of addresses, which must be
+/// downgraded via [`downgrade_mailbox_list`].
+const ADDRESS_HEADERS: &[&str] = &[
+    "From",
+    "Sender",
+    "Reply-To",
+    "To",
+    "Cc",
+    "Bcc",
+    "Resent-From",
+    "Resent-Sender",
+    "Resent-To",
+    "Resent-Cc",
+    "Resent-Bcc",
+];
+
+/// Structured headers which can't be represented with encoded-words,
+/// and get renamed to `Downgraded-*` instead.
+const RENAMED_HEADERS: &[&str] = &[
+    "Message-ID",
+    "Resent-Message-ID",
+    "In-Reply-To",
+    "References",
+    "Original-Recipient",
+    "Final-Recipient",
+];
+
+/// How a header got downgraded by [`downgrade_header`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Technique {
+    /// The header was already ASCII-only and was written as is
+    Unchanged,
+    /// The value was encoded via RFC 2047 encoded-words
+    EncodedWords,
+    /// The header was renamed to `Downgraded-*`, with its value
+    /// encoded via RFC 2047 encoded-words
+    Renamed,
+}
+
+/// Downgrade a header, other than the address headers.
+///
+/// * ASCII-only values are written unchanged, except that they get
+///   unfolded and refolded: line breaks which aren't folds are replaced
+///   by a space, so that they can't be used to inject headers.
+/// * Structured headers which can't contain encoded-words, such as
+///   `Message-ID` and `References`, are renamed to `Downgraded-*`
+///   (see [`write_downgraded`]).
+/// * Any other header is treated as unstructured and non-ASCII words are
+///   encoded via RFC 2047 (see [`unstructured::encode`]).
+///
+/// The header is started via [`EmailWriter::start_header`].
+///
+/// # Errors
+///
+/// Returns [`Error::AddressHeader`] if `name` is an address header,
+/// such as `From` or `To`, whose mailboxes must be downgraded via
+/// [`downgrade_mailbox_list`], and [`Error::InvalidFieldName`] if `name`
+/// isn't a valid field name.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{downgrade::Technique, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::downgrade::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     let technique = email_encoding::headers::downgrade::downgrade_header(
+///         "Subject",
+///         "¡Hola, señor!",
+///         &mut writer,
+///     )?;
+///     assert_eq!(technique, Technique::EncodedWords);
+///
+///     let technique = email_encoding::headers::downgrade::downgrade_header(
+///         "Message-ID",
+///         "<1234@bücher.example>",
+///         &mut writer,
+///     )?;
+///     assert_eq!(technique, Technique::Renamed);
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "Subject: =?utf-8?b?wqFIb2xhLCBzZcOxb3Ih?=\r\n",
+///         "Downgraded-Message-ID: =?utf-8?b?PDEyMzRAYsO8Y2hlci5leGFtcGxlPg==?="
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn downgrade_header<W: ?Sized + Write>(
+    name: &str,
+    value: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<Technique, Error> {
+    if is_one_of(name, ADDRESS_HEADERS) {
+        return Err(Error::AddressHeader);
+    }
+
+    if value.is_ascii() {
+        w.start_header(name)?;
+        w.folding().write_str(&utils::unfold(value))?;
+        Ok(Technique::Unchanged)
+    } else if is_one_of(name, RENAMED_HEADERS) {
+        write_downgraded(name, value, w)?;
+        Ok(Technique::Renamed)
+    } else {
+        w.start_header(name)?;
+        ascii_only(w, |w| unstructured::encode(value, w))?;
+        Ok(Technique::EncodedWords)
+    }
+}
+
+/// Write the `Downgraded-*` version of the header called `name`.
+///
+/// `value` is encoded via RFC 2047 encoded-words, so that the original
+/// value can be recovered by upgrading the message again.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::downgrade::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     email_encoding::headers::downgrade::write_downgraded(
+///         "In-Reply-To",
+///         "<1234@bücher.example>",
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     "Downgraded-In-Reply-To: =?utf-8?b?PDEyMzRAYsO8Y2hlci5leGFtcGxlPg==?="
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_downgraded<W: ?Sized + Write>(
+    name: &str,
+    value: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    w.start_header(&format!("Downgraded-{}", name))?;
+    ascii_only(w, |w| rfc2047::encode(value, w))?;
+    Ok(())
+}
+
+/// Downgrade a list of mailboxes, such as the value of the `From` or `To` headers.
+///
+/// Display names are encoded via RFC 2047. Non-ASCII domains are converted
+/// to A-labels if the `idna` feature is enabled. Mailboxes whose address
+/// still can't be represented in ASCII, because of a non-ASCII local part,
+/// are replaced by an empty group whose name is the encoded-word of the
+/// original mailbox, as described by [RFC 6857].
+///
+/// # Errors
+///
+/// Stops at the first ASCII-only mailbox which can't be encoded.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{address::Mailbox, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::downgrade::Error> {
+/// let mailboxes = [
+///     Mailbox::new(Some("Adrián"), "adrian", "example.com"),
+///     Mailbox::new(None, "josé", "example.com"),
+/// ];
+///
+/// let mut output = String::from("To: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::downgrade::downgrade_mailbox_list(mailboxes, &mut writer)?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "To: =?utf-8?b?QWRyacOhbg==?= <adrian@example.com>, =?utf-8?b?am9zw6lAZXhh?=\r\n",
+///         " =?utf-8?b?bXBsZS5jb20=?= :;"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [RFC 6857]: https://datatracker.ietf.org/doc/html/rfc6857#section-3.1.9
+pub fn downgrade_mailbox_list<'a, I, W>(
+    mailboxes: I,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Mailbox<'a>>,
+    W: ?Sized + Write,
+{
+    ascii_only(w, |w| {
+        for (i, mailbox) in mailboxes.into_iter().enumerate() {
+            if i > 0 {
+                w.write_char(',')?;
+                w.space();
+            }
+
+            downgrade_mailbox(mailbox, w)?;
+        }
+
+        Ok(())
+    })
+}
+
+fn downgrade_mailbox<W: ?Sized + Write>(
+    mailbox: Mailbox<'_>,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    if mailbox.local_part.is_ascii() && mailbox.domain.is_ascii() {
+        encode_mailbox(mailbox, w)?;
+        return Ok(());
+    }
+
+    let mut scratch = String::new();
+    if encode_mailbox(mailbox, &mut w.scratch(&mut scratch)).is_ok() {
+        // The domain could be converted to A-labels
+        encode_mailbox(mailbox, w)?;
+        return Ok(());
+    }
+
+    let original = match mailbox.display_name.filter(|name| !name.is_empty()) {
+        Some(display_name) => format!(
+            "{} <{}@{}>",
+            display_name, mailbox.local_part, mailbox.domain
+        ),
+        None => format!("{}@{}", mailbox.local_part, mailbox.domain),
+    };
+    rfc2047::encode(&original, w)?;
+    w.space();
+    w.write_str(":;")?;
+    Ok(())
+}
+
+fn encode_mailbox<W: ?Sized + Write>(
+    mailbox: Mailbox<'_>,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), address::Error> {
+    address::encode_mailbox(mailbox.display_name, mailbox.local_part, mailbox.domain, w)
+}
+
+/// Run `f` with raw UTF-8 disabled on `w`
+fn ascii_only<W, T, E>(
+    w: &mut EmailWriter<'_, W>,
+    f: impl FnOnce(&mut EmailWriter<'_, W>) -> Result<T, E>,
+) -> Result<T, E>
+where
+    W: ?Sized + Write,
+{
+    let allow_utf8 = w.allows_utf8();
+    w.set_allow_utf8(false);
+    let result = f(w);
+    w.set_allow_utf8(allow_utf8);
+    result
+}
+
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+/// Error returned by the functions of this module.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The header name isn't valid
+    InvalidFieldName(writer::Error),
+    /// The header is an address header, which must be downgraded
+    /// via [`downgrade_mailbox_list`]
+    AddressHeader,
+    /// A mailbox can't be encoded
+    Address(address::Error),
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl From<writer::Error> for Error {
+    fn from(err: writer::Error) -> Self {
+        match err {
+            writer::Error::Fmt(err) => Self::Fmt(err),
+            err => Self::InvalidFieldName(err),
+        }
+    }
+}
+
+impl From<address::Error> for Error {
+    fn from(err: address::Error) -> Self {
+        match err {
+            address::Error::Fmt(err) => Self::Fmt(err),
+            err => Self::Address(err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::InvalidFieldName(err) => err.fmt(f),
+            Self::AddressHeader => {
+                f.write_str("address headers must be downgraded as a list of mailboxes")
+            }
+            Self::Address(err) => write!(f, "invalid mailbox: {}", err),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn unchanged() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            w.set_allow_utf8(true);
+            assert_eq!(
+                downgrade_header("message-id", "<1234@example.com>", &mut w),
+                Ok(Technique::Unchanged)
+            );
+            assert_eq!(
+                downgrade_header("Subject", "Adrián", &mut w),
+                Ok(Technique::EncodedWords)
+            );
+            assert!(w.allows_utf8());
+        }
+
+        assert_eq!(
+            s,
+            "message-id: <1234@example.com>\r\nSubject: =?utf-8?b?QWRyacOhbg==?="
+        );
+    }
+
+    #[test]
+    fn unchanged_line_breaks() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                downgrade_header("Subject", "hi\r\nBcc: evil@example.com", &mut w),
+                Ok(Technique::Unchanged)
+            );
+            assert_eq!(
+                downgrade_header("Comments", "Hello,\r\n world", &mut w),
+                Ok(Technique::Unchanged)
+            );
+            assert_eq!(w.line_len(), "Comments: Hello, world".len());
+        }
+
+        assert_eq!(
+            s,
+            "Subject: hi Bcc: evil@example.com\r\nComments: Hello, world"
+        );
+    }
+
+    #[test]
+    fn address_header() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                downgrade_header("to", "josé@example.com", &mut w),
+                Err(Error::AddressHeader)
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn mailbox_list_display_name() {
+        let mut s = "From: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            downgrade_mailbox_list([Mailbox::new(Some("José"), "josé", "example.com")], &mut w)
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "From: =?utf-8?b?Sm9zw6kgPGpvc8OpQGV4YW1wbGUuY29tPg==?= :;"
+        );
+    }
+
+    #[cfg(feature = "idna")]
+    #[test]
+    fn mailbox_list_idna() {
+        let mut s = "From: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            downgrade_mailbox_list([Mailbox::new(None, "info", "bücher.example")], &mut w).unwrap();
+        }
+
+        assert_eq!(s, "From: info@xn--bcher-kva.example");
+    }
+}
@@ -0,0 +1,827 @@
+//! Decoding of [RFC 2047] encoded-words, and the primitives it's built on.
+//!
+//! Unlike the rest of the decoders, this is always compiled: the subject
+//! and address helpers decode the encoded-words of their input. The
+//! [`Error`] type and the charset handling are exposed by `decode`.
+//!
+//! [RFC 2047]: https://datatracker.ietf.org/doc/html/rfc2047
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{fmt, str};
+
+use super::utils;
+use crate::base64_engine;
+
+/// Error returned by the decoders of this crate.
+///
+/// Positions are byte offsets into the input of the decoder.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The input contains a byte which isn't allowed by the encoding
+    InvalidByte {
+        /// Byte offset of the byte
+        position: usize,
+        /// The invalid byte
+        byte: u8,
+    },
+    /// An `=` isn't followed by two hexadecimal digits or a line break,
+    /// or a `%` isn't followed by two hexadecimal digits
+    InvalidEscape {
+        /// Byte offset of the `=`
+        position: usize,
+    },
+    /// The base64 padding is misplaced
+    InvalidPadding {
+        /// Byte offset of the padding
+        position: usize,
+    },
+    /// The base64 input ends in the middle of a byte
+    InvalidLength,
+    /// Something looking like an encoded-word isn't one
+    InvalidEncodedWord {
+        /// Byte offset of the encoded-word
+        position: usize,
+    },
+    /// The charset isn't supported
+    UnsupportedCharset {
+        /// Byte offset of the encoded-word or of the parameter value
+        position: usize,
+    },
+    /// The decoded bytes aren't valid in their charset
+    InvalidCharsetData {
+        /// Byte offset of the encoded-word or of the parameter value
+        position: usize,
+    },
+    /// An RFC 2231 parameter is malformed or its sections don't match
+    #[cfg(feature = "decode")]
+    InvalidParameter {
+        /// Index of the parameter in the list
+        index: usize,
+    },
+    /// The `Content-Transfer-Encoding` isn't supported
+    #[cfg(feature = "decode")]
+    UnsupportedEncoding,
+    /// A quoted-string or a comment isn't closed
+    Unterminated {
+        /// Byte offset of the opening `"` or `(`
+        position: usize,
+    },
+    /// A header line isn't a field name followed by `:`, nor the continuation
+    /// of a folded field
+    InvalidField {
+        /// Byte offset of the line
+        position: usize,
+    },
+    /// A line doesn't follow the syntax of the encoding, or an expected
+    /// line is missing
+    InvalidLine {
+        /// Byte offset of the line, or the length of the input if it's missing
+        position: usize,
+    },
+    /// The input exceeds one of the [`Limits`] of the decoder
+    LimitExceeded(Limit),
+}
+
+impl Error {
+    /// Shift the position of the error by `offset` bytes
+    pub(crate) fn offset(self, offset: usize) -> Self {
+        match self {
+            Self::InvalidByte { position, byte } => Self::InvalidByte {
+                position: position + offset,
+                byte,
+            },
+            Self::InvalidEscape { position } => Self::InvalidEscape {
+                position: position + offset,
+            },
+            Self::InvalidPadding { position } => Self::InvalidPadding {
+                position: position + offset,
+            },
+            Self::InvalidEncodedWord { position } => Self::InvalidEncodedWord {
+                position: position + offset,
+            },
+            Self::UnsupportedCharset { position } => Self::UnsupportedCharset {
+                position: position + offset,
+            },
+            Self::InvalidCharsetData { position } => Self::InvalidCharsetData {
+                position: position + offset,
+            },
+            Self::Unterminated { position } => Self::Unterminated {
+                position: position + offset,
+            },
+            Self::InvalidField { position } => Self::InvalidField {
+                position: position + offset,
+            },
+            Self::InvalidLine { position } => Self::InvalidLine {
+                position: position + offset,
+            },
+            Self::InvalidLength | Self::LimitExceeded(_) => self,
+            #[cfg(feature = "decode")]
+            Self::InvalidParameter { .. } | Self::UnsupportedEncoding => self,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidByte { position, byte } => {
+                write!(f, "invalid byte {:#04x} at byte {}", byte, position)
+            }
+            Self::InvalidEscape { position } => {
+                write!(f, "invalid escape sequence at byte {}", position)
+            }
+            Self::InvalidPadding { position } => {
+                write!(f, "invalid base64 padding at byte {}", position)
+            }
+            Self::InvalidLength => f.write_str("base64 input ends in the middle of a byte"),
+            Self::InvalidEncodedWord { position } => {
+                write!(f, "invalid encoded-word at byte {}", position)
+            }
+            Self::UnsupportedCharset { position } => {
+                write!(f, "unsupported charset at byte {}", position)
+            }
+            Self::InvalidCharsetData { position } => {
+                write!(f, "text at byte {} isn't valid in its charset", position)
+            }
+            #[cfg(feature = "decode")]
+            Self::InvalidParameter { index } => write!(f, "invalid parameter {}", index),
+            #[cfg(feature = "decode")]
+            Self::UnsupportedEncoding => f.write_str("unsupported Content-Transfer-Encoding"),
+            Self::Unterminated { position } => {
+                write!(
+                    f,
+                    "unterminated quoted-string or comment at byte {}",
+                    position
+                )
+            }
+            Self::InvalidField { position } => {
+                write!(f, "invalid header field at byte {}", position)
+            }
+            Self::InvalidLine { position } => write!(f, "invalid line at byte {}", position),
+            Self::LimitExceeded(limit) => match limit {
+                Limit::OutputLen => f.write_str("decoded output is too long"),
+                Limit::EncodedWords => f.write_str("too many encoded-words"),
+                #[cfg(feature = "decode")]
+                Limit::Sections => f.write_str("too many parameter sections"),
+            },
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+/// A limit of [`Limits`], reported by [`Error::LimitExceeded`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Limit {
+    /// [`Limits::max_output_len`]
+    OutputLen,
+    /// [`Limits::max_encoded_words`]
+    EncodedWords,
+    /// [`Limits::max_sections`]
+    #[cfg(feature = "decode")]
+    Sections,
+}
+
+/// Resource limits of the decoders.
+///
+/// Decoders reading untrusted input can be given limits to bound the
+/// memory and the time they spend. Limits are unbounded by default, and
+/// each decoder only enforces those relevant to it.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "decode")] {
+/// # use email_encoding::decode::{Error, Limit, Limits};
+/// let limits = Limits {
+///     max_output_len: 4,
+///     ..Limits::default()
+/// };
+/// assert_eq!(
+///     email_encoding::body::Encoding::Base64.decode_with_limits(b"SGVsbG8h", limits),
+///     Err(Error::LimitExceeded(Limit::OutputLen))
+/// );
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum length of the decoded output, in bytes
+    ///
+    /// For decoders returning multiple values, this is their total length.
+    pub max_output_len: usize,
+    /// Maximum number of encoded-words in a header value
+    pub max_encoded_words: usize,
+    /// Maximum number of RFC 2231 sections of a parameter
+    pub max_sections: usize,
+}
+
+impl Limits {
+    /// Limits which are never exceeded.
+    pub const UNLIMITED: Self = Self {
+        max_output_len: usize::MAX,
+        max_encoded_words: usize::MAX,
+        max_sections: usize::MAX,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+/// A function mapping charset labels to the canonical names of charsets.
+///
+/// Decoders use it to extend [`canonical_charset`]: the canonical name it
+/// returns, if any, is used instead of the label.
+pub type CharsetAlias = fn(&str) -> Option<&'static str>;
+
+/// Aliases of charsets, followed by their canonical name
+const CHARSET_ALIASES: &[(&[&str], &str)] = &[
+    (&["utf8", "unicode-1-1-utf-8", "x-unicode20utf8"], "utf-8"),
+    (
+        &[
+            "ascii",
+            "us",
+            "ansi_x3.4-1968",
+            "ansi_x3.4-1986",
+            "iso646-us",
+            "iso-ir-6",
+            "iso_646.irv:1991",
+            "cp367",
+            "ibm367",
+            "csascii",
+            "646",
+        ],
+        "us-ascii",
+    ),
+    (
+        &[
+            "latin1",
+            "latin-1",
+            "l1",
+            "iso8859-1",
+            "iso88591",
+            "iso_8859-1",
+            "iso_8859-1:1987",
+            "iso-ir-100",
+            "cp819",
+            "ibm819",
+            "csisolatin1",
+        ],
+        "iso-8859-1",
+    ),
+    (
+        &[
+            "latin2",
+            "latin-2",
+            "l2",
+            "iso8859-2",
+            "iso88592",
+            "iso_8859-2",
+        ],
+        "iso-8859-2",
+    ),
+    (
+        &[
+            "latin9",
+            "latin-9",
+            "l9",
+            "iso8859-15",
+            "iso885915",
+            "iso_8859-15",
+        ],
+        "iso-8859-15",
+    ),
+    (
+        &["cp1252", "win-1252", "windows1252", "x-cp1252", "ms-ansi"],
+        "windows-1252",
+    ),
+    (
+        &[
+            "sjis",
+            "shift-jis",
+            "x-sjis",
+            "ms_kanji",
+            "cp932",
+            "windows-31j",
+        ],
+        "shift_jis",
+    ),
+    (&["ks_c_5601-1987", "cp949", "euckr"], "euc-kr"),
+    (&["cp936", "ms936", "windows-936"], "gbk"),
+];
+
+/// Get the canonical name of the charset labeled `label`.
+///
+/// Real messages use many spellings for the same charsets: the labels of
+/// a table of well-known aliases, such as `utf8`, `latin1`, `cp1252` and
+/// `ansi_x3.4-1968`, are mapped to the names of their charsets, like
+/// `utf-8`, `iso-8859-1`, `windows-1252` and `us-ascii`. The comparison
+/// is case-insensitive. `None` is returned for other labels, including
+/// canonical names.
+///
+/// The decoders of this crate resolve charsets through this table.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "decode")] {
+/// # use email_encoding::decode::canonical_charset;
+/// assert_eq!(canonical_charset("UTF8"), Some("utf-8"));
+/// assert_eq!(canonical_charset("latin1"), Some("iso-8859-1"));
+/// assert_eq!(canonical_charset("utf-8"), None);
+/// # }
+/// ```
+pub fn canonical_charset(label: &str) -> Option<&'static str> {
+    let label = label.trim();
+    CHARSET_ALIASES
+        .iter()
+        .find(|(aliases, _)| {
+            aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(label))
+        })
+        .map(|&(_, charset)| charset)
+}
+
+/// Resolve `label` via `alias`, then via [`canonical_charset`].
+pub(crate) fn resolve_charset(label: &str, alias: Option<CharsetAlias>) -> &str {
+    alias
+        .and_then(|alias| alias(label))
+        .or_else(|| canonical_charset(label))
+        .unwrap_or(label)
+}
+
+/// Run a lossy decoder, turning the first problem it reports into an error.
+pub(crate) fn strict<T>(f: impl FnOnce(&mut dyn FnMut(Error)) -> T) -> Result<T, Error> {
+    let mut first_error = None;
+    let decoded = f(&mut |err| {
+        first_error.get_or_insert(err);
+    });
+    match first_error {
+        Some(err) => Err(err),
+        None => Ok(decoded),
+    }
+}
+
+/// Decode base64, skipping whitespace and line breaks.
+///
+/// Invalid bytes are skipped, and data following the padding is
+/// decoded on its own. A trailing incomplete byte is dropped.
+pub(crate) fn base64_lossy(input: &[u8], on_error: &mut dyn FnMut(Error)) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(input.len() / 4 * 3);
+    let mut segment = Vec::new();
+    let mut padding_start = None;
+    for (position, &byte) in input.iter().enumerate() {
+        match byte {
+            b' ' | b'\t' | b'\r' | b'\n' => {}
+            b'=' => {
+                padding_start.get_or_insert(position);
+            }
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' => {
+                if let Some(position) = padding_start.take() {
+                    on_error(Error::InvalidPadding { position });
+                    base64_segment(&mut segment, &mut decoded, on_error);
+                }
+                segment.push(byte);
+            }
+            _ => on_error(Error::InvalidByte { position, byte }),
+        }
+    }
+    base64_segment(&mut segment, &mut decoded, on_error);
+
+    decoded
+}
+
+/// Decode and clear `segment`, made out of base64 chars without padding
+fn base64_segment(segment: &mut Vec<u8>, decoded: &mut Vec<u8>, on_error: &mut dyn FnMut(Error)) {
+    if segment.len() % 4 == 1 {
+        on_error(Error::InvalidLength);
+        segment.pop();
+    }
+
+    let start = decoded.len();
+    decoded.resize(start + (segment.len() + 3) / 4 * 3, 0);
+    let len = base64_engine::decode_unpadded_slice(segment, &mut decoded[start..])
+        .expect("`segment` is valid base64");
+    decoded.truncate(start + len);
+    segment.clear();
+}
+
+/// Decode the `Q` encoding of RFC 2047.
+///
+/// Invalid escapes and literal bytes which should have been escaped
+/// are kept as they are.
+pub(crate) fn q_lossy(input: &[u8], on_error: &mut dyn FnMut(Error)) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'_' => decoded.push(b' '),
+            b'=' => match hex_pair(&input[i + 1..]) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => {
+                    on_error(Error::InvalidEscape { position: i });
+                    decoded.push(b'=');
+                }
+            },
+            byte => {
+                if !byte.is_ascii_graphic() || byte == b'?' {
+                    on_error(Error::InvalidByte { position: i, byte });
+                }
+                decoded.push(byte);
+            }
+        }
+        i += 1;
+    }
+    decoded
+}
+
+/// Decode the two hexadecimal digits at the start of `s`
+pub(crate) fn hex_pair(s: &[u8]) -> Option<u8> {
+    let high = char::from(*s.first()?).to_digit(16)?;
+    let low = char::from(*s.get(1)?).to_digit(16)?;
+    Some((high * 16 + low) as u8)
+}
+
+/// Convert `bytes`, encoded with `charset`, to a `String`.
+///
+/// `charset` is resolved via [`canonical_charset`].
+/// `utf-8`, `us-ascii` and `iso-8859-1` are always supported, while the
+/// other charsets require the `encoding_rs` feature: `bytes` are given
+/// back for unsupported charsets. Invalid sequences are replaced
+/// by U+FFFD REPLACEMENT CHARACTER.
+pub(crate) fn charset_to_string(
+    charset: &str,
+    bytes: Vec<u8>,
+    on_error: &mut dyn FnMut(Error),
+) -> Result<String, Vec<u8>> {
+    let charset = canonical_charset(charset).unwrap_or(charset);
+    if charset.eq_ignore_ascii_case("utf-8") {
+        Ok(String::from_utf8(bytes).unwrap_or_else(|err| {
+            on_error(Error::InvalidCharsetData { position: 0 });
+            String::from_utf8_lossy(err.as_bytes()).into_owned()
+        }))
+    } else if charset.eq_ignore_ascii_case("us-ascii") {
+        if !bytes.is_ascii() {
+            on_error(Error::InvalidCharsetData { position: 0 });
+        }
+        Ok(bytes
+            .into_iter()
+            .map(|b| {
+                if b.is_ascii() {
+                    char::from(b)
+                } else {
+                    char::REPLACEMENT_CHARACTER
+                }
+            })
+            .collect())
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Ok(bytes.into_iter().map(char::from).collect())
+    } else {
+        legacy_charset_to_string(charset, &bytes, on_error).ok_or(bytes)
+    }
+}
+
+#[cfg(feature = "encoding_rs")]
+fn legacy_charset_to_string(
+    charset: &str,
+    bytes: &[u8],
+    on_error: &mut dyn FnMut(Error),
+) -> Option<String> {
+    let encoding = match encoding_rs::Encoding::for_label_no_replacement(charset.as_bytes()) {
+        Some(encoding) => encoding,
+        None => {
+            on_error(Error::UnsupportedCharset { position: 0 });
+            return None;
+        }
+    };
+
+    let (decoded, had_errors) = encoding.decode_without_bom_handling(bytes);
+    if had_errors {
+        on_error(Error::InvalidCharsetData { position: 0 });
+    }
+    Some(decoded.into_owned())
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+fn legacy_charset_to_string(
+    _charset: &str,
+    _bytes: &[u8],
+    on_error: &mut dyn FnMut(Error),
+) -> Option<String> {
+    on_error(Error::UnsupportedCharset { position: 0 });
+    None
+}
+
+/// Decode the encoded-words found in the unstructured value `s`.
+///
+/// Stops at the first encoded-word which can't be decoded.
+pub(crate) fn decode_words(s: &str) -> Result<Cow<'_, str>, Error> {
+    strict(|on_error| decode_words_lossy(s, false, None, Limits::UNLIMITED, on_error))
+}
+
+/// Decode the encoded-words found in the unstructured value `s`,
+/// keeping those which can't be decoded as they are.
+///
+/// See `rfc2047::DecodeOptions` for `join_words`, `charset_alias` and `limits`.
+/// Once the limit of encoded-words is exceeded, the rest of `s` is kept
+/// as it is.
+pub(crate) fn decode_words_lossy<'a>(
+    s: &'a str,
+    join_words: bool,
+    charset_alias: Option<CharsetAlias>,
+    limits: Limits,
+    on_error: &mut dyn FnMut(Error),
+) -> Cow<'a, str> {
+    if !s.contains("=?") && s.len() <= limits.max_output_len {
+        // No encoded-word
+        return Cow::Borrowed(s);
+    }
+
+    let mut decoded = String::with_capacity(s.len());
+    let mut after_encoded_word = false;
+    let mut pending: Option<PendingWords<'_>> = None;
+    let mut encoded_words = 0_usize;
+
+    let mut pos = 0;
+    while pos < s.len() {
+        let rest = &s[pos..];
+        let word_start = rest.find(|c| c != ' ' && c != '\t').unwrap_or(rest.len());
+        let whitespace = &rest[..word_start];
+        let word_len = rest[word_start..]
+            .find([' ', '\t'])
+            .unwrap_or(rest.len() - word_start);
+        let word = &rest[word_start..word_start + word_len];
+        let word_pos = pos + word_start;
+        pos = word_pos + word_len;
+
+        let is_encoded_word = word.starts_with("=?") && word.ends_with("?=") && word.len() > 4;
+        if is_encoded_word {
+            encoded_words += 1;
+            if encoded_words > limits.max_encoded_words {
+                if let Some(words) = pending.take() {
+                    words.decode(s, &mut decoded, on_error);
+                }
+                on_error(Error::LimitExceeded(Limit::EncodedWords));
+                decoded.push_str(rest);
+                break;
+            }
+        }
+        let bytes = if is_encoded_word {
+            decode_word_bytes(&word[2..word.len() - 2])
+                .map(|(charset, bytes)| (resolve_charset(charset, charset_alias), bytes))
+        } else {
+            None
+        };
+
+        if let (Some(words), Some((charset, bytes))) = (&mut pending, &bytes) {
+            if join_words && words.charset.eq_ignore_ascii_case(charset) {
+                words.bytes.extend_from_slice(bytes);
+                words.end = pos;
+                continue;
+            }
+        }
+        if let Some(words) = pending.take() {
+            after_encoded_word = words.decode(s, &mut decoded, on_error);
+        }
+
+        match bytes {
+            Some((charset, bytes)) => {
+                pending = Some(PendingWords {
+                    whitespace,
+                    after_encoded_word,
+                    charset,
+                    bytes,
+                    start: word_pos,
+                    end: pos,
+                });
+            }
+            None => {
+                if is_encoded_word {
+                    on_error(Error::InvalidEncodedWord { position: word_pos });
+                }
+                decoded.push_str(whitespace);
+                decoded.push_str(word);
+                after_encoded_word = false;
+            }
+        }
+    }
+    if let Some(words) = pending {
+        words.decode(s, &mut decoded, on_error);
+    }
+
+    if decoded.len() > limits.max_output_len {
+        on_error(Error::LimitExceeded(Limit::OutputLen));
+        let len = utils::truncate_to_char_boundary(&decoded, limits.max_output_len).len();
+        decoded.truncate(len);
+    }
+    Cow::Owned(decoded)
+}
+
+/// Adjacent encoded-words, waiting to be converted from their charset
+struct PendingWords<'a> {
+    /// Whitespace preceding the first encoded-word
+    whitespace: &'a str,
+    /// Whether the whitespace follows another encoded-word
+    after_encoded_word: bool,
+    charset: &'a str,
+    bytes: Vec<u8>,
+    /// Byte offset of the first encoded-word
+    start: usize,
+    /// Byte offset of the end of the last encoded-word
+    end: usize,
+}
+
+impl PendingWords<'_> {
+    /// Convert the words and append them to `decoded`, or append them as
+    /// they are if their charset isn't supported
+    ///
+    /// Returns whether they could be converted.
+    fn decode(self, s: &str, decoded: &mut String, on_error: &mut dyn FnMut(Error)) -> bool {
+        let start = self.start;
+        let text = charset_to_string(self.charset, self.bytes, &mut |err| {
+            on_error(err.offset(start));
+        });
+        match text {
+            Ok(text) => {
+                if !self.after_encoded_word {
+                    decoded.push_str(self.whitespace);
+                }
+                decoded.push_str(&text);
+                true
+            }
+            Err(_) => {
+                decoded.push_str(self.whitespace);
+                decoded.push_str(&s[self.start..self.end]);
+                false
+            }
+        }
+    }
+}
+
+/// Get the charset and the decoded bytes of `charset?encoding?encoded-text`
+fn decode_word_bytes(word: &str) -> Option<(&str, Vec<u8>)> {
+    let mut parts = word.splitn(3, '?');
+    let charset = parts.next()?;
+    let encoding = parts.next()?;
+    let text = parts.next()?;
+    // Strip the RFC 2231 language
+    let charset = charset.split('*').next()?;
+
+    let bytes = if encoding.eq_ignore_ascii_case("b") {
+        strict(|on_error| base64_lossy(text.as_bytes(), on_error))
+    } else if encoding.eq_ignore_ascii_case("q") {
+        // Unescaped special chars and 8-bit bytes are common in real
+        // messages, unlike invalid escapes
+        let mut invalid_escape = false;
+        let bytes = q_lossy(text.as_bytes(), &mut |err| {
+            if matches!(err, Error::InvalidEscape { .. }) {
+                invalid_escape = true;
+            }
+        });
+        if invalid_escape {
+            Err(Error::InvalidEscape { position: 0 })
+        } else {
+            Ok(bytes)
+        }
+    } else {
+        return None;
+    };
+    // Invalid encoded text is reported at the start of the encoded-word
+    Some((charset, bytes.ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn decode_words() {
+        assert_eq!(
+            super::decode_words("=?utf-8?b?QWRyacOhbg==?= =?UTF-8?Q?_rocks?= and  more "),
+            Ok("Adrián rocks and  more ".into())
+        );
+        assert_eq!(
+            super::decode_words("=?iso-8859-1*es?q?Adri=E1n?="),
+            Ok("Adrián".into())
+        );
+        assert_eq!(
+            super::decode_words("a =?x-unknown?b?QQ==?="),
+            Err(Error::UnsupportedCharset { position: 2 })
+        );
+        assert_eq!(
+            super::decode_words("a  =?utf-8?q?=C3?="),
+            Err(Error::InvalidCharsetData { position: 3 })
+        );
+        assert_eq!(
+            super::decode_words("=?utf-8?x?abc?="),
+            Err(Error::InvalidEncodedWord { position: 0 })
+        );
+        assert_eq!(
+            super::decode_words("=?utf-8?q?=ZZ?="),
+            Err(Error::InvalidEncodedWord { position: 0 })
+        );
+    }
+
+    #[test]
+    fn decode_words_lossy() {
+        let mut errors = Vec::new();
+        let decoded = super::decode_words_lossy(
+            "=?utf-8?q?a?= =?utf-8?x?b?= =?us-ascii?q?=FFc?= =?utf-8?q?d?=",
+            false,
+            None,
+            Limits::UNLIMITED,
+            &mut |err| errors.push(err),
+        );
+        assert_eq!(decoded, "a =?utf-8?x?b?= \u{FFFD}cd");
+        assert_eq!(
+            errors,
+            [
+                Error::InvalidEncodedWord { position: 14 },
+                Error::InvalidCharsetData { position: 28 },
+            ]
+        );
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn decode_words_legacy_charsets() {
+        assert_eq!(
+            super::decode_words(
+                "=?koi8-r?b?8NLJ18XU?= =?iso-8859-2?q?=BFaba?= =?windows-1252?q?=80?= \
+                 =?shift_jis?b?k/qWe4zq?= =?gb2312?b?1tDOxA==?="
+            ),
+            Ok("Приветżaba€日本語中文".into())
+        );
+        assert_eq!(
+            super::decode_words("=?shift_jis?b?gQ==?="),
+            Err(Error::InvalidCharsetData { position: 0 })
+        );
+    }
+
+    #[test]
+    fn decode_joined_words() {
+        let joined = |s| {
+            let mut errors = Vec::new();
+            let decoded = super::decode_words_lossy(s, true, None, Limits::UNLIMITED, &mut |err| {
+                errors.push(err)
+            });
+            (decoded, errors)
+        };
+
+        assert_eq!(
+            joined("a =?utf-8?b?4o==?= =?UTF-8?q?=82?=\t=?utf-8?b?rA==?= b"),
+            ("a € b".into(), vec![])
+        );
+        // Different charsets aren't joined
+        assert_eq!(
+            joined("=?utf-8?q?=C3?= =?iso-8859-1?q?=A1?="),
+            (
+                "\u{FFFD}¡".into(),
+                vec![Error::InvalidCharsetData { position: 0 }]
+            )
+        );
+        assert_eq!(
+            joined("=?x-unknown?q?a?= =?x-unknown?q?b?= =?utf-8?q?c?="),
+            (
+                "=?x-unknown?q?a?= =?x-unknown?q?b?= c".into(),
+                vec![Error::UnsupportedCharset { position: 0 }]
+            )
+        );
+    }
+
+    #[test]
+    fn decode_q_word_literals() {
+        // Unescaped literals are tolerated in encoded-words, unlike invalid escapes
+        assert_eq!(
+            super::decode_words("=?utf-8?q?caf\u{e9}\"ok\"?="),
+            Ok("caf\u{e9}\"ok\"".into())
+        );
+        assert_eq!(
+            super::decode_words("a =?utf-8?q?a=3?="),
+            Err(Error::InvalidEncodedWord { position: 2 })
+        );
+    }
+
+    #[test]
+    fn decode_words_borrowed() {
+        assert!(matches!(
+            super::decode_words("Hello, =? world"),
+            Ok(Cow::Owned(s)) if s == "Hello, =? world"
+        ));
+        assert!(matches!(
+            super::decode_words("Hello, world"),
+            Ok(Cow::Borrowed("Hello, world"))
+        ));
+    }
+}
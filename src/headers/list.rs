@@ -0,0 +1,402 @@
+//! [RFC 2369] `List-*` and [RFC 2919] `List-ID` header encoders.
+//!
+//! Covers `List-Help`, `List-Unsubscribe`, `List-Subscribe`, `List-Post`,
+//! `List-Owner` and `List-Archive`, whose values are lists of URIs,
+//! and `List-ID`.
+//!
+//! [RFC 2369]: https://datatracker.ietf.org/doc/html/rfc2369
+//! [RFC 2919]: https://datatracker.ietf.org/doc/html/rfc2919
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use super::{quoted_string, utils, writer::EmailWriter};
+
+const MAX_LIST_ID_LEN: usize = 255;
+
+/// The value of the [RFC 8058] `List-Unsubscribe-Post` header,
+/// which enables one-click unsubscription.
+///
+/// [RFC 8058]: https://datatracker.ietf.org/doc/html/rfc8058
+pub const ONE_CLICK_UNSUBSCRIBE: &str = "List-Unsubscribe=One-Click";
+
+/// Encode a list of URIs, as found in the `List-*` headers.
+///
+/// Every URI gets enclosed in angle brackets, and URIs are separated
+/// by commas. Lines are only folded between URIs, never inside of one.
+/// All URIs are validated before anything gets written.
+///
+/// # Errors
+///
+/// Returns [`Error::EmptyUri`] if a URI is empty, or [`Error::InvalidChar`]
+/// if it contains whitespace, control chars, angle brackets or
+/// non-ASCII chars, which must be percent encoded.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::list::Error> {
+/// let mut output = String::from("List-Unsubscribe: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::list::encode_uris(
+///         [
+///             "mailto:unsubscribe@lists.example.com?subject=unsubscribe",
+///             "https://lists.example.com/unsubscribe?id=0123456789abcdef",
+///         ],
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "List-Unsubscribe: <mailto:unsubscribe@lists.example.com?subject=unsubscribe>,\r\n",
+///         " <https://lists.example.com/unsubscribe?id=0123456789abcdef>"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_uris<'a, I, W>(uris: I, w: &mut EmailWriter<'_, W>) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a str>,
+    W: ?Sized + Write,
+{
+    let uris = uris.into_iter().collect::<Vec<_>>();
+    for (index, uri) in uris.iter().enumerate() {
+        validate_uri(index, uri)?;
+    }
+
+    for (i, uri) in uris.into_iter().enumerate() {
+        let len = "<>".len() + uri.len() + ",".len();
+        if i == 0 {
+            if w.can_go_to_new_line_now()
+                && w.projected_line_len() + len > w.max_line_len()
+                && " ".len() + len <= w.max_line_len()
+            {
+                // Fits a new line, but not the current one
+                w.new_line()?;
+                if !w.has_spaces() {
+                    w.space();
+                }
+            }
+        } else {
+            w.write_char(',')?;
+            w.space();
+            if w.projected_line_len() + len > w.max_line_len() {
+                // Doesn't fit the current line, even if it may not fit a new one either
+                w.new_line()?;
+            }
+        }
+
+        w.write_char('<')?;
+        w.write_str(uri)?;
+        w.write_char('>')?;
+    }
+
+    Ok(())
+}
+
+/// Encode a `List-ID` value.
+///
+/// The optional `description` is encoded like a display name, via
+/// [`quoted_string::encode_phrase`], and is followed by `list_id`
+/// enclosed in angle brackets. `list_id` is never folded.
+///
+/// # Errors
+///
+/// Returns [`Error::EmptyListId`], [`Error::InvalidListId`],
+/// [`Error::MissingListIdNamespace`] or [`Error::ListIdTooLong`] if
+/// `list_id` isn't made out of a label and a namespace as required by
+/// [RFC 2919]. Nothing is written in this case.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::list::Error> {
+/// let mut output = String::from("List-ID: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::list::encode_list_id(
+///         Some("Rust users"),
+///         "users.lists.example.com",
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(output, "List-ID: Rust users <users.lists.example.com>");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [RFC 2919]: https://datatracker.ietf.org/doc/html/rfc2919#section-2
+pub fn encode_list_id<W: ?Sized + Write>(
+    description: Option<&str>,
+    list_id: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    if list_id.is_empty() {
+        return Err(Error::EmptyListId);
+    }
+    if let Some((position, c)) = utils::find_invalid_dot_atom_char(list_id, false) {
+        return Err(Error::InvalidListId { position, c });
+    }
+    if !list_id.contains('.') {
+        return Err(Error::MissingListIdNamespace);
+    }
+    if list_id.len() > MAX_LIST_ID_LEN {
+        return Err(Error::ListIdTooLong);
+    }
+
+    let description = description.filter(|description| !description.is_empty());
+    if let Some(description) = description {
+        quoted_string::encode_phrase(description, w)?;
+        w.space();
+    }
+
+    let mut bracketed = String::with_capacity("<>".len() + list_id.len());
+    bracketed.push('<');
+    bracketed.push_str(list_id);
+    bracketed.push('>');
+    utils::write_unfolded(&bracketed, w)?;
+    Ok(())
+}
+
+fn validate_uri(index: usize, uri: &str) -> Result<(), Error> {
+    if uri.is_empty() {
+        return Err(Error::EmptyUri { index });
+    }
+    if let Some((position, c)) = uri
+        .char_indices()
+        .find(|&(_, c)| !c.is_ascii_graphic() || matches!(c, '<' | '>'))
+    {
+        return Err(Error::InvalidChar { index, position, c });
+    }
+
+    Ok(())
+}
+
+/// Error returned by [`encode_uris`] and [`encode_list_id`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// A URI is empty
+    EmptyUri {
+        /// Index of the URI
+        index: usize,
+    },
+    /// A URI contains a char which isn't allowed
+    InvalidChar {
+        /// Index of the URI
+        index: usize,
+        /// Byte offset of the char in the URI
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The list-id is empty
+    EmptyListId,
+    /// The list-id contains a char which isn't allowed
+    InvalidListId {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The list-id doesn't have a namespace after its label
+    MissingListIdNamespace,
+    /// The list-id is longer than 255 bytes
+    ListIdTooLong,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::EmptyUri { index } => write!(f, "URI {} is empty", index),
+            Self::InvalidChar { index, position, c } => {
+                write!(
+                    f,
+                    "invalid char {:?} in URI {} at byte {}",
+                    c, index, position
+                )
+            }
+            Self::EmptyListId => f.write_str("list-id is empty"),
+            Self::InvalidListId { position, c } => {
+                write!(f, "invalid char {:?} in list-id at byte {}", c, position)
+            }
+            Self::MissingListIdNamespace => f.write_str("list-id doesn't have a namespace"),
+            Self::ListIdTooLong => write!(f, "list-id is longer than {} bytes", MAX_LIST_ID_LEN),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn single() {
+        let mut s = "List-Post: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_uris(["mailto:list@example.com"], &mut w).unwrap();
+        }
+
+        assert_eq!(s, "List-Post: <mailto:list@example.com>");
+    }
+
+    #[test]
+    fn short() {
+        let mut s = "List-Help: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_uris(
+                ["mailto:help@example.com", "https://example.com/help"],
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "List-Help: <mailto:help@example.com>, <https://example.com/help>"
+        );
+    }
+
+    #[test]
+    fn long_uri() {
+        let mut s = "List-Unsubscribe: ".to_string();
+        let line_len = s.len();
+        let long_uri = format!("https://example.com/unsubscribe?token={}", "0".repeat(64));
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_uris(["mailto:u@example.com", &long_uri], &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            format!(
+                "List-Unsubscribe: <mailto:u@example.com>,\r\n <{}>",
+                long_uri
+            )
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                encode_uris(["mailto:a@example.com", ""], &mut w),
+                Err(Error::EmptyUri { index: 1 })
+            );
+            assert_eq!(
+                encode_uris(["mailto:a@example.com", "https://example.com/a b"], &mut w),
+                Err(Error::InvalidChar {
+                    index: 1,
+                    position: 21,
+                    c: ' '
+                })
+            );
+            assert_eq!(
+                encode_uris(["https://example.com/>"], &mut w),
+                Err(Error::InvalidChar {
+                    index: 0,
+                    position: 20,
+                    c: '>'
+                })
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn list_id_encoded_description() {
+        let mut s = "List-ID: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_list_id(
+                Some("Usuarios de Rust en español, la lista de correo de la comunidad"),
+                "usuarios.lists.example.com",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "List-ID: Usuarios de Rust en =?utf-8?b?ZXNwYcOxb2ws?= la lista de correo de\r\n",
+                " la comunidad <usuarios.lists.example.com>"
+            )
+        );
+    }
+
+    #[test]
+    fn list_id_no_description() {
+        let mut s = "List-ID: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_list_id(None, "users.example.com", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "List-ID: <users.example.com>");
+    }
+
+    #[test]
+    fn list_id_invalid() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(encode_list_id(None, "", &mut w), Err(Error::EmptyListId));
+            assert_eq!(
+                encode_list_id(None, "users", &mut w),
+                Err(Error::MissingListIdNamespace)
+            );
+            assert_eq!(
+                encode_list_id(Some("Users"), "users..example.com", &mut w),
+                Err(Error::InvalidListId {
+                    position: 6,
+                    c: '.'
+                })
+            );
+            assert_eq!(
+                encode_list_id(None, &format!("{}.com", "a".repeat(252)), &mut w),
+                Err(Error::ListIdTooLong)
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+}
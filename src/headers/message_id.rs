@@ -0,0 +1,448 @@
+//! [RFC 5322] Message-ID generator, validator and encoder.
+//!
+//! [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.4
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use super::{hex, utils, writer::EmailWriter};
+
+#[cfg(feature = "rand")]
+const RANDOM_LEN: usize = 16;
+
+/// Generate a new msg-id, with a random left part.
+///
+/// The left part is made out of 128 random bits, obtained from the
+/// operating system. `domain` becomes the right part and should be
+/// the domain of the host generating the message.
+///
+/// Requires the `rand` feature. See [`generate_with_entropy`]
+/// for supplying the randomness yourself.
+///
+/// # Errors
+///
+/// Returns [`Error::EmptyIdRight`] or [`Error::InvalidChar`] if `domain`
+/// isn't a valid `id-right`, and [`Error::Random`] if the operating
+/// system couldn't provide random bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::headers::message_id::Error> {
+/// let msg_id = email_encoding::headers::message_id::generate("example.com")?;
+/// assert!(msg_id.starts_with('<'));
+/// assert!(msg_id.ends_with("@example.com>"));
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "rand")]
+pub fn generate(domain: &str) -> Result<String, Error> {
+    let mut entropy = [0; RANDOM_LEN];
+    getrandom::getrandom(&mut entropy).map_err(|_| Error::Random)?;
+    generate_with_entropy(&entropy, domain)
+}
+
+/// Generate a new msg-id, with a left part derived from `entropy`.
+///
+/// `entropy` is hex encoded into the left part, so it must be unique
+/// for every message generated for `domain`: either random bytes, or
+/// a mix of a timestamp, a process identifier and a counter.
+///
+/// # Errors
+///
+/// Returns [`Error::EmptyIdLeft`] if `entropy` is empty, and
+/// [`Error::EmptyIdRight`] or [`Error::InvalidChar`] if `domain`
+/// isn't a valid `id-right`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::headers::message_id::Error> {
+/// let msg_id = email_encoding::headers::message_id::generate_with_entropy(
+///     &[0xDE, 0xAD, 0xBE, 0xEF],
+///     "example.com",
+/// )?;
+/// assert_eq!(msg_id, "<DEADBEEF@example.com>");
+/// # Ok(())
+/// # }
+/// ```
+pub fn generate_with_entropy(entropy: &[u8], domain: &str) -> Result<String, Error> {
+    let mut msg_id = String::with_capacity("<@>".len() + entropy.len() * 2 + domain.len());
+    msg_id.push('<');
+    for &byte in entropy {
+        let [high, low] = hex::encode_byte(byte);
+        msg_id.push(char::from(high));
+        msg_id.push(char::from(low));
+    }
+    msg_id.push('@');
+    msg_id.push_str(domain);
+    msg_id.push('>');
+
+    validate(&msg_id)?;
+    Ok(msg_id)
+}
+
+/// Validate a msg-id against the RFC 5322 grammar.
+///
+/// A valid msg-id is made out of a `dot-atom-text` left part and
+/// a `dot-atom-text` or domain literal right part, separated by `@`
+/// and enclosed in angle brackets. The obsolete syntax isn't accepted.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::message_id::{validate, Error};
+/// assert_eq!(validate("<1234.5678@example.com>"), Ok(()));
+/// assert_eq!(validate("<1234.5678@[192.0.2.1]>"), Ok(()));
+///
+/// assert_eq!(validate("1234.5678@example.com"), Err(Error::MissingAngleBrackets));
+/// assert_eq!(validate("<1234.5678>"), Err(Error::MissingAt));
+/// assert_eq!(
+///     validate("<1234..5678@example.com>"),
+///     Err(Error::InvalidChar {
+///         position: 6,
+///         c: '.'
+///     })
+/// );
+/// ```
+pub fn validate(msg_id: &str) -> Result<(), Error> {
+    let inner = msg_id
+        .strip_prefix('<')
+        .and_then(|msg_id| msg_id.strip_suffix('>'))
+        .ok_or(Error::MissingAngleBrackets)?;
+    let (id_left, id_right) = inner.split_once('@').ok_or(Error::MissingAt)?;
+
+    if id_left.is_empty() {
+        return Err(Error::EmptyIdLeft);
+    }
+    if id_right.is_empty() {
+        return Err(Error::EmptyIdRight);
+    }
+
+    let id_right_start = "<".len() + id_left.len() + "@".len();
+    let invalid = utils::find_invalid_dot_atom_char(id_left, false)
+        .map(|(position, c)| ("<".len() + position, c))
+        .or_else(|| {
+            utils::find_invalid_domain_char(id_right, false)
+                .map(|(position, c)| (id_right_start + position, c))
+        });
+    match invalid {
+        Some((position, c)) => Err(Error::InvalidChar { position, c }),
+        None => Ok(()),
+    }
+}
+
+/// Encode a list of msg-ids, as found in `References` and `In-Reply-To`.
+///
+/// Every msg-id is validated via [`validate`] before anything gets written.
+/// Lines are only folded between msg-ids, never inside of one.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::message_id::Error> {
+/// let mut output = String::from("References: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::message_id::encode_list(
+///         [
+///             "<1234.5678@mail.example.com>",
+///             "<8765.4321@mail.example.com>",
+///             "<abcd.efgh@mail.example.com>",
+///         ],
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "References: <1234.5678@mail.example.com> <8765.4321@mail.example.com>\r\n",
+///         " <abcd.efgh@mail.example.com>"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_list<'a, I, W>(msg_ids: I, w: &mut EmailWriter<'_, W>) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a str>,
+    W: ?Sized + Write,
+{
+    encode_list_with_options(msg_ids, ListOptions::default(), w)
+}
+
+/// Options for [`encode_list_with_options`].
+///
+/// New options may be added in minor releases, so the struct can only be
+/// constructed via [`ListOptions::default`] and the builder methods.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ListOptions {
+    /// The maximum number of msg-ids to be written.
+    ///
+    /// Longer lists get truncated from the middle: the first msg-id,
+    /// identifying the start of the thread, is kept together with
+    /// the most recent ones. At least the first msg-id is always written.
+    pub max_len: Option<usize>,
+}
+
+impl ListOptions {
+    /// Set the maximum number of msg-ids to be written.
+    ///
+    /// Defaults to no limit. See [`ListOptions::max_len`](#structfield.max_len).
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+}
+
+/// Encode a list of msg-ids, constrained by `options`.
+///
+/// See [`encode_list`]. Only the msg-ids which get written are validated.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{message_id::ListOptions, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::message_id::Error> {
+/// let mut output = String::from("References: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     let options = ListOptions::default().max_len(3);
+///     email_encoding::headers::message_id::encode_list_with_options(
+///         ["<1@example.com>", "<2@example.com>", "<3@example.com>", "<4@example.com>"],
+///         options,
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     "References: <1@example.com> <3@example.com> <4@example.com>"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_list_with_options<'a, I, W>(
+    msg_ids: I,
+    options: ListOptions,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a str>,
+    W: ?Sized + Write,
+{
+    let mut msg_ids = msg_ids.into_iter().collect::<Vec<_>>();
+    if let Some(max_len) = options.max_len {
+        if msg_ids.len() > max_len {
+            let keep_last = max_len.saturating_sub(1);
+            msg_ids.drain(1..msg_ids.len() - keep_last);
+        }
+    }
+
+    for msg_id in &msg_ids {
+        validate(msg_id)?;
+    }
+
+    for (i, msg_id) in msg_ids.into_iter().enumerate() {
+        if i == 0 {
+            utils::write_unfolded(msg_id, w)?;
+            continue;
+        }
+
+        w.space();
+        if w.projected_line_len() + msg_id.len() > w.max_line_len() {
+            // Doesn't fit the current line, even if it may not fit a new one either
+            w.new_line()?;
+        }
+        w.write_str(msg_id)?;
+    }
+
+    Ok(())
+}
+
+/// Error returned by `generate`, [`generate_with_entropy`], [`validate`]
+/// and [`encode_list`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The msg-id isn't enclosed in `<` and `>`
+    MissingAngleBrackets,
+    /// The msg-id doesn't contain an `@`
+    MissingAt,
+    /// The left part of the msg-id is empty
+    EmptyIdLeft,
+    /// The right part of the msg-id is empty
+    EmptyIdRight,
+    /// The msg-id contains a char which isn't allowed
+    InvalidChar {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The operating system couldn't provide random bytes
+    ///
+    /// Only returned if the `rand` feature is enabled.
+    Random,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::MissingAngleBrackets => f.write_str("msg-id isn't enclosed in angle brackets"),
+            Self::MissingAt => f.write_str("msg-id doesn't contain an `@`"),
+            Self::EmptyIdLeft => f.write_str("left part of msg-id is empty"),
+            Self::EmptyIdRight => f.write_str("right part of msg-id is empty"),
+            Self::InvalidChar { position, c } => {
+                write!(f, "invalid char {:?} in msg-id at byte {}", c, position)
+            }
+            Self::Random => f.write_str("couldn't obtain random bytes"),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, string::ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn entropy() {
+        assert_eq!(
+            generate_with_entropy(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB], "mail.example.com"),
+            Ok("<0123456789AB@mail.example.com>".to_owned())
+        );
+        assert_eq!(
+            generate_with_entropy(&[], "example.com"),
+            Err(Error::EmptyIdLeft)
+        );
+        assert_eq!(
+            generate_with_entropy(&[0xFF], "exa mple.com"),
+            Err(Error::InvalidChar {
+                position: 7,
+                c: ' '
+            })
+        );
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random() {
+        let first = generate("example.com").unwrap();
+        let second = generate("example.com").unwrap();
+
+        assert_eq!(first.len(), "<@example.com>".len() + RANDOM_LEN * 2);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(validate("<@example.com>"), Err(Error::EmptyIdLeft));
+        assert_eq!(validate("<1234@>"), Err(Error::EmptyIdRight));
+        assert_eq!(
+            validate("<.1234@example.com>"),
+            Err(Error::InvalidChar {
+                position: 1,
+                c: '.'
+            })
+        );
+        assert_eq!(
+            validate("<1234@example.com.>"),
+            Err(Error::InvalidChar {
+                position: 17,
+                c: '.'
+            })
+        );
+        assert_eq!(
+            validate("<1234@exa@mple.com>"),
+            Err(Error::InvalidChar {
+                position: 9,
+                c: '@'
+            })
+        );
+        assert_eq!(
+            validate("<1234@[192.0.2.[1]>"),
+            Err(Error::InvalidChar {
+                position: 15,
+                c: '['
+            })
+        );
+    }
+
+    #[test]
+    fn list_long() {
+        let mut s = "In-Reply-To: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_list(
+                [
+                    "<0123456789.0123456789.0123456789.0123456789@mail.example.com>",
+                    "<0123456789.0123456789.0123456789.0123456789.0123456789.0123456789@mail.example.com>",
+                    "<x@y>",
+                ],
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "In-Reply-To: <0123456789.0123456789.0123456789.0123456789@mail.example.com>\r\n",
+                " <0123456789.0123456789.0123456789.0123456789.0123456789.0123456789@mail.example.com>\r\n",
+                " <x@y>"
+            )
+        );
+    }
+
+    #[test]
+    fn list_truncated() {
+        let msg_ids = ["<1@a>", "<2@a>", "<3@a>", "<4@a>", "<5@a>"];
+        let encode = |max_len| {
+            let mut s = String::new();
+            {
+                let mut w = EmailWriter::new(&mut s, 0, 0, false);
+                encode_list_with_options(msg_ids, ListOptions { max_len }, &mut w).unwrap();
+            }
+            s
+        };
+
+        assert_eq!(encode(None), "<1@a> <2@a> <3@a> <4@a> <5@a>");
+        assert_eq!(encode(Some(5)), "<1@a> <2@a> <3@a> <4@a> <5@a>");
+        assert_eq!(encode(Some(2)), "<1@a> <5@a>");
+        assert_eq!(encode(Some(0)), "<1@a>");
+    }
+
+    #[test]
+    fn list_invalid() {
+        let mut s = String::new();
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                encode_list(["<1@a>", "<2@a"], &mut w),
+                Err(Error::MissingAngleBrackets)
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+}
@@ -0,0 +1,92 @@
+//! [RFC 2045] `MIME-Version` and `Content-Transfer-Encoding` header writers.
+//!
+//! [RFC 2045]: https://datatracker.ietf.org/doc/html/rfc2045
+
+use core::fmt::Write;
+
+use super::writer::{EmailWriter, Error};
+use crate::body::Encoding;
+
+/// Write a `MIME-Version: 1.0` header.
+///
+/// The header is started via [`EmailWriter::start_header`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::writer::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     email_encoding::headers::mime::write_mime_version(&mut writer)?;
+/// }
+/// assert_eq!(output, "MIME-Version: 1.0");
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_mime_version<W: ?Sized + Write>(w: &mut EmailWriter<'_, W>) -> Result<(), Error> {
+    w.start_header("MIME-Version")?;
+    w.write_str("1.0")?;
+    Ok(())
+}
+
+/// Write a `Content-Transfer-Encoding` header for `encoding`.
+///
+/// The header is started via [`EmailWriter::start_header`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::{body::Encoding, headers::writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::writer::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     email_encoding::headers::mime::write_content_transfer_encoding(
+///         Encoding::choose("Hello, World! 📬", false),
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(output, "Content-Transfer-Encoding: quoted-printable");
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_content_transfer_encoding<W: ?Sized + Write>(
+    encoding: Encoding,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    w.start_header("Content-Transfer-Encoding")?;
+    w.write_str(encoding.as_str())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn headers() {
+        let mut s = "Subject: Hello".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            write_mime_version(&mut w).unwrap();
+            write_content_transfer_encoding(Encoding::Base64, &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: Hello\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Transfer-Encoding: base64"
+            )
+        );
+    }
+}
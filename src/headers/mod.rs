@@ -1,11 +1,130 @@
 //! Email header encoding algorithms.
 
+use core::fmt;
+
+pub mod address;
+pub mod authentication_results;
+#[cfg(feature = "decode")]
+pub mod block;
+pub mod boundary;
+pub mod content_id;
+pub mod content_language;
+pub mod disposition;
+pub mod dkim;
+pub mod downgrade;
+pub(crate) mod encoded_words;
 mod hex;
-mod hex_encoding;
+pub mod list;
+pub mod message_id;
+pub mod mime;
+#[cfg(feature = "decode")]
+pub mod params;
+pub mod percent;
 pub mod quoted_string;
+pub mod received;
+pub mod resent;
 pub mod rfc2047;
 pub mod rfc2231;
+pub mod subject;
+pub mod unstructured;
 mod utils;
 pub mod writer;
 
-pub(super) const MAX_LINE_LEN: usize = 76;
+/// The default maximum length of the lines written to an
+/// [`EmailWriter`](writer::EmailWriter).
+///
+/// [RFC 5322] recommends lines of at most 78 chars, excluding the `CRLF`.
+/// This crate stays a bit below that, matching the 76 chars limit
+/// of `quoted-printable` and `base64` bodies.
+///
+/// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-2.1.1
+pub const MAX_LINE_LEN: usize = 76;
+
+/// The maximum length of a line allowed by [RFC 5322], excluding the `CRLF`.
+///
+/// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-2.1.1
+pub const HARD_LINE_LIMIT: usize = 998;
+
+/// Find out whether `name` is a valid [RFC 5322] header field name.
+///
+/// See [`validate_field_name`] for finding out why it isn't.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::is_valid_field_name;
+/// assert!(is_valid_field_name("X-Mailer"));
+/// assert!(!is_valid_field_name("X Mailer"));
+/// assert!(!is_valid_field_name(""));
+/// ```
+///
+/// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.8
+pub fn is_valid_field_name(name: &str) -> bool {
+    validate_field_name(name).is_ok()
+}
+
+/// Validate a header field name against the [RFC 5322] grammar.
+///
+/// A valid field name is made out of one or more `ftext` chars:
+/// printable US-ASCII chars except `:`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{validate_field_name, FieldNameError};
+/// assert_eq!(validate_field_name("X-Mailer"), Ok(()));
+/// assert_eq!(validate_field_name(""), Err(FieldNameError::Empty));
+/// assert_eq!(
+///     validate_field_name("X-Mailer:"),
+///     Err(FieldNameError::InvalidByte {
+///         position: 8,
+///         byte: b':'
+///     })
+/// );
+/// ```
+///
+/// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.8
+pub fn validate_field_name(name: &str) -> Result<(), FieldNameError> {
+    if name.is_empty() {
+        return Err(FieldNameError::Empty);
+    }
+    match name
+        .bytes()
+        .enumerate()
+        .find(|&(_, b)| !utils::char_is_ftext(char::from(b)))
+    {
+        Some((position, byte)) => Err(FieldNameError::InvalidByte { position, byte }),
+        None => Ok(()),
+    }
+}
+
+/// Error returned by [`validate_field_name`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FieldNameError {
+    /// The field name is empty
+    Empty,
+    /// The field name contains a byte which isn't `ftext`
+    InvalidByte {
+        /// Offset of the byte
+        position: usize,
+        /// The invalid byte
+        byte: u8,
+    },
+}
+
+impl fmt::Display for FieldNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("field name is empty"),
+            Self::InvalidByte { position, byte } => write!(
+                f,
+                "invalid byte {:?} in field name at byte {}",
+                char::from(*byte),
+                position
+            ),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for FieldNameError {}
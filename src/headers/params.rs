@@ -0,0 +1,286 @@
+//! `Content-Type` and `Content-Disposition` parameter parser.
+//!
+//! Requires the `decode` feature.
+
+use alloc::{borrow::Cow, borrow::ToOwned, vec::Vec};
+
+use super::{rfc2231, utils};
+use crate::decode::Error;
+
+/// A header value followed by `;` separated parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterizedValue<'a> {
+    /// The value preceding the parameters, like `text/plain` or `attachment`
+    pub value: &'a str,
+    /// The parameters, with their values unquoted
+    ///
+    /// Keys are kept as they are, including their RFC 2231 markers
+    /// (`filename*0*`), and values are only allocated if they contained
+    /// quoted-pairs or line breaks.
+    pub params: Vec<(&'a str, Cow<'a, str>)>,
+}
+
+impl ParameterizedValue<'_> {
+    /// Decode the parameters via [`rfc2231::decode`].
+    ///
+    /// # Errors
+    ///
+    /// See [`rfc2231::decode`].
+    pub fn decode_params(&self) -> Result<Vec<(&str, Cow<'_, str>)>, Error> {
+        rfc2231::decode(self.params.iter().map(|(key, value)| (*key, &**value)))
+    }
+}
+
+/// Parse the unfolded value of a `Content-Type` or
+/// `Content-Disposition` header.
+///
+/// The value is made out of a token, which may contain `/`, followed by
+/// `;` separated `key=value` parameters. Values are either tokens or
+/// quoted-strings. Comments and whitespace are skipped, and so are empty
+/// parameters. Unquoted values may contain `tspecials` other than `;`,
+/// `"` and `(`, which are common in real messages.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidParameter`] if a parameter has no key or isn't
+/// followed by `=`, [`Error::InvalidByte`] if something other than `;`
+/// follows a value, and [`Error::Unterminated`] if a quoted-string or a
+/// comment isn't closed.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let parsed = email_encoding::headers::params::parse(
+///     "attachment; filename*0*=utf-8''fakt%C3%BA; filename*1=\"ra 2022.pdf\"",
+/// )?;
+/// assert_eq!(parsed.value, "attachment");
+/// assert_eq!(parsed.params[0], ("filename*0*", "utf-8''fakt%C3%BA".into()));
+/// assert_eq!(parsed.params[1], ("filename*1", "ra 2022.pdf".into()));
+///
+/// assert_eq!(
+///     parsed.decode_params()?,
+///     [("filename", "faktúra 2022.pdf".into())]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::InvalidParameter`]: crate::decode::Error::InvalidParameter
+/// [`Error::InvalidByte`]: crate::decode::Error::InvalidByte
+/// [`Error::Unterminated`]: crate::decode::Error::Unterminated
+pub fn parse(s: &str) -> Result<ParameterizedValue<'_>, Error> {
+    let mut parser = Parser { s, pos: 0 };
+
+    parser.skip_cfws()?;
+    let value = parser.take_while(|c| utils::char_is_token(c) || c == '/');
+
+    let mut params = Vec::new();
+    loop {
+        parser.skip_cfws()?;
+        match parser.peek() {
+            None => break,
+            Some(';') => parser.pos += 1,
+            Some(_) => return Err(parser.invalid_byte()),
+        }
+        parser.skip_cfws()?;
+        if matches!(parser.peek(), None | Some(';')) {
+            // Empty parameter
+            continue;
+        }
+
+        let invalid = Error::InvalidParameter {
+            index: params.len(),
+        };
+        let key = parser.take_while(utils::char_is_token);
+        parser.skip_cfws()?;
+        if key.is_empty() || parser.peek() != Some('=') {
+            return Err(invalid);
+        }
+        parser.pos += 1;
+        parser.skip_cfws()?;
+
+        let value = if parser.peek() == Some('"') {
+            parser.quoted_string()?
+        } else {
+            Cow::Borrowed(
+                parser.take_while(|c| !matches!(c, ';' | '"' | '(' | ' ' | '\t' | '\r' | '\n')),
+            )
+        };
+        params.push((key, value));
+    }
+
+    Ok(ParameterizedValue { value, params })
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = &self.s[self.pos..];
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn invalid_byte(&self) -> Error {
+        Error::InvalidByte {
+            position: self.pos,
+            byte: self.s.as_bytes()[self.pos],
+        }
+    }
+
+    /// Skip whitespace, line breaks and comments
+    fn skip_cfws(&mut self) -> Result<(), Error> {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => self.pos += 1,
+                Some('(') => self.skip_comment()?,
+                Some(')') => return Err(self.invalid_byte()),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) -> Result<(), Error> {
+        let position = self.pos;
+        let mut depth = 0_usize;
+        while let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                '\\' => {
+                    if let Some(c) = self.peek() {
+                        self.pos += c.len_utf8();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Err(Error::Unterminated { position })
+    }
+
+    fn quoted_string(&mut self) -> Result<Cow<'a, str>, Error> {
+        let position = self.pos;
+        let rest = &self.s[position + 1..];
+        let end = rest
+            .find(['"', '\\', '\r', '\n'])
+            .ok_or(Error::Unterminated { position })?;
+        if rest[end..].starts_with('"') {
+            self.pos += 1 + end + 1;
+            return Ok(Cow::Borrowed(&rest[..end]));
+        }
+
+        let mut unquoted = rest[..end].to_owned();
+        let mut chars = rest[end..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += 1 + end + i + 1;
+                    return Ok(Cow::Owned(unquoted));
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) => unquoted.push(c),
+                    None => break,
+                },
+                // Unfold
+                '\r' | '\n' => {}
+                c => unquoted.push(c),
+            }
+        }
+
+        Err(Error::Unterminated { position })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn content_type() {
+        let parsed = parse("text/plain; charset=utf-8 ; format=flowed;").unwrap();
+        assert_eq!(parsed.value, "text/plain");
+        assert_eq!(
+            parsed.params,
+            [("charset", "utf-8".into()), ("format", "flowed".into())]
+        );
+    }
+
+    #[test]
+    fn no_params() {
+        let parsed = parse(" (comment) inline ").unwrap();
+        assert_eq!(parsed.value, "inline");
+        assert!(parsed.params.is_empty());
+    }
+
+    #[test]
+    fn quoted() {
+        let parsed = parse(
+            "multipart/mixed;\r\n boundary=\"=_a b\"; (x) name = \"say \\\"hi\\\"\" ;; q=\"\"",
+        )
+        .unwrap();
+        assert_eq!(parsed.value, "multipart/mixed");
+        assert_eq!(
+            parsed.params,
+            [
+                ("boundary", "=_a b".into()),
+                ("name", "say \"hi\"".into()),
+                ("q", "".into()),
+            ]
+        );
+        assert!(matches!(parsed.params[0].1, Cow::Borrowed(_)));
+        assert!(matches!(parsed.params[1].1, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn lenient_values() {
+        let parsed = parse("attachment; filename=report@2022.pdf").unwrap();
+        assert_eq!(parsed.params, [("filename", "report@2022.pdf".into())]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            parse("text/plain; charset"),
+            Err(Error::InvalidParameter { index: 0 })
+        );
+        assert_eq!(
+            parse("text/plain; a=b; =c"),
+            Err(Error::InvalidParameter { index: 1 })
+        );
+        assert_eq!(
+            parse("text/plain; a=\"b"),
+            Err(Error::Unterminated { position: 14 })
+        );
+        assert_eq!(
+            parse("text/plain; a=b (c"),
+            Err(Error::Unterminated { position: 16 })
+        );
+        assert_eq!(
+            parse("text/plain; a=b c"),
+            Err(Error::InvalidByte {
+                position: 16,
+                byte: b'c'
+            })
+        );
+    }
+}
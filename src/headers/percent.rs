@@ -0,0 +1,193 @@
+//! Percent encoding, as used by [RFC 2231] and [RFC 5987] parameters.
+//!
+//! Chars for which the `is_safe` predicate returns `false` are written as
+//! the `%XX` escapes of their UTF-8 bytes. [`is_attr_char`] and
+//! [`is_rfc2231_safe`] are the predicates used by
+//! [`rfc2231`](super::rfc2231), but any `fn(char) -> bool` can be used.
+//!
+//! [RFC 2231]: https://datatracker.ietf.org/doc/html/rfc2231#section-4
+//! [RFC 5987]: https://datatracker.ietf.org/doc/html/rfc5987#section-3.2
+
+#[cfg(feature = "decode")]
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt::{self, Write};
+
+use super::hex;
+#[cfg(feature = "decode")]
+use crate::decode::{self, Error};
+
+/// Percent encode `s`, escaping every char for which `is_safe`
+/// returns `false`.
+///
+/// The output is never folded, so it can be written to any [`Write`]r.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::percent;
+/// # fn main() -> std::fmt::Result {
+/// let mut output = String::new();
+/// percent::encode("faktúra 2022.pdf", percent::is_attr_char, &mut output)?;
+/// assert_eq!(output, "fakt%C3%BAra%202022.pdf");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Write`]: core::fmt::Write
+pub fn encode<W: ?Sized + Write>(s: &str, is_safe: fn(char) -> bool, w: &mut W) -> fmt::Result {
+    s.chars().try_for_each(|c| encode_char(c, is_safe, w))
+}
+
+/// Percent encode a single char, escaping it if `is_safe` returns `false`.
+///
+/// Useful for encoders which need to break lines between chars.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::percent;
+/// # fn main() -> std::fmt::Result {
+/// let mut output = String::new();
+/// percent::encode_char('a', percent::is_attr_char, &mut output)?;
+/// percent::encode_char('€', percent::is_attr_char, &mut output)?;
+/// assert_eq!(output, "a%E2%82%AC");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_char<W: ?Sized + Write>(
+    c: char,
+    is_safe: fn(char) -> bool,
+    w: &mut W,
+) -> fmt::Result {
+    if is_safe(c) {
+        return w.write_char(c);
+    }
+
+    let mut dst = [0; 4];
+    for &byte in c.encode_utf8(&mut dst).as_bytes() {
+        let [high, low] = hex::encode_byte(byte);
+        w.write_char('%')?;
+        w.write_char(char::from(high))?;
+        w.write_char(char::from(low))?;
+    }
+    Ok(())
+}
+
+/// Get the length of `s` once percent encoded by [`encode`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::percent;
+/// assert_eq!(percent::encoded_len("faktúra", percent::is_attr_char), "fakt%C3%BAra".len());
+/// ```
+pub fn encoded_len(s: &str, is_safe: fn(char) -> bool) -> usize {
+    s.chars()
+        .map(|c| {
+            if is_safe(c) {
+                c.len_utf8()
+            } else {
+                "%XX".len() * c.len_utf8()
+            }
+        })
+        .sum()
+}
+
+/// Decode the `%XX` escapes of `s`.
+///
+/// Chars other than `%` are kept as they are. The input is borrowed
+/// if it doesn't contain any escapes.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidEscape`] if a `%` isn't followed by two
+/// hexadecimal digits.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "decode")] {
+/// # use email_encoding::headers::percent;
+/// assert_eq!(*percent::decode("fakt%C3%BAra").unwrap(), *"faktúra".as_bytes());
+/// assert!(percent::decode("fakt%C3%B").is_err());
+/// # }
+/// ```
+///
+/// [`Error::InvalidEscape`]: crate::decode::Error::InvalidEscape
+#[cfg(feature = "decode")]
+pub fn decode(s: &str) -> Result<Cow<'_, [u8]>, Error> {
+    let bytes = s.as_bytes();
+    let first = match memchr::memchr(b'%', bytes) {
+        Some(first) => first,
+        None => return Ok(Cow::Borrowed(bytes)),
+    };
+
+    let mut decoded = Vec::with_capacity(bytes.len());
+    decoded.extend_from_slice(&bytes[..first]);
+    let mut i = first;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte =
+                decode::hex_pair(&bytes[i + 1..]).ok_or(Error::InvalidEscape { position: i })?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(Cow::Owned(decoded))
+}
+
+/// `attr-char` as defined by [RFC 5987]: ascii alphanumeric chars
+/// and ``!#$&+-.^_`|~``.
+///
+/// [RFC 5987]: https://datatracker.ietf.org/doc/html/rfc5987#section-3.2.1
+pub const fn is_attr_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '!' | '#' | '$' | '&' | '+' | '-' | '.' | '^' | '_' | '`' | '|' | '~'
+        )
+}
+
+/// The chars left unencoded by [`rfc2231`](super::rfc2231):
+/// ascii alphanumeric chars and `-_.`.
+///
+/// RFC 2231 allows more, but some email clients don't decode them.
+pub const fn is_rfc2231_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn encode_str() {
+        let mut s = String::new();
+        encode("a b-c€📬", is_rfc2231_safe, &mut s).unwrap();
+        assert_eq!(s, "a%20b-c%E2%82%AC%F0%9F%93%AC");
+        assert_eq!(encoded_len("a b-c€📬", is_rfc2231_safe), s.len());
+
+        let mut s = String::new();
+        encode("a!b~", |c| c != '!', &mut s).unwrap();
+        assert_eq!(s, "a%21b~");
+    }
+
+    #[test]
+    #[cfg(feature = "decode")]
+    fn decode_str() {
+        assert!(matches!(decode("abc"), Ok(Cow::Borrowed(b"abc"))));
+        assert_eq!(*decode("a%20b%e2%82%AC").unwrap(), *"a b€".as_bytes());
+        assert_eq!(*decode("%FF").unwrap(), [0xff]);
+        assert_eq!(decode("ab%2"), Err(Error::InvalidEscape { position: 2 }));
+        assert_eq!(decode("%%20"), Err(Error::InvalidEscape { position: 0 }));
+    }
+}
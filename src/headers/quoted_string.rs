@@ -1,11 +1,19 @@
-//! Quoted String encoder.
+//! Quoted String encoder and decoder.
 
-use std::fmt::{self, Write};
+#[cfg(feature = "decode")]
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use core::fmt::{self, Write};
 
 use super::{rfc2047, utils, writer::EmailWriter};
+#[cfg(feature = "decode")]
+use crate::decode::Error as DecodeError;
 
 /// Encode a string that may need to be quoted.
 ///
+/// Control chars, including line breaks, always get RFC 2047 encoded,
+/// so that they can't be used to inject headers.
+///
 /// # Examples
 ///
 /// ```rust
@@ -57,72 +65,486 @@ use super::{rfc2047, utils, writer::EmailWriter};
 /// # Ok(())
 /// # }
 /// ```
-pub fn encode(value: &str, w: &mut EmailWriter<'_>) -> fmt::Result {
-    #[derive(Debug)]
-    enum Strategy {
-        Plain,
-        Quoted,
-        QuotedEscaped,
-        Rfc2047,
+pub fn encode<W: ?Sized + Write>(value: &str, w: &mut EmailWriter<'_, W>) -> fmt::Result {
+    write(
+        value,
+        Strategy::choose(value, Context::Phrase, w.allows_utf8()),
+        w,
+    )
+}
+
+/// The syntactic context a string is being encoded for.
+///
+/// The context decides which chars may be written without quoting.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Context {
+    /// A display name or any other [RFC 5322] `phrase`
+    ///
+    /// Any `atext` char may be written without quoting. Dots are quoted,
+    /// since they aren't allowed in a phrase.
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.5
+    #[default]
+    Phrase,
+    /// An [RFC 5322] `dot-atom`, such as a local part or a msg-id part
+    ///
+    /// Any `atext` char may be written without quoting. Dots may too, as long as they aren't
+    /// the first or the last char, and aren't next to each other.
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.3
+    DotAtom,
+    /// An [RFC 2045] `token`, such as a `Content-Type` parameter value
+    ///
+    /// Values are never encoded via RFC 2047, since [RFC 2047] doesn't
+    /// allow encoded-words in parameter values. Values which can't be
+    /// quoted, like non-ASCII ones when UTF-8 isn't allowed, are rejected
+    /// with [`Error::Unrepresentable`] and must be encoded via
+    /// [`rfc2231`] instead.
+    ///
+    /// [RFC 2045]: https://datatracker.ietf.org/doc/html/rfc2045#section-5.1
+    /// [RFC 2047]: https://datatracker.ietf.org/doc/html/rfc2047#section-5
+    /// [`rfc2231`]: super::rfc2231
+    Token,
+}
+
+impl Context {
+    fn is_plain_char(self, b: u8) -> bool {
+        match self {
+            Self::Phrase => utils::char_is_atext(char::from(b)),
+            Self::DotAtom => utils::char_is_atext(char::from(b)) || b == b'.',
+            Self::Token => utils::char_is_token(char::from(b)),
+        }
     }
 
-    let mut strategy = Strategy::Plain;
+    fn is_quotable_char(self, b: u8) -> bool {
+        // `qtext` and spaces
+        matches!(b, b' '..=b'~') && !matches!(b, b'\\' | b'"')
+    }
 
-    let mut bytes = value.as_bytes();
+    /// The strategy required by raw UTF-8 chars, if allowed
+    fn utf8_strategy(self) -> Strategy {
+        match self {
+            Self::Phrase | Self::DotAtom => Strategy::Plain,
+            Self::Token => Strategy::Quoted,
+        }
+    }
 
-    // Plain -> Quoted
-    while !bytes.is_empty() {
-        let byte = bytes[0];
+    /// Whether the `a` `b` sequence prevents the value from being written as is
+    fn forbids_pair(self, a: u8, b: u8) -> bool {
+        match self {
+            // Don't let atoms be mistaken for RFC 2047 encoded-words
+            Self::Phrase => a == b'=' && b == b'?',
+            Self::DotAtom => a == b'.' && b == b'.',
+            Self::Token => false,
+        }
+    }
 
-        if !byte.is_ascii_alphanumeric() && !matches!(byte, b'-' | b'_' | b'.') {
-            strategy = Strategy::Quoted;
-            break;
+    /// Check that `required` can be used in this context
+    fn check(self, required: Strategy) -> Result<(), Error> {
+        match (self, required) {
+            (Self::Token, Strategy::Rfc2047) => Err(Error::Unrepresentable { required }),
+            _ => Ok(()),
         }
+    }
 
-        bytes = &bytes[1..];
+    fn allows_plain(self, first: Option<u8>, last: Option<u8>) -> bool {
+        match self {
+            Self::Phrase => true,
+            Self::DotAtom => {
+                !matches!(first, None | Some(b'.')) && !matches!(last, None | Some(b'.'))
+            }
+            Self::Token => first.is_some(),
+        }
     }
+}
+
+/// Encode a string that may need to be quoted, following the rules of `context`.
+///
+/// # Errors
+///
+/// Returns [`Error::Unrepresentable`] if `value` can't be represented in
+/// [`Context::Token`] without RFC 2047.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{quoted_string::Context, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::quoted_string::Error> {
+/// {
+///     let input = "text/plain";
+///
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_with_context(
+///             input,
+///             Context::Token,
+///             &mut writer,
+///         )?;
+///     }
+///     assert_eq!(output, "\"text/plain\"");
+/// }
+///
+/// {
+///     let input = "iso-8859-1";
+///
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_with_context(
+///             input,
+///             Context::Token,
+///             &mut writer,
+///         )?;
+///     }
+///     assert_eq!(output, "iso-8859-1");
+/// }
+///
+/// {
+///     let input = "john.smith";
+///
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_with_context(
+///             input,
+///             Context::DotAtom,
+///             &mut writer,
+///         )?;
+///     }
+///     assert_eq!(output, "john.smith");
+/// }
+///
+/// {
+///     let input = "J. Smith";
+///
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_with_context(
+///             input,
+///             Context::Phrase,
+///             &mut writer,
+///         )?;
+///     }
+///     assert_eq!(output, "\"J. Smith\"");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_with_context<W: ?Sized + Write>(
+    value: &str,
+    context: Context,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    let strategy = Strategy::choose(value, context, w.allows_utf8());
+    context.check(strategy)?;
+    write(value, strategy, w)?;
+    Ok(())
+}
 
-    // Quoted -> QuotedEscaped
-    while !bytes.is_empty() {
-        let byte = bytes[0];
+/// Options for [`encode_with_options`].
+///
+/// New options may be added in minor releases, so the struct can only be
+/// constructed via [`Options::default`] and the builder methods.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Options {
+    /// The syntactic context the string is being encoded for.
+    pub context: Context,
+    /// The least expensive strategy which may be used.
+    ///
+    /// For example [`Strategy::Quoted`] always quotes the value.
+    pub min_strategy: Strategy,
+    /// The most expensive strategy which may be used.
+    ///
+    /// For example [`Strategy::QuotedEscaped`] never falls back to RFC 2047.
+    pub max_strategy: Strategy,
+    /// Whether non-ASCII chars may be written as raw UTF-8.
+    ///
+    /// Only enable this if the message is going to be sent to a server
+    /// supporting the `SMTPUTF8` extension, as defined by [RFC 6532].
+    /// Non-ASCII phrases and dot-atoms are then written plain or quoted
+    /// instead of being encoded via RFC 2047.
+    ///
+    /// Always enabled if [`EmailWriter::allows_utf8`].
+    ///
+    /// [RFC 6532]: https://datatracker.ietf.org/doc/html/rfc6532
+    pub allow_utf8: bool,
+}
 
-        if !byte.is_ascii_alphanumeric() && !matches!(byte, b' ' | b'-' | b'_' | b'.') {
-            strategy = Strategy::QuotedEscaped;
-            break;
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            context: Context::default(),
+            min_strategy: Strategy::Plain,
+            max_strategy: Strategy::Rfc2047,
+            allow_utf8: false,
         }
+    }
+}
 
-        bytes = &bytes[1..];
+impl Options {
+    /// Set the syntactic context the string is being encoded for.
+    ///
+    /// Defaults to [`Context::Phrase`].
+    pub fn context(mut self, context: Context) -> Self {
+        self.context = context;
+        self
     }
 
-    // QuotedEscaped -> Rfc2047
-    while !bytes.is_empty() {
-        let byte = bytes[0];
+    /// Set the least expensive strategy which may be used.
+    ///
+    /// Defaults to [`Strategy::Plain`].
+    pub fn min_strategy(mut self, min_strategy: Strategy) -> Self {
+        self.min_strategy = min_strategy;
+        self
+    }
 
-        if !byte.is_ascii_alphanumeric()
-            && !matches!(byte, b'\\' | b'"' | b' ' | b'-' | b'_' | b'.')
-        {
-            strategy = Strategy::Rfc2047;
-            break;
+    /// Set the most expensive strategy which may be used.
+    ///
+    /// Defaults to [`Strategy::Rfc2047`].
+    pub fn max_strategy(mut self, max_strategy: Strategy) -> Self {
+        self.max_strategy = max_strategy;
+        self
+    }
+
+    /// Set whether non-ASCII chars may be written as raw UTF-8.
+    ///
+    /// Defaults to `false`. See [`Options::allow_utf8`](#structfield.allow_utf8).
+    pub fn allow_utf8(mut self, allow_utf8: bool) -> Self {
+        self.allow_utf8 = allow_utf8;
+        self
+    }
+}
+
+/// Encode a string that may need to be quoted, constrained by `options`.
+///
+/// Returns the [`Strategy`] which was used to encode `value`.
+///
+/// # Errors
+///
+/// Returns [`Error::Unrepresentable`] if `value` can only be represented
+/// by a strategy more expensive than [`Options::max_strategy`], or by
+/// RFC 2047 in [`Context::Token`].
+///
+/// Returns [`Error::InvalidChar`] if `value` contains a CR, LF or NUL char,
+/// which could otherwise be used to inject headers.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{
+/// #     quoted_string::{Error, Options, Strategy},
+/// #     writer::EmailWriter,
+/// # };
+/// # fn main() -> Result<(), Error> {
+/// let options = Options::default()
+///     .min_strategy(Strategy::Quoted)
+///     .max_strategy(Strategy::QuotedEscaped);
+///
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         let strategy = email_encoding::headers::quoted_string::encode_with_options(
+///             "John",
+///             options,
+///             &mut writer,
+///         )?;
+///         assert_eq!(strategy, Strategy::Quoted);
+///     }
+///     assert_eq!(output, "\"John\"");
+/// }
+///
+/// {
+///     let mut output = String::new();
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     assert_eq!(
+///         email_encoding::headers::quoted_string::encode_with_options("Adrián", options, &mut writer),
+///         Err(Error::Unrepresentable {
+///             required: Strategy::Rfc2047
+///         })
+///     );
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_with_options<W: ?Sized + Write>(
+    value: &str,
+    options: Options,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<Strategy, Error> {
+    let mut classifier = Classifier::new(options.context, options.allow_utf8 || w.allows_utf8());
+    classifier.push_str(value);
+    let required = classifier.finish_checked()?;
+    if required > options.max_strategy {
+        return Err(Error::Unrepresentable { required });
+    }
+    options.context.check(required)?;
+
+    let strategy = required.max(options.min_strategy);
+    write(value, strategy, w)?;
+    Ok(strategy)
+}
+
+/// Encode the local part of an email address.
+///
+/// Follows the [RFC 5321] and [RFC 5322] local-part rules: `local_part`
+/// is written as a dot-atom when possible, otherwise it gets quoted.
+/// Local parts are never folded nor encoded via RFC 2047.
+///
+/// # Errors
+///
+/// Returns [`Error::Unrepresentable`] if `local_part` contains
+/// non-ASCII or control chars.
+///
+/// Returns [`Error::InvalidChar`] if `local_part` contains a CR, LF or NUL char.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::headers::quoted_string::Error> {
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_local_part("john.smith", &mut writer)?;
+///     }
+///     assert_eq!(output, "john.smith");
+/// }
+///
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_local_part("john..\"smith\"", &mut writer)?;
+///     }
+///     assert_eq!(output, "\"john..\\\"smith\\\"\"");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [RFC 5321]: https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2
+/// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.4.1
+pub fn encode_local_part<W: ?Sized + Write>(
+    local_part: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    let mut classifier = Classifier::new(Context::DotAtom, w.allows_utf8());
+    classifier.push_str(local_part);
+    match classifier.finish_checked()? {
+        Strategy::Plain => {
+            w.write_str(local_part)?;
+        }
+        Strategy::Quoted | Strategy::QuotedEscaped => {
+            w.write_char('"')?;
+            utils::write_escaped(local_part, w)?;
+            w.write_char('"')?;
+        }
+        required @ Strategy::Rfc2047 => {
+            return Err(Error::Unrepresentable { required });
         }
+    }
+
+    Ok(())
+}
 
-        bytes = &bytes[1..];
+/// Encode anything implementing [`Display`](fmt::Display), constrained by `options`.
+///
+/// Works like [`encode_with_options`], but avoids having to allocate
+/// a `String` in order to encode values composed from multiple parts,
+/// such as [`format_args!`]. `value` is formatted twice: once to find out
+/// the strategy and once to write it, so its [`Display`](fmt::Display)
+/// implementation must always give the same output. Only values needing
+/// to be RFC 2047 encoded get buffered.
+///
+/// # Errors
+///
+/// Returns [`Error::Unrepresentable`] if `value` can only be represented
+/// by a strategy more expensive than [`Options::max_strategy`], or by
+/// RFC 2047 in [`Context::Token`].
+///
+/// Returns [`Error::InvalidChar`] if `value` contains a CR, LF or NUL char,
+/// which could otherwise be used to inject headers.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{
+/// #     quoted_string::{Options, Strategy},
+/// #     writer::EmailWriter,
+/// # };
+/// # fn main() -> Result<(), email_encoding::headers::quoted_string::Error> {
+/// let (first_name, last_name) = ("John", "Smith");
+///
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     let strategy = email_encoding::headers::quoted_string::encode_display(
+///         format_args!("{} {}", first_name, last_name),
+///         Options::default(),
+///         &mut writer,
+///     )?;
+///     assert_eq!(strategy, Strategy::Quoted);
+/// }
+/// assert_eq!(output, "\"John Smith\"");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_display<W: ?Sized + Write>(
+    value: impl fmt::Display,
+    options: Options,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<Strategy, Error> {
+    let mut classifier = Classifier::new(options.context, options.allow_utf8 || w.allows_utf8());
+    write!(classifier, "{}", value)?;
+    let required = classifier.finish_checked()?;
+    if required > options.max_strategy {
+        return Err(Error::Unrepresentable { required });
     }
+    options.context.check(required)?;
 
+    let strategy = required.max(options.min_strategy);
     match strategy {
         Strategy::Plain => {
-            w.write_str(value)?;
+            write!(w, "{}", value)?;
         }
-        Strategy::Quoted => {
+        Strategy::Quoted | Strategy::QuotedEscaped => {
             w.write_char('"')?;
-            w.folding().write_str(value)?;
+            let mut words = QuotedWords::new(w);
+            write!(words, "{}", value)?;
+            words.finish()?;
             w.write_char('"')?;
         }
-        Strategy::QuotedEscaped => {
+        Strategy::Rfc2047 => {
+            debug!("value can't be quoted, falling back to RFC 2047");
+            rfc2047::encode(&value.to_string(), w)?;
+        }
+    }
+    Ok(strategy)
+}
+
+fn write<W: ?Sized + Write>(
+    value: &str,
+    strategy: Strategy,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    match strategy {
+        Strategy::Plain => {
+            w.write_str(value)?;
+        }
+        Strategy::Quoted | Strategy::QuotedEscaped => {
             w.write_char('"')?;
-            utils::write_escaped(value, &mut w.folding())?;
+            let mut words = QuotedWords::new(w);
+            words.write_str(value)?;
+            words.finish()?;
             w.write_char('"')?;
         }
         Strategy::Rfc2047 => {
+            debug!("value can't be quoted, falling back to RFC 2047");
             rfc2047::encode(value, w)?;
         }
     }
@@ -130,83 +552,570 @@ pub fn encode(value: &str, w: &mut EmailWriter<'_>) -> fmt::Result {
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use pretty_assertions::assert_eq;
-
-    use super::*;
-
-    #[test]
-    fn plain() {
-        let mut s = String::new();
-        let line_len = s.len();
+/// Encode a display name or any other phrase word by word.
+///
+/// Only the words which need it get quoted or encoded via RFC 2047,
+/// instead of the whole phrase. Adjacent words needing the same treatment
+/// are quoted or encoded together. This gives a shorter and more readable
+/// output than [`encode`].
+///
+/// Phrases with leading, trailing or consecutive spaces are encoded as a
+/// whole, like [`encode`] does, so that spaces aren't lost.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> std::fmt::Result {
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_phrase("John Smith", &mut writer)?;
+///     }
+///     assert_eq!(output, "John Smith");
+/// }
+///
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_phrase("J. R. Smith", &mut writer)?;
+///     }
+///     assert_eq!(output, "\"J. R.\" Smith");
+/// }
+///
+/// {
+///     let mut output = String::new();
+///     {
+///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///         email_encoding::headers::quoted_string::encode_phrase("Adrián Smith", &mut writer)?;
+///     }
+///     assert_eq!(output, "=?utf-8?b?QWRyacOhbg==?= Smith");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_phrase<W: ?Sized + Write>(phrase: &str, w: &mut EmailWriter<'_, W>) -> fmt::Result {
+    if phrase.split(' ').any(str::is_empty) {
+        return encode(phrase, w);
+    }
 
-        {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
-            encode("1234567890abcd", &mut w).unwrap();
-        }
+    // Byte range and strategy of the words waiting to be written
+    let mut run: Option<(usize, usize, Strategy)> = None;
+    let mut wrote = false;
+    let mut start = 0;
+    for word in phrase.split(' ') {
+        let end = start + word.len();
+        let strategy = match Strategy::choose(word, Context::Phrase, w.allows_utf8()) {
+            Strategy::QuotedEscaped => Strategy::Quoted,
+            strategy => strategy,
+        };
 
-        assert_eq!(s, "1234567890abcd");
+        run = match run {
+            Some((run_start, _, run_strategy))
+                if run_strategy == strategy && strategy != Strategy::Plain =>
+            {
+                Some((run_start, end, strategy))
+            }
+            Some((run_start, run_end, run_strategy)) => {
+                write_phrase_run(&phrase[run_start..run_end], run_strategy, wrote, w)?;
+                wrote = true;
+                Some((start, end, strategy))
+            }
+            None => Some((start, end, strategy)),
+        };
+        start = end + 1;
     }
 
-    #[test]
-    fn quoted() {
-        let mut s = String::new();
-        let line_len = s.len();
+    if let Some((run_start, run_end, run_strategy)) = run {
+        write_phrase_run(&phrase[run_start..run_end], run_strategy, wrote, w)?;
+    }
 
-        {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
-            encode("1234567890 abcd", &mut w).unwrap();
-        }
+    Ok(())
+}
 
-        assert_eq!(s, "\"1234567890 abcd\"");
+fn write_phrase_run<W: ?Sized + Write>(
+    run: &str,
+    strategy: Strategy,
+    after_space: bool,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    if after_space {
+        w.space();
     }
 
-    #[test]
-    fn quoted_long() {
-        let mut s = String::new();
-        let line_len = s.len();
-
-        {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
-            encode("1234567890 abcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd", &mut w).unwrap();
+    match strategy {
+        Strategy::Plain => w.folding().write_str(run),
+        Strategy::Quoted | Strategy::QuotedEscaped => {
+            let mut quoted = String::with_capacity(run.len() + 2);
+            quoted.push('"');
+            utils::write_escaped(run, &mut quoted)?;
+            quoted.push('"');
+            w.folding().write_str(&quoted)
+        }
+        Strategy::Rfc2047 => {
+            debug!("words can't be quoted, falling back to RFC 2047");
+            rfc2047::encode(run, w)
         }
-
-        assert_eq!(s, concat!(
-            "\"1234567890\r\n",
-            " abcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd\""
-        ));
     }
+}
 
-    #[test]
-    fn quoted_escaped() {
-        let mut s = String::new();
-        let line_len = s.len();
-
-        {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
-            encode("12345\\67890 ab\"cd", &mut w).unwrap();
-        }
+/// Find out the least expensive [`Strategy`] able to represent `value` in `context`.
+///
+/// This is the strategy [`encode_with_context`] uses.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::quoted_string::{classify, Context, Strategy};
+/// assert_eq!(classify("John", Context::Phrase), Strategy::Plain);
+/// assert_eq!(classify("John Smith", Context::Phrase), Strategy::Quoted);
+/// assert_eq!(classify("Rogue \" User", Context::Phrase), Strategy::QuotedEscaped);
+/// assert_eq!(classify("Adrián", Context::Phrase), Strategy::Rfc2047);
+/// ```
+pub fn classify(value: &str, context: Context) -> Strategy {
+    Strategy::choose(value, context, false)
+}
 
-        assert_eq!(s, "\"12345\\\\67890 ab\\\"cd\"");
-    }
+/// Find out whether `value` can't be written as is in `context`.
+///
+/// Returns `true` if `value` needs to be quoted or RFC 2047 encoded.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::quoted_string::{needs_quoting, Context};
+/// assert!(!needs_quoting("john.smith", Context::DotAtom));
+/// assert!(needs_quoting("john.smith", Context::Phrase));
+/// assert!(needs_quoting("Adrián", Context::Phrase));
+/// ```
+pub fn needs_quoting(value: &str, context: Context) -> bool {
+    classify(value, context) != Strategy::Plain
+}
 
-    // TODO: get it working for the quoted escaped strategy
-    // #[test]
-    // fn quoted_escaped_long() {
-    //     let mut s = String::new();
-    //     let line_len = s.len();
-    //
-    //     {
-    //         let mut w = EmailWriter::new(&mut s, line_len, 0, false, false);
-    //         encode("12345\\67890 ab\"cdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd", &mut w).unwrap();
-    //     }
-    //
-    //     assert_eq!(s, concat!(
-    //         "\"12345\\\\67890\r\n",
-    //         " ab\\\"cdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd\""
-    //     ));
-    // }
+/// What [`decode`] does with comments.
+#[cfg(feature = "decode")]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Comments {
+    /// Keep comments as they are
+    #[default]
+    Keep,
+    /// Remove comments and the whitespace surrounding them
+    Remove,
+}
+
+/// Decode a header value made out of quoted-strings and other text.
+///
+/// Quoted-strings get unquoted and their quoted-pairs resolved, while
+/// the text around them is returned as is. Line breaks are removed,
+/// unfolding the value. Comments are kept or removed, depending on
+/// `comments`.
+///
+/// The input is borrowed when it doesn't need to be modified, or when
+/// it only needs to be stripped of its surrounding quotes.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::Unterminated`] if a quoted-string or a comment isn't
+/// closed, and [`Error::InvalidByte`] if a `)` doesn't close a comment.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::quoted_string::{decode, Comments};
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// assert_eq!(decode("\"John Smith\"", Comments::Keep)?, "John Smith");
+/// assert_eq!(
+///     decode("\"John \\\"Q\\\" Smith\" (the boss)", Comments::Keep)?,
+///     "John \"Q\" Smith (the boss)"
+/// );
+/// assert_eq!(
+///     decode("\"John \\\"Q\\\" Smith\" (the boss)", Comments::Remove)?,
+///     "John \"Q\" Smith"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::Unterminated`]: crate::decode::Error::Unterminated
+/// [`Error::InvalidByte`]: crate::decode::Error::InvalidByte
+#[cfg(feature = "decode")]
+pub fn decode(s: &str, comments: Comments) -> Result<Cow<'_, str>, DecodeError> {
+    let is_special = |c| matches!(c, '"' | '\\' | '(' | ')' | '\r' | '\n');
+    if !s.contains(is_special) {
+        return Ok(Cow::Borrowed(s));
+    }
+    if let Some(inner) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        if !inner.contains(is_special) {
+            return Ok(Cow::Borrowed(inner));
+        }
+    }
+
+    let mut decoded = String::with_capacity(s.len());
+    let mut removed_comment = false;
+    let mut chars = s.char_indices();
+    while let Some((position, c)) = chars.next() {
+        if removed_comment && !matches!(c, ' ' | '\t' | '\r' | '\n' | '(') {
+            // The comment separated the surrounding text
+            if !decoded.is_empty() {
+                decoded.push(' ');
+            }
+            removed_comment = false;
+        }
+
+        match c {
+            '"' => loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => decoded.push(c),
+                        None => return Err(DecodeError::Unterminated { position }),
+                    },
+                    Some((_, '\r' | '\n')) => {}
+                    Some((_, c)) => decoded.push(c),
+                    None => return Err(DecodeError::Unterminated { position }),
+                }
+            },
+            '(' => {
+                let mut depth = 1_usize;
+                let mut end = position + 1;
+                while depth > 0 {
+                    let (i, c) = chars.next().ok_or(DecodeError::Unterminated { position })?;
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        '\\' => {
+                            chars.next().ok_or(DecodeError::Unterminated { position })?;
+                        }
+                        _ => {}
+                    }
+                    end = i + c.len_utf8();
+                }
+
+                match comments {
+                    Comments::Keep => decoded.push_str(&s[position..end]),
+                    Comments::Remove => {
+                        let trimmed_len = decoded.trim_end_matches([' ', '\t']).len();
+                        decoded.truncate(trimmed_len);
+                        removed_comment = true;
+                    }
+                }
+            }
+            ')' => {
+                return Err(DecodeError::InvalidByte {
+                    position,
+                    byte: b')',
+                })
+            }
+            '\r' | '\n' => {}
+            ' ' | '\t' if removed_comment => {}
+            c => decoded.push(c),
+        }
+    }
+
+    Ok(Cow::Owned(decoded))
+}
+
+/// Escapes the content of a quoted string and writes it to the inner
+/// writer, folding it only between words, so that quoted-pairs are never
+/// split and escapes are accounted for.
+///
+/// Words are buffered, since they may be written in multiple chunks.
+struct QuotedWords<'a, 'b, W: ?Sized + Write> {
+    w: &'b mut EmailWriter<'a, W>,
+    word: String,
+}
+
+impl<'a, 'b, W: ?Sized + Write> QuotedWords<'a, 'b, W> {
+    fn new(w: &'b mut EmailWriter<'a, W>) -> Self {
+        Self {
+            w,
+            word: String::new(),
+        }
+    }
+
+    fn flush_word(&mut self) -> fmt::Result {
+        if !self.word.is_empty() {
+            self.w.folding().write_str(&self.word)?;
+            self.word.clear();
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> fmt::Result {
+        self.flush_word()
+    }
+}
+
+impl<W: ?Sized + Write> Write for QuotedWords<'_, '_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if c == ' ' {
+                self.flush_word()?;
+                self.w.space();
+            } else {
+                if matches!(c, '\\' | '"') {
+                    self.word.push('\\');
+                }
+                self.word.push(c);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds out the [`Strategy`] required by a value written in chunks.
+struct Classifier {
+    context: Context,
+    allow_utf8: bool,
+    strategy: Strategy,
+    first: Option<u8>,
+    last: Option<u8>,
+    len: usize,
+    line_break: Option<(usize, char)>,
+}
+
+impl Classifier {
+    fn new(context: Context, allow_utf8: bool) -> Self {
+        Self {
+            context,
+            allow_utf8,
+            strategy: Strategy::Plain,
+            first: None,
+            last: None,
+            len: 0,
+            line_break: None,
+        }
+    }
+
+    fn push(&mut self, b: u8) {
+        let context = self.context;
+        let required = if context.is_plain_char(b) {
+            Strategy::Plain
+        } else if !b.is_ascii() && self.allow_utf8 {
+            context.utf8_strategy()
+        } else if context.is_quotable_char(b) {
+            Strategy::Quoted
+        } else if matches!(b, b'\\' | b'"') {
+            Strategy::QuotedEscaped
+        } else {
+            Strategy::Rfc2047
+        };
+
+        let required = match self.last {
+            Some(last) if context.forbids_pair(last, b) => required.max(Strategy::Quoted),
+            _ => required,
+        };
+
+        self.strategy = self.strategy.max(required);
+        if self.line_break.is_none() && matches!(b, b'\r' | b'\n' | b'\0') {
+            self.line_break = Some((self.len, char::from(b)));
+        }
+
+        self.first = self.first.or(Some(b));
+        self.last = Some(b);
+        self.len += 1;
+    }
+
+    fn push_str(&mut self, s: &str) {
+        s.bytes().for_each(|b| self.push(b));
+    }
+
+    /// Like [`Classifier::finish`], but rejects line breaks and NUL chars
+    fn finish_checked(self) -> Result<Strategy, Error> {
+        match self.line_break {
+            Some((position, c)) => Err(Error::InvalidChar { position, c }),
+            None => Ok(self.finish()),
+        }
+    }
+
+    fn finish(self) -> Strategy {
+        if self.context.allows_plain(self.first, self.last) {
+            self.strategy
+        } else {
+            self.strategy.max(Strategy::Quoted)
+        }
+    }
+}
+
+impl Write for Classifier {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+/// The way a string gets encoded.
+///
+/// Strategies are ordered from the least to the most expensive one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Strategy {
+    /// Written as is (`John`)
+    Plain,
+    /// Surrounded by double quotes (`"John Smith"`)
+    Quoted,
+    /// Surrounded by double quotes, with `\` and `"` escaped (`"Rogue \" User"`)
+    QuotedEscaped,
+    /// Encoded via [RFC 2047](super::rfc2047) (`=?utf-8?b?QWRyacOhbg==?=`)
+    Rfc2047,
+}
+
+impl Strategy {
+    fn choose(value: &str, context: Context, allow_utf8: bool) -> Self {
+        let mut classifier = Classifier::new(context, allow_utf8);
+        classifier.push_str(value);
+        classifier.finish()
+    }
+}
+
+/// Error returned by [`encode_with_options`], [`encode_display`] and [`encode_local_part`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The value can't be represented by any of the allowed strategies
+    Unrepresentable {
+        /// The least expensive strategy able to represent the value
+        required: Strategy,
+    },
+    /// The value contains a line break or a NUL char
+    InvalidChar {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::Unrepresentable { required } => {
+                write!(f, "value can only be encoded as {:?}", required)
+            }
+            Self::InvalidChar { position, c } => {
+                write!(f, "invalid char {:?} in value at byte {}", c, position)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn plain() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("1234567890abcd", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "1234567890abcd");
+    }
+
+    #[test]
+    fn quoted() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("1234567890 abcd", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"1234567890 abcd\"");
+    }
+
+    #[test]
+    fn quoted_long() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("1234567890 abcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd", &mut w).unwrap();
+        }
+
+        assert_eq!(s, concat!(
+            "\"1234567890\r\n",
+            " abcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd\""
+        ));
+    }
+
+    #[test]
+    fn quoted_escaped() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("12345\\67890 ab\"cd", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"12345\\\\67890 ab\\\"cd\"");
+    }
+
+    #[test]
+    fn quoted_escaped_long() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("12345\\67890 ab\"cdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd", &mut w).unwrap();
+        }
+
+        assert_eq!(s, concat!(
+            "\"12345\\\\67890\r\n",
+            " ab\\\"cdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd\""
+        ));
+    }
+
+    #[test]
+    fn quoted_escaped_fold_budget() {
+        let mut s = "To: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode(
+                "\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\" \"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\"\" \"\"\"\"",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "To: \"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\r\n",
+                " \\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\"\\\" \\\"\\\"\\\"\\\"\""
+            )
+        );
+    }
 
     #[test]
     fn rfc2047() {
@@ -220,4 +1129,630 @@ mod tests {
 
         assert_eq!(s, "=?utf-8?b?MTIzNDVcNjc4OTAgcGVyY2jDqSBhYiJjZA==?=");
     }
+
+    #[test]
+    fn token_plain() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_with_context("1234567890.abcd+!#$", Context::Token, &mut w).unwrap();
+        }
+
+        assert_eq!(s, "1234567890.abcd+!#$");
+    }
+
+    #[test]
+    fn token_quoted() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_with_context("1234567890=abcd/(a)", Context::Token, &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"1234567890=abcd/(a)\"");
+    }
+
+    #[test]
+    fn token_empty() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_with_context("", Context::Token, &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"\"");
+    }
+
+    #[test]
+    fn token_quoted_escaped() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_with_context("ab\"cd;", Context::Token, &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"ab\\\"cd;\"");
+    }
+
+    #[test]
+    fn token_non_ascii() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            assert_eq!(
+                encode_with_context("ü", Context::Token, &mut w),
+                Err(Error::Unrepresentable {
+                    required: Strategy::Rfc2047
+                })
+            );
+            assert_eq!(
+                encode_with_options(
+                    "ü",
+                    Options {
+                        context: Context::Token,
+                        ..Options::default()
+                    },
+                    &mut w
+                ),
+                Err(Error::Unrepresentable {
+                    required: Strategy::Rfc2047
+                })
+            );
+        }
+
+        assert_eq!(s, "");
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_allow_utf8(true);
+            encode_with_context("ü", Context::Token, &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"ü\"");
+    }
+
+    #[test]
+    fn phrase_dot() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("J.Smith", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"J.Smith\"");
+    }
+
+    #[test]
+    fn phrase_specials() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("Smith, John (Sales)", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"Smith, John (Sales)\"");
+    }
+
+    #[test]
+    fn dot_atom_plain() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_with_context("john.q.smith", Context::DotAtom, &mut w).unwrap();
+        }
+
+        assert_eq!(s, "john.q.smith");
+    }
+
+    #[test]
+    fn dot_atom_quoted() {
+        for value in [".john", "john.", "john..smith", "", "john smith"] {
+            let mut s = String::new();
+            let line_len = s.len();
+
+            {
+                let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+                encode_with_context(value, Context::DotAtom, &mut w).unwrap();
+            }
+
+            assert_eq!(s, format!("\"{}\"", value));
+        }
+    }
+
+    #[test]
+    fn forced_quoted() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let options = Options {
+                min_strategy: Strategy::Quoted,
+                ..Options::default()
+            };
+            let strategy = encode_with_options("1234567890abcd", options, &mut w).unwrap();
+            assert_eq!(strategy, Strategy::Quoted);
+        }
+
+        assert_eq!(s, "\"1234567890abcd\"");
+    }
+
+    #[test]
+    fn forced_rfc2047() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let options = Options {
+                min_strategy: Strategy::Rfc2047,
+                ..Options::default()
+            };
+            let strategy = encode_with_options("abcd", options, &mut w).unwrap();
+            assert_eq!(strategy, Strategy::Rfc2047);
+        }
+
+        assert_eq!(s, "=?utf-8?b?YWJjZA==?=");
+    }
+
+    #[test]
+    fn forbidden_escaping() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let options = Options {
+                max_strategy: Strategy::Quoted,
+                ..Options::default()
+            };
+            assert_eq!(
+                encode_with_options("ab\"cd", options, &mut w),
+                Err(Error::Unrepresentable {
+                    required: Strategy::QuotedEscaped
+                })
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn chosen_strategy() {
+        let cases = [
+            ("John", Strategy::Plain),
+            ("John Smith", Strategy::Quoted),
+            ("Rogue \" User", Strategy::QuotedEscaped),
+            ("Adrián", Strategy::Rfc2047),
+        ];
+
+        for (value, expected) in cases {
+            let mut s = String::new();
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            let strategy = encode_with_options(value, Options::default(), &mut w).unwrap();
+            assert_eq!(strategy, expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn classify_contexts() {
+        let cases = [
+            ("a.b", Context::Phrase, Strategy::Quoted),
+            ("a.b", Context::DotAtom, Strategy::Plain),
+            ("a.b", Context::Token, Strategy::Plain),
+            ("a/b", Context::Token, Strategy::Quoted),
+            ("", Context::Phrase, Strategy::Plain),
+            ("", Context::DotAtom, Strategy::Quoted),
+            ("\\", Context::Token, Strategy::QuotedEscaped),
+            ("\t", Context::Phrase, Strategy::Rfc2047),
+        ];
+
+        for (value, context, expected) in cases {
+            assert_eq!(classify(value, context), expected, "{:?}", value);
+            assert_eq!(
+                needs_quoting(value, context),
+                expected != Strategy::Plain,
+                "{:?}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn local_part_not_folded() {
+        let mut s = "To: <".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            encode_local_part(
+                "a very long local part which would normally get folded by the writer",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "To: <\"a very long local part which would normally get folded by the writer\""
+        );
+    }
+
+    #[test]
+    fn local_part_non_ascii() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(
+            encode_local_part("adrián", &mut w),
+            Err(Error::Unrepresentable {
+                required: Strategy::Rfc2047
+            })
+        );
+    }
+
+    #[test]
+    fn utf8_plain() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let options = Options {
+                allow_utf8: true,
+                ..Options::default()
+            };
+            let strategy = encode_with_options("Adrián", options, &mut w).unwrap();
+            assert_eq!(strategy, Strategy::Plain);
+        }
+
+        assert_eq!(s, "Adrián");
+    }
+
+    #[test]
+    fn utf8_quoted() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let options = Options {
+                allow_utf8: true,
+                ..Options::default()
+            };
+            let strategy = encode_with_options("Adrián \"Rogue\" Pérez", options, &mut w).unwrap();
+            assert_eq!(strategy, Strategy::QuotedEscaped);
+        }
+
+        assert_eq!(s, "\"Adrián \\\"Rogue\\\" Pérez\"");
+    }
+
+    #[test]
+    fn utf8_control_chars() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+        let options = Options {
+            allow_utf8: true,
+            ..Options::default()
+        };
+
+        let strategy = encode_with_options("Adrián\u{7}", options, &mut w).unwrap();
+        assert_eq!(strategy, Strategy::Rfc2047);
+    }
+
+    #[test]
+    fn phrase_atext() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("O'Brien!#$%&*+/?=^_`{|}~", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "O'Brien!#$%&*+/?=^_`{|}~");
+    }
+
+    #[test]
+    fn phrase_encoded_word_lookalike() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("=?utf-8?q?abcd?=", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"=?utf-8?q?abcd?=\"");
+    }
+
+    #[test]
+    fn display_split_lookalike() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let strategy = encode_display(
+                format_args!("{}{}", "abc=", "?def"),
+                Options::default(),
+                &mut w,
+            )
+            .unwrap();
+            assert_eq!(strategy, Strategy::Quoted);
+        }
+
+        assert_eq!(s, "\"abc=?def\"");
+    }
+
+    #[test]
+    fn display_split_words_fold() {
+        let mut s = String::new();
+        let line_len = "From: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let strategy = encode_display(
+                format_args!(
+                    "{} {}{} \"{}\"",
+                    "Lorem ipsum dolor sit amet, consectetur",
+                    "adipis",
+                    "cing elit, sed do",
+                    "eiusmod"
+                ),
+                Options::default(),
+                &mut w,
+            )
+            .unwrap();
+            assert_eq!(strategy, Strategy::QuotedEscaped);
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "\"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do\r\n",
+                " \\\"eiusmod\\\"\"",
+            )
+        );
+    }
+
+    #[test]
+    fn display_rfc2047() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let strategy = encode_display(
+                format_args!("{} {}", "Adrián", "Smith"),
+                Options::default(),
+                &mut w,
+            )
+            .unwrap();
+            assert_eq!(strategy, Strategy::Rfc2047);
+        }
+
+        assert_eq!(s, "=?utf-8?b?QWRyacOhbiBTbWl0aA==?=");
+    }
+
+    #[test]
+    fn display_dot_atom_trailing_dot() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+        let options = Options {
+            context: Context::DotAtom,
+            ..Options::default()
+        };
+
+        let strategy = encode_display(format_args!("{}.", "john"), options, &mut w).unwrap();
+        assert_eq!(strategy, Strategy::Quoted);
+    }
+
+    #[test]
+    fn line_breaks_rejected() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(
+            encode_with_options("John\r\nBcc: x@example.com", Options::default(), &mut w),
+            Err(Error::InvalidChar {
+                position: 4,
+                c: '\r'
+            })
+        );
+        assert_eq!(
+            encode_display(
+                format_args!("{}\n{}", "John", "Smith"),
+                Options::default(),
+                &mut w
+            ),
+            Err(Error::InvalidChar {
+                position: 4,
+                c: '\n'
+            })
+        );
+        assert_eq!(
+            encode_local_part("jo\0hn", &mut w),
+            Err(Error::InvalidChar {
+                position: 2,
+                c: '\0'
+            })
+        );
+        drop(w);
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn line_breaks_encoded() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("John\r\nBcc: x", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "=?utf-8?b?Sm9obg0KQmNjOiB4?=");
+    }
+
+    #[test]
+    fn phrase_words_mixed() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_phrase("Dr. Adrián García \"Rogue\" Smith", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "\"Dr.\" =?utf-8?b?QWRyacOhbiBHYXJjw61h?= \"\\\"Rogue\\\"\" Smith"
+        );
+    }
+
+    #[test]
+    fn phrase_words_fold() {
+        let mut s = String::new();
+        let line_len = "From: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_phrase(
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Lorem ipsum dolor sit \"amet,\" consectetur adipiscing \"elit,\" sed do\r\n",
+                " eiusmod tempor",
+            )
+        );
+    }
+
+    #[test]
+    fn phrase_words_extra_spaces() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_phrase("John  Smith", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "\"John  Smith\"");
+    }
+
+    #[test]
+    fn phrase_words_empty() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_phrase("", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn phrase_writer_utf8() {
+        let mut s = String::new();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_allow_utf8(true);
+            encode_phrase("Adrián J. García", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "Adrián \"J.\" García");
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_borrowed() {
+        assert!(matches!(
+            decode("John Smith", Comments::Keep),
+            Ok(Cow::Borrowed("John Smith"))
+        ));
+        assert!(matches!(
+            decode("\"John Smith\"", Comments::Remove),
+            Ok(Cow::Borrowed("John Smith"))
+        ));
+        assert!(matches!(
+            decode("\"John\" \"Smith\"", Comments::Keep),
+            Ok(Cow::Owned(_))
+        ));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_quoted() {
+        assert_eq!(
+            decode("\"a\\\\b\\c\" d \"e\r\n f\"", Comments::Keep).unwrap(),
+            "a\\bc d e f"
+        );
+        assert_eq!(decode("\"\"", Comments::Keep).unwrap(), "");
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_comments() {
+        let input = "(start) \"a (not a comment)\" (nested (\\() comment)\r\n\tb(c)d (end)";
+        assert_eq!(
+            decode(input, Comments::Keep).unwrap(),
+            "(start) a (not a comment) (nested (\\() comment)\tb(c)d (end)"
+        );
+        assert_eq!(
+            decode(input, Comments::Remove).unwrap(),
+            "a (not a comment) b d"
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_invalid() {
+        assert_eq!(
+            decode("a \"b", Comments::Keep),
+            Err(DecodeError::Unterminated { position: 2 })
+        );
+        assert_eq!(
+            decode("\"b\\", Comments::Keep),
+            Err(DecodeError::Unterminated { position: 0 })
+        );
+        assert_eq!(
+            decode("a (b (c)", Comments::Remove),
+            Err(DecodeError::Unterminated { position: 2 })
+        );
+        assert_eq!(
+            decode("a b)", Comments::Keep),
+            Err(DecodeError::InvalidByte {
+                position: 3,
+                byte: b')'
+            })
+        );
+    }
 }
@@ -0,0 +1,293 @@
+//! [RFC 5321] `Received` header encoder.
+//!
+//! [RFC 5321]: https://datatracker.ietf.org/doc/html/rfc5321#section-4.4
+
+use alloc::format;
+use core::fmt::{self, Write};
+
+use super::{utils, writer::EmailWriter};
+
+/// Builder for the value of a `Received` header.
+///
+/// Clauses are written in the standard `from`, `by`, `via`, `with`,
+/// `id`, `for` order, followed by `;` and the date. Lines are folded
+/// between clauses, and inside of a clause only if it doesn't fit
+/// a line on its own.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{received::Received, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::received::Error> {
+/// let mut output = String::from("Received: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     Received::new("Fri, 21 Nov 1997 09:55:06 -0600")
+///         .from("client.example.org (client.example.org [192.0.2.1])")
+///         .by("mx.example.com")
+///         .with("ESMTPS")
+///         .id("4JQ8Zs1kQpz9sWf")
+///         .for_("<john@example.com>")
+///         .encode(&mut writer)?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "Received: from client.example.org (client.example.org [192.0.2.1])\r\n",
+///         " by mx.example.com with ESMTPS id 4JQ8Zs1kQpz9sWf for <john@example.com>;\r\n",
+///         " Fri, 21 Nov 1997 09:55:06 -0600"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Received<'a> {
+    from: Option<&'a str>,
+    by: Option<&'a str>,
+    via: Option<&'a str>,
+    with: Option<&'a str>,
+    id: Option<&'a str>,
+    for_: Option<&'a str>,
+    date: &'a str,
+}
+
+impl<'a> Received<'a> {
+    /// Construct a new `Received` builder, without any clause.
+    ///
+    /// `date` must already be formatted as an [RFC 5322] `date-time`.
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.3
+    pub fn new(date: &'a str) -> Self {
+        Self {
+            from: None,
+            by: None,
+            via: None,
+            with: None,
+            id: None,
+            for_: None,
+            date,
+        }
+    }
+
+    /// Set the `from` clause, identifying the host the message was received from.
+    pub fn from(mut self, from: &'a str) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Set the `by` clause, identifying the host which received the message.
+    pub fn by(mut self, by: &'a str) -> Self {
+        self.by = Some(by);
+        self
+    }
+
+    /// Set the `via` clause, naming the link the message was received over.
+    pub fn via(mut self, via: &'a str) -> Self {
+        self.via = Some(via);
+        self
+    }
+
+    /// Set the `with` clause, naming the protocol the message was received with.
+    pub fn with(mut self, with: &'a str) -> Self {
+        self.with = Some(with);
+        self
+    }
+
+    /// Set the `id` clause, identifying the message on the receiving host.
+    pub fn id(mut self, id: &'a str) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Set the `for` clause, naming the recipient the message was received for.
+    pub fn for_(mut self, for_: &'a str) -> Self {
+        self.for_ = Some(for_);
+        self
+    }
+
+    /// Encode the header value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidChar`] if any clause or the date contains
+    /// a control char other than tab. Nothing is written in this case.
+    pub fn encode<W: ?Sized + Write>(&self, w: &mut EmailWriter<'_, W>) -> Result<(), Error> {
+        let clauses = [
+            ("from", self.from),
+            ("by", self.by),
+            ("via", self.via),
+            ("with", self.with),
+            ("id", self.id),
+            ("for", self.for_),
+        ];
+
+        for (clause, value) in clauses
+            .iter()
+            .filter_map(|&(clause, value)| value.map(|value| (clause, value)))
+            .chain([("date", self.date)])
+        {
+            if let Some((position, c)) = value
+                .char_indices()
+                .find(|&(_, c)| c.is_control() && c != '\t')
+            {
+                return Err(Error::InvalidChar {
+                    clause,
+                    position,
+                    c,
+                });
+            }
+        }
+
+        let mut tokens = clauses
+            .iter()
+            .filter_map(|&(clause, value)| value.map(|value| format!("{} {}", clause, value)))
+            .peekable();
+        let mut first = true;
+        while let Some(mut token) = tokens.next() {
+            if tokens.peek().is_none() {
+                // The `;` must stay attached to the last clause
+                token.push(';');
+            }
+            utils::write_folded(&token, first, w)?;
+            first = false;
+        }
+        if first {
+            w.write_char(';')?;
+        }
+
+        utils::write_folded(self.date, false, w)?;
+        Ok(())
+    }
+}
+
+/// Error returned by [`Received::encode`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// A clause contains a control char
+    InvalidChar {
+        /// The name of the clause, or `date`
+        clause: &'static str,
+        /// Byte offset of the char in the clause
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::InvalidChar {
+                clause,
+                position,
+                c,
+            } => write!(
+                f,
+                "invalid char {:?} in `{}` clause at byte {}",
+                c, clause, position
+            ),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn short() {
+        let mut s = "Received: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            Received::new("Fri, 21 Nov 1997 09:55:06 -0600")
+                .from("a.example")
+                .by("b.example")
+                .encode(&mut w)
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "Received: from a.example by b.example; Fri, 21 Nov 1997 09:55:06 -0600"
+        );
+    }
+
+    #[test]
+    fn no_clauses() {
+        let mut s = "Received: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            Received::new("Fri, 21 Nov 1997 09:55:06 -0600")
+                .encode(&mut w)
+                .unwrap();
+        }
+
+        assert_eq!(s, "Received: ; Fri, 21 Nov 1997 09:55:06 -0600");
+    }
+
+    #[test]
+    fn long_clause() {
+        let mut s = "Received: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            Received::new("Fri, 21 Nov 1997 09:55:06 -0600")
+                .from("a.example")
+                .by("mx.example.com (Postfix) with an unusually long comment explaining the setup")
+                .encode(&mut w)
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Received: from a.example by mx.example.com (Postfix) with an unusually long\r\n",
+                " comment explaining the setup; Fri, 21 Nov 1997 09:55:06 -0600"
+            )
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                Received::new("Fri, 21 Nov 1997 09:55:06 -0600")
+                    .by("mx.example.com\r\nBcc: attacker@example.com")
+                    .encode(&mut w),
+                Err(Error::InvalidChar {
+                    clause: "by",
+                    position: 14,
+                    c: '\r'
+                })
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+}
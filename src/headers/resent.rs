@@ -0,0 +1,326 @@
+//! [RFC 5322] `Resent-*` header block writer.
+//!
+//! [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.6
+
+use core::fmt::{self, Write};
+
+use super::{
+    address::{self, Mailbox},
+    message_id,
+    writer::{self, CountingWriter, EmailWriter},
+};
+
+/// Builder for a block of `Resent-*` headers.
+///
+/// The headers are written in the order [RFC 5322] recommends:
+/// `Resent-Date`, `Resent-From`, `Resent-Sender`, `Resent-To`,
+/// `Resent-Cc`, `Resent-Bcc` and `Resent-Message-ID`.
+/// Headers which haven't been set are omitted.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{address::Mailbox, resent::Resent, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::resent::Error> {
+/// let from = [Mailbox::new(Some("Mary Smith"), "mary", "example.net")];
+/// let to = [Mailbox::new(None, "jdoe", "example.org")];
+///
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     Resent::new("Mon, 24 Nov 1997 14:22:01 -0800", &from)
+///         .to(&to)
+///         .message_id("<78910@example.net>")
+///         .write(&mut writer)?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "Resent-Date: Mon, 24 Nov 1997 14:22:01 -0800\r\n",
+///         "Resent-From: Mary Smith <mary@example.net>\r\n",
+///         "Resent-To: jdoe@example.org\r\n",
+///         "Resent-Message-ID: <78910@example.net>"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.6
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Resent<'a> {
+    date: &'a str,
+    from: &'a [Mailbox<'a>],
+    sender: Option<Mailbox<'a>>,
+    to: &'a [Mailbox<'a>],
+    cc: &'a [Mailbox<'a>],
+    bcc: Option<&'a [Mailbox<'a>]>,
+    message_id: Option<&'a str>,
+}
+
+impl<'a> Resent<'a> {
+    /// Construct a new `Resent` builder with the only mandatory headers.
+    ///
+    /// `date` must already be formatted as an [RFC 5322] `date-time`.
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.3
+    pub fn new(date: &'a str, from: &'a [Mailbox<'a>]) -> Self {
+        Self {
+            date,
+            from,
+            sender: None,
+            to: &[],
+            cc: &[],
+            bcc: None,
+            message_id: None,
+        }
+    }
+
+    /// Set `Resent-Sender`, required if `Resent-From` has multiple mailboxes.
+    pub fn sender(mut self, sender: Mailbox<'a>) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Set `Resent-To`.
+    pub fn to(mut self, to: &'a [Mailbox<'a>]) -> Self {
+        self.to = to;
+        self
+    }
+
+    /// Set `Resent-Cc`.
+    pub fn cc(mut self, cc: &'a [Mailbox<'a>]) -> Self {
+        self.cc = cc;
+        self
+    }
+
+    /// Set `Resent-Bcc`.
+    ///
+    /// Unlike the other address headers, it gets written even if `bcc` is empty.
+    pub fn bcc(mut self, bcc: &'a [Mailbox<'a>]) -> Self {
+        self.bcc = Some(bcc);
+        self
+    }
+
+    /// Set `Resent-Message-ID`.
+    pub fn message_id(mut self, message_id: &'a str) -> Self {
+        self.message_id = Some(message_id);
+        self
+    }
+
+    /// Write the headers, each one started via [`EmailWriter::start_header`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyFrom`] if there isn't any `Resent-From` mailbox,
+    /// [`Error::MissingSender`] if there are multiple ones but no
+    /// `Resent-Sender`, [`Error::InvalidDate`] if the date contains control
+    /// chars, and [`Error::Address`] or [`Error::MessageId`] if a mailbox or
+    /// the msg-id can't be encoded. Nothing is written in this case.
+    pub fn write<W: ?Sized + Write>(&self, w: &mut EmailWriter<'_, W>) -> Result<(), Error> {
+        if self.from.is_empty() {
+            return Err(Error::EmptyFrom);
+        }
+        if self.from.len() > 1 && self.sender.is_none() {
+            return Err(Error::MissingSender);
+        }
+        if let Some((position, c)) = self.date.char_indices().find(|&(_, c)| c.is_control()) {
+            return Err(Error::InvalidDate { position, c });
+        }
+
+        // Catch the errors of the inner encoders before writing anything
+        let mut counter = CountingWriter::new(0);
+        self.write_headers(&mut w.scratch(&mut counter))?;
+
+        self.write_headers(w)
+    }
+
+    fn write_headers<W: ?Sized + Write>(&self, w: &mut EmailWriter<'_, W>) -> Result<(), Error> {
+        w.start_header("Resent-Date")?;
+        w.folding().write_str(self.date)?;
+
+        w.start_header("Resent-From")?;
+        address::encode_mailbox_list(self.from.iter().copied(), w)?;
+
+        if let Some(sender) = self.sender {
+            w.start_header("Resent-Sender")?;
+            address::encode_mailbox_list([sender], w)?;
+        }
+
+        for (name, mailboxes) in [("Resent-To", self.to), ("Resent-Cc", self.cc)] {
+            if !mailboxes.is_empty() {
+                w.start_header(name)?;
+                address::encode_mailbox_list(mailboxes.iter().copied(), w)?;
+            }
+        }
+
+        if let Some(bcc) = self.bcc {
+            w.start_header("Resent-Bcc")?;
+            address::encode_mailbox_list(bcc.iter().copied(), w)?;
+        }
+
+        if let Some(message_id) = self.message_id {
+            w.start_header("Resent-Message-ID")?;
+            message_id::encode_list([message_id], w)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Error returned by [`Resent::write`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// `Resent-From` doesn't have any mailbox
+    EmptyFrom,
+    /// `Resent-From` has multiple mailboxes, but `Resent-Sender` isn't set
+    MissingSender,
+    /// The date contains a control char
+    InvalidDate {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// A mailbox can't be encoded
+    Address(address::Error),
+    /// The msg-id can't be encoded
+    MessageId(message_id::Error),
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl From<writer::Error> for Error {
+    fn from(err: writer::Error) -> Self {
+        match err {
+            writer::Error::Fmt(err) => Self::Fmt(err),
+            // The header names are valid
+            _ => Self::Fmt(fmt::Error),
+        }
+    }
+}
+
+impl From<address::Error> for Error {
+    fn from(err: address::Error) -> Self {
+        match err {
+            address::Error::Fmt(err) => Self::Fmt(err),
+            err => Self::Address(err),
+        }
+    }
+}
+
+impl From<message_id::Error> for Error {
+    fn from(err: message_id::Error) -> Self {
+        match err {
+            message_id::Error::Fmt(err) => Self::Fmt(err),
+            err => Self::MessageId(err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::EmptyFrom => f.write_str("Resent-From doesn't have any mailbox"),
+            Self::MissingSender => {
+                f.write_str("Resent-From has multiple mailboxes but Resent-Sender isn't set")
+            }
+            Self::InvalidDate { position, c } => {
+                write!(f, "invalid char {:?} in date at byte {}", c, position)
+            }
+            Self::Address(err) => write!(f, "invalid mailbox: {}", err),
+            Self::MessageId(err) => write!(f, "invalid msg-id: {}", err),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const DATE: &str = "Mon, 24 Nov 1997 14:22:01 -0800";
+
+    #[test]
+    fn full() {
+        let from = [
+            Mailbox::new(Some("Mary Smith"), "mary", "example.net"),
+            Mailbox::new(None, "john", "example.net"),
+        ];
+        let to = [Mailbox::new(None, "jdoe", "example.org")];
+        let cc = [Mailbox::new(Some("Adrián"), "adrian", "example.org")];
+
+        let mut s = "Subject: Hello".to_string();
+        let line_len = s.len();
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            Resent::new(DATE, &from)
+                .sender(Mailbox::new(None, "mary", "example.net"))
+                .to(&to)
+                .cc(&cc)
+                .bcc(&[])
+                .message_id("<78910@example.net>")
+                .write(&mut w)
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: Hello\r\n",
+                "Resent-Date: Mon, 24 Nov 1997 14:22:01 -0800\r\n",
+                "Resent-From: Mary Smith <mary@example.net>, john@example.net\r\n",
+                "Resent-Sender: mary@example.net\r\n",
+                "Resent-To: jdoe@example.org\r\n",
+                "Resent-Cc: =?utf-8?b?QWRyacOhbg==?= <adrian@example.org>\r\n",
+                "Resent-Bcc: \r\n",
+                "Resent-Message-ID: <78910@example.net>"
+            )
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let from = [Mailbox::new(None, "mary", "example.net")];
+        let two_from = [from[0], from[0]];
+        let to = [Mailbox::new(None, "jdoe", "exa mple.org")];
+
+        let mut s = String::new();
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(Resent::new(DATE, &[]).write(&mut w), Err(Error::EmptyFrom));
+            assert_eq!(
+                Resent::new(DATE, &two_from).write(&mut w),
+                Err(Error::MissingSender)
+            );
+            assert_eq!(
+                Resent::new(DATE, &from).to(&to).write(&mut w),
+                Err(Error::Address(address::Error::InvalidDomain {
+                    position: 3,
+                    c: ' '
+                }))
+            );
+            assert_eq!(
+                Resent::new(DATE, &from)
+                    .message_id("78910@example.net")
+                    .write(&mut w),
+                Err(Error::MessageId(message_id::Error::MissingAngleBrackets))
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+}
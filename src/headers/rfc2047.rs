@@ -2,15 +2,29 @@
 //!
 //! [RFC 2047]: https://datatracker.ietf.org/doc/html/rfc2047
 
-use std::fmt::{self, Write};
+#[cfg(feature = "decode")]
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
 
-use super::{utils, writer::EmailWriter, MAX_LINE_LEN};
+#[cfg(feature = "decode")]
+use super::encoded_words::{decode_words, decode_words_lossy};
+use super::{
+    utils,
+    writer::{CountingWriter, EmailWriter},
+};
+use crate::base64_engine;
+#[cfg(feature = "decode")]
+use crate::decode::{self, Error as DecodeError};
 
 const ENCODING_START_PREFIX: &str = "=?utf-8?b?";
 const ENCODING_END_SUFFIX: &str = "?=";
-
 /// Encode a string via RFC 2047.
 ///
+/// If [`EmailWriter::allows_utf8`], `s` is written as is instead,
+/// folding it at spaces, unless it contains control chars or
+/// something looking like an encoded-word.
+///
 /// # Examples
 ///
 /// ```rust
@@ -27,11 +41,62 @@ const ENCODING_END_SUFFIX: &str = "?=";
 /// # Ok(())
 /// # }
 /// ```
-pub fn encode(mut s: &str, w: &mut EmailWriter<'_>) -> fmt::Result {
+pub fn encode<W: ?Sized + Write>(s: &str, w: &mut EmailWriter<'_, W>) -> fmt::Result {
+    encode_words(s, w, |word, w| base64_engine::write_encoded(word, w))
+}
+
+/// Encode a string via RFC 2047, using `engine` for the encoded-words.
+///
+/// Behaves like [`encode`], for applications which already configured
+/// a [`base64::Engine`]. `engine` should use the standard alphabet with
+/// padding, as required by RFC 2047.
+///
+/// Requires the `base64` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "base64")] {
+/// # use email_encoding::headers::writer::EmailWriter;
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     email_encoding::headers::rfc2047::encode_with_engine(
+///         "Adrián",
+///         &base64::engine::general_purpose::STANDARD,
+///         &mut writer,
+///     )
+///     .unwrap();
+/// }
+/// assert_eq!(output, "=?utf-8?b?QWRyacOhbg==?=");
+/// # }
+/// ```
+///
+/// [`base64::Engine`]: ::base64::Engine
+#[cfg(feature = "base64")]
+pub fn encode_with_engine<E: ::base64::Engine, W: ?Sized + Write>(
+    s: &str,
+    engine: &E,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    encode_words(s, w, |word, w| {
+        write!(w, "{}", ::base64::display::Base64Display::new(word, engine))
+    })
+}
+
+fn encode_words<W: ?Sized + Write>(
+    mut s: &str,
+    w: &mut EmailWriter<'_, W>,
+    mut write_base64: impl FnMut(&[u8], &mut EmailWriter<'_, W>) -> fmt::Result,
+) -> fmt::Result {
+    if w.allows_utf8() && !s.contains(char::is_control) && !s.contains("=?") {
+        return w.folding().write_str(s);
+    }
+
     let mut wrote = false;
 
     while !s.is_empty() {
-        let remaining_line_len = MAX_LINE_LEN.saturating_sub(
+        let remaining_line_len = w.max_line_len().saturating_sub(
             ENCODING_START_PREFIX.len() + ENCODING_END_SUFFIX.len() + w.line_len() + "\r\n".len(),
         );
         let unencoded_remaining_line_len = remaining_line_len / 4 * 3;
@@ -61,11 +126,7 @@ pub fn encode(mut s: &str, w: &mut EmailWriter<'_>) -> fmt::Result {
         w.write_str(ENCODING_START_PREFIX)?;
 
         // Encode `word`
-        let encoder = base64::display::Base64Display::new(
-            word.as_bytes(),
-            &base64::engine::general_purpose::STANDARD,
-        );
-        write!(w, "{}", encoder)?;
+        write_base64(word.as_bytes(), w)?;
 
         // Write the suffix
         w.write_str(ENCODING_END_SUFFIX)?;
@@ -77,8 +138,324 @@ pub fn encode(mut s: &str, w: &mut EmailWriter<'_>) -> fmt::Result {
     Ok(())
 }
 
+/// Truncate `s` so that [`encode`] writes at most `max_len` bytes to `w`.
+///
+/// The encoded length is measured as if `s` was encoded by `w` in its
+/// current state, including the folding and the line breaks. `s` is only
+/// truncated at char boundaries, and since the returned prefix gets
+/// encoded on its own, encoded-words never get cut.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> std::fmt::Result {
+/// let input = "Adrián, your order has been shipped";
+///
+/// let mut output = String::from("Subject: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     let truncated =
+///         email_encoding::headers::rfc2047::truncate_to_encoded_len(input, 30, &writer);
+///     assert_eq!(truncated, "Adrián, you");
+///     email_encoding::headers::rfc2047::encode(truncated, &mut writer)?;
+/// }
+/// assert_eq!(output, "Subject: =?utf-8?b?QWRyacOhbiwgeW91?=");
+/// # Ok(())
+/// # }
+/// ```
+pub fn truncate_to_encoded_len<'s, W: ?Sized + Write>(
+    s: &'s str,
+    max_len: usize,
+    w: &EmailWriter<'_, W>,
+) -> &'s str {
+    let encoded_len = |prefix: &str| {
+        let mut counter = CountingWriter::new(w.line_len());
+        {
+            let mut scratch = w.scratch(&mut counter);
+            // `CountingWriter` never fails
+            let _ = encode(prefix, &mut scratch);
+        }
+        counter.len()
+    };
+
+    if encoded_len(s) <= max_len {
+        return s;
+    }
+
+    // Binary search the longest fitting prefix among the char boundaries.
+    // The encoded length never decreases as the prefix grows.
+    let boundaries = s
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain([s.len()])
+        .collect::<Vec<_>>();
+    let (mut low, mut high) = (0, boundaries.len() - 1);
+    while low < high {
+        let mid = (low + high + 1) / 2;
+        if encoded_len(&s[..boundaries[mid]]) <= max_len {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    &s[..boundaries[low]]
+}
+
+/// Decode the encoded-words found in an unstructured header value.
+///
+/// Encoded-words must be separated from the surrounding text by
+/// whitespace. The whitespace between adjacent encoded-words is removed,
+/// while the rest of the text is returned as is. Both the `B` and `Q`
+/// encodings are supported, with the `utf-8`, `us-ascii` and `iso-8859-1`
+/// charsets. Enabling the `encoding_rs` feature adds support for all
+/// the charsets known to [`encoding_rs`], such as `iso-8859-2`,
+/// `windows-1252`, `koi8-r`, `gb2312` and `shift_jis`. Charset labels
+/// are resolved via [`canonical_charset`], so aliases like `utf8` and
+/// `latin1` are accepted too.
+///
+/// `s` is borrowed if it doesn't contain any encoded-word.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidEncodedWord`] if something looking like an
+/// encoded-word isn't valid, and [`Error::UnsupportedCharset`] or
+/// [`Error::InvalidCharsetData`] if it can't be converted to UTF-8.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let decoded = email_encoding::headers::rfc2047::decode(
+///     "Hello =?utf-8?b?QWRyacOhbg==?= =?utf-8?q?_and_Mar=C3=ADa?= and Mario",
+/// )?;
+/// assert_eq!(decoded, "Hello Adrián and María and Mario");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`encoding_rs`]: https://docs.rs/encoding_rs
+/// [`canonical_charset`]: crate::decode::canonical_charset
+/// [`Error::InvalidEncodedWord`]: crate::decode::Error::InvalidEncodedWord
+/// [`Error::UnsupportedCharset`]: crate::decode::Error::UnsupportedCharset
+/// [`Error::InvalidCharsetData`]: crate::decode::Error::InvalidCharsetData
+#[cfg(feature = "decode")]
+pub fn decode(s: &str) -> Result<Cow<'_, str>, DecodeError> {
+    decode_words(s)
+}
+
+/// Decode the encoded-words found in an unstructured header value,
+/// recovering from errors.
+///
+/// Like [`decode()`], except that invalid encoded-words and those using
+/// an unsupported charset are kept as they are, while invalid charset
+/// data is replaced by U+FFFD REPLACEMENT CHARACTER. `on_error` is called
+/// with the position and the nature of every problem found in `s`.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::decode::Error;
+/// let mut errors = Vec::new();
+/// let decoded = email_encoding::headers::rfc2047::decode_lossy(
+///     "=?utf-8?q?Adri=C3=A1n?= =?x-unknown?b?88HbwQ==?= =?utf-8?q?Mar=EDa?=",
+///     |err| errors.push(err),
+/// );
+/// assert_eq!(decoded, "Adrián =?x-unknown?b?88HbwQ==?= Mar\u{FFFD}a");
+/// assert_eq!(
+///     errors,
+///     [
+///         Error::UnsupportedCharset { position: 24 },
+///         Error::InvalidCharsetData { position: 49 },
+///     ]
+/// );
+/// ```
+#[cfg(feature = "decode")]
+pub fn decode_lossy(s: &str, mut on_error: impl FnMut(DecodeError)) -> Cow<'_, str> {
+    decode_words_lossy(s, false, None, decode::Limits::UNLIMITED, &mut on_error)
+}
+
+/// Options for [`decode_with_options`].
+///
+/// New options may be added in minor releases, so the struct can only be
+/// constructed via [`DecodeOptions::default`] and the builder methods.
+#[cfg(feature = "decode")]
+#[derive(Debug, Copy, Clone, Default)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// Join the decoded bytes of adjacent encoded-words using the same
+    /// charset before converting them from it.
+    ///
+    /// RFC 2047 requires every encoded-word to contain whole chars, but
+    /// some encoders split multi-byte chars across encoded-words, which
+    /// can then only be decoded together.
+    pub join_words: bool,
+    /// Resolve charset labels unknown to [`canonical_charset`].
+    ///
+    /// [`canonical_charset`]: crate::decode::canonical_charset
+    pub charset_alias: Option<decode::CharsetAlias>,
+    /// Limit the number of encoded-words and the length of the
+    /// decoded value.
+    pub limits: decode::Limits,
+}
+
+#[cfg(feature = "decode")]
+impl DecodeOptions {
+    /// Set whether adjacent encoded-words using the same charset are
+    /// joined before being converted from it.
+    ///
+    /// Defaults to `false`.
+    pub fn join_words(mut self, join_words: bool) -> Self {
+        self.join_words = join_words;
+        self
+    }
+
+    /// Set the resolver of charset labels unknown to [`canonical_charset`].
+    ///
+    /// Defaults to none.
+    ///
+    /// [`canonical_charset`]: crate::decode::canonical_charset
+    pub fn charset_alias(mut self, charset_alias: decode::CharsetAlias) -> Self {
+        self.charset_alias = Some(charset_alias);
+        self
+    }
+
+    /// Set the limits of the decoder.
+    ///
+    /// Defaults to [`Limits::UNLIMITED`].
+    ///
+    /// [`Limits::UNLIMITED`]: crate::decode::Limits::UNLIMITED
+    pub fn limits(mut self, limits: decode::Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+/// Decode the encoded-words found in an unstructured header value,
+/// as configured by `options`.
+///
+/// See [`decode()`].
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::rfc2047::DecodeOptions;
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// // `á` is split across the encoded-words
+/// let input = "=?utf-8?q?Adri=C3?= =?utf-8?q?=A1n?=";
+/// assert!(email_encoding::headers::rfc2047::decode(input).is_err());
+///
+/// let options = DecodeOptions::default().join_words(true);
+/// let decoded = email_encoding::headers::rfc2047::decode_with_options(input, options)?;
+/// assert_eq!(decoded, "Adrián");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "decode")]
+pub fn decode_with_options(s: &str, options: DecodeOptions) -> Result<Cow<'_, str>, DecodeError> {
+    decode::strict(|on_error| {
+        decode_words_lossy(
+            s,
+            options.join_words,
+            options.charset_alias,
+            options.limits,
+            on_error,
+        )
+    })
+}
+
+/// Decode the encoded-text of a `Q` encoded-word.
+///
+/// `_` is decoded as a space and `=` followed by two hexadecimal digits
+/// as the byte they represent. Every other byte must be a printable ASCII
+/// char other than `?` and space. The charset of the decoded bytes isn't
+/// known: it's part of the encoded-word.
+///
+/// [`decode()`] already takes care of the encoded-words found in
+/// unstructured values. This is meant for encoded-texts extracted by
+/// other parsers.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidEscape`] if an `=` isn't followed by two
+/// hexadecimal digits, and [`Error::InvalidByte`] if a byte isn't
+/// allowed in encoded-text.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::decode::Error;
+/// # fn main() -> Result<(), Error> {
+/// let decoded = email_encoding::headers::rfc2047::decode_q(b"Adri=C3=A1n_=3D_A=5fB")?;
+/// assert_eq!(decoded, "Adrián = A_B".as_bytes());
+///
+/// assert_eq!(
+///     email_encoding::headers::rfc2047::decode_q(b"a b"),
+///     Err(Error::InvalidByte {
+///         position: 1,
+///         byte: b' '
+///     })
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::InvalidEscape`]: crate::decode::Error::InvalidEscape
+/// [`Error::InvalidByte`]: crate::decode::Error::InvalidByte
+#[cfg(feature = "decode")]
+pub fn decode_q(text: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decode::strict(|on_error| decode::q_lossy(text, on_error))
+}
+
+/// Decode the encoded-text of a `Q` encoded-word, recovering from errors.
+///
+/// Like [`decode_q`], except that invalid escapes and bytes which aren't
+/// allowed in encoded-text are kept as they are. `on_error` is called
+/// with the position and the nature of every problem found in `text`.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::decode::Error;
+/// let mut errors = Vec::new();
+/// let decoded = email_encoding::headers::rfc2047::decode_q_lossy(b"1+1=2 =3D_2", |err| {
+///     errors.push(err)
+/// });
+/// assert_eq!(decoded, b"1+1=2 = 2");
+/// assert_eq!(
+///     errors,
+///     [
+///         Error::InvalidEscape { position: 3 },
+///         Error::InvalidByte {
+///             position: 5,
+///             byte: b' '
+///         },
+///     ]
+/// );
+/// ```
+#[cfg(feature = "decode")]
+pub fn decode_q_lossy(text: &[u8], mut on_error: impl FnMut(DecodeError)) -> Vec<u8> {
+    decode::q_lossy(text, &mut on_error)
+}
+
 #[cfg(test)]
 mod tests {
+    use alloc::string::{String, ToString};
+    #[cfg(feature = "decode")]
+    use alloc::vec;
+
     use pretty_assertions::assert_eq;
 
     use super::*;
@@ -165,4 +542,193 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn utf8() {
+        let mut s = "Subject: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_allow_utf8(true);
+            encode(
+                "Über die Straße, über die Brücke und über den Fluss gelangen wir zum Schloss",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: Über die Straße, über die Brücke und über den Fluss gelangen\r\n",
+                " wir zum Schloss"
+            )
+        );
+    }
+
+    #[test]
+    fn utf8_encoded_word_lookalike() {
+        let mut s = "Subject: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_allow_utf8(true);
+            encode("=?utf-8?q?Hi?=", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "Subject: =?utf-8?b?PT91dGYtOD9xP0hpPz0=?=");
+    }
+
+    #[test]
+    fn truncate_folded() {
+        let input = "ééééééééééééééééééééééééééééééééééééééééééééééééééé";
+        let mut s = "Subject: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let truncated = truncate_to_encoded_len(input, 100, &w);
+            assert_eq!(truncated, "é".repeat(26));
+            encode(truncated, &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: =?utf-8?b?w6nDqcOpw6nDqcOpw6nDqcOpw6nDqcOpw6nDqcOpw6nDqcOpw6k=?=\r\n",
+                " =?utf-8?b?w6nDqcOpw6nDqcOpw6k=?="
+            )
+        );
+        assert!(s.len() - line_len <= 100);
+    }
+
+    #[test]
+    fn truncate_nothing_fits() {
+        let s = String::new();
+        let mut output = String::new();
+        let w = EmailWriter::new(&mut output, s.len(), 0, false);
+
+        assert_eq!(truncate_to_encoded_len("Adrián", 5, &w), "");
+        assert_eq!(truncate_to_encoded_len("Adrián", 1000, &w), "Adrián");
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_charset_aliases() {
+        assert_eq!(
+            decode("=?UTF8?q?=C3=A1?= =?latin1?q?=E1?= =?ANSI_X3.4-1968?q?a?="),
+            Ok("ááa".into())
+        );
+        // Joined after being resolved
+        let options = DecodeOptions {
+            join_words: true,
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options("=?utf8?q?=C3?= =?utf-8?q?=A1?=", options),
+            Ok("á".into())
+        );
+
+        let options = DecodeOptions {
+            charset_alias: Some(|label| (label == "x-mine").then_some("utf-8")),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options("=?x-mine?q?=C3=A1?= =?latin1?q?=E1?=", options),
+            Ok("áá".into())
+        );
+        assert_eq!(
+            decode("=?x-mine?q?a?="),
+            Err(DecodeError::UnsupportedCharset { position: 0 })
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_q_text() {
+        assert_eq!(decode_q(b""), Ok(vec![]));
+        assert_eq!(
+            decode_q(b"=e1=C3=A1_!*+-/"),
+            Ok(b"\xe1\xc3\xa1 !*+-/".to_vec())
+        );
+        assert_eq!(
+            decode_q(b"a=4"),
+            Err(DecodeError::InvalidEscape { position: 1 })
+        );
+        assert_eq!(
+            decode_q(b"a?="),
+            Err(DecodeError::InvalidByte {
+                position: 1,
+                byte: b'?'
+            })
+        );
+        assert_eq!(
+            decode_q("á".as_bytes()),
+            Err(DecodeError::InvalidByte {
+                position: 0,
+                byte: 0xc3
+            })
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_limits() {
+        let input = "a =?utf-8?q?b?= =?utf-8?q?c?= =?utf-8?q?d?= e";
+        let limits = |max_output_len, max_encoded_words| DecodeOptions {
+            limits: decode::Limits {
+                max_output_len,
+                max_encoded_words,
+                ..decode::Limits::default()
+            },
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options(input, limits(7, 3)),
+            Ok("a bcd e".into())
+        );
+        assert_eq!(
+            decode_with_options(input, limits(7, 2)),
+            Err(DecodeError::LimitExceeded(decode::Limit::EncodedWords))
+        );
+        assert_eq!(
+            decode_with_options(input, limits(6, 3)),
+            Err(DecodeError::LimitExceeded(decode::Limit::OutputLen))
+        );
+
+        let mut errors = Vec::new();
+        assert_eq!(
+            super::decode_words_lossy(input, false, None, limits(6, 2).limits, &mut |err| {
+                errors.push(err)
+            }),
+            "a bc ="
+        );
+        assert_eq!(
+            errors,
+            [
+                DecodeError::LimitExceeded(decode::Limit::EncodedWords),
+                DecodeError::LimitExceeded(decode::Limit::OutputLen),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn with_engine() {
+        let input = "Hello, this is a subject long enough to be folded, with some àccents in it";
+        let mut expected = "Subject: ".to_string();
+        let mut s = expected.clone();
+        {
+            let mut w = EmailWriter::new(&mut expected, "Subject: ".len(), 0, false);
+            encode(input, &mut w).unwrap();
+        }
+        {
+            let mut w = EmailWriter::new(&mut s, "Subject: ".len(), 0, false);
+            encode_with_engine(input, &::base64::engine::general_purpose::STANDARD, &mut w)
+                .unwrap();
+        }
+        assert_eq!(s, expected);
+    }
 }
@@ -2,9 +2,15 @@
 //!
 //! [RFC 2231]: https://datatracker.ietf.org/doc/html/rfc2231
 
-use std::fmt::{self, Write};
+use alloc::string::String;
+#[cfg(feature = "decode")]
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt::{self, Write};
 
-use super::{hex_encoding, utils, writer::EmailWriter, MAX_LINE_LEN};
+use super::{percent, utils, writer::EmailWriter};
+#[cfg(feature = "decode")]
+use crate::decode::{self, Error as DecodeError};
+use crate::Error as EncodeError;
 
 /// Encode a string via RFC 2231.
 ///
@@ -12,7 +18,7 @@ use super::{hex_encoding, utils, writer::EmailWriter, MAX_LINE_LEN};
 ///
 /// ```rust
 /// # use email_encoding::headers::writer::EmailWriter;
-/// # fn main() -> std::fmt::Result {
+/// # fn main() -> Result<(), email_encoding::Error> {
 /// {
 ///     let input = "invoice.pdf";
 ///
@@ -50,32 +56,324 @@ use super::{hex_encoding, utils, writer::EmailWriter, MAX_LINE_LEN};
 ///         let mut writer = EmailWriter::new(&mut output, 0, 0, false);
 ///         email_encoding::headers::rfc2231::encode("filename", input, &mut writer)?;
 ///     }
-///     assert_eq!(
-///         output,
-///         concat!(
-///             "\r\n",
-///             " filename*0*=utf-8''fakt%C3%BAra.pdf"
-///         )
-///     );
+///     assert_eq!(output, "filename*=utf-8''fakt%C3%BAra.pdf");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode<W: ?Sized + Write>(
+    key: &str,
+    value: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), EncodeError> {
+    encode_with_mode(key, value, Mode::Rfc2231, w)
+}
+
+/// The parameter syntax produced by [`encode_with_mode`] and [`encode_with_options`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// [RFC 2231] MIME parameters, folded via Parameter Value Continuations
+    ///
+    /// [RFC 2231]: https://datatracker.ietf.org/doc/html/rfc2231
+    #[default]
+    Rfc2231,
+    /// [RFC 5987] (obsoleted by [RFC 8187]) parameters, as used by
+    /// HTTP headers, which don't support continuations
+    ///
+    /// [RFC 5987]: https://datatracker.ietf.org/doc/html/rfc5987
+    /// [RFC 8187]: https://datatracker.ietf.org/doc/html/rfc8187
+    Rfc5987,
+}
+
+impl Mode {
+    fn is_safe_char(self) -> fn(char) -> bool {
+        match self {
+            Self::Rfc2231 => percent::is_rfc2231_safe,
+            Self::Rfc5987 => percent::is_attr_char,
+        }
+    }
+}
+
+/// Encode a string via RFC 2231 or RFC 5987, depending on `mode`.
+///
+/// [`Mode::Rfc2231`] behaves exactly like [`encode`].
+/// [`Mode::Rfc5987`] never splits the value into continuations
+/// and uses the wider RFC 5987 `attr-char` set for unencoded chars.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{rfc2231::Mode, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::Error> {
+/// let input = "€ rates.pdf";
+///
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     email_encoding::headers::rfc2231::encode_with_mode(
+///         "filename",
+///         input,
+///         Mode::Rfc5987,
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(output, "filename*=utf-8''%E2%82%AC%20rates.pdf");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_with_mode<W: ?Sized + Write>(
+    key: &str,
+    value: &str,
+    mode: Mode,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), EncodeError> {
+    let options = Options {
+        mode,
+        ..Options::default()
+    };
+    encode_with_options(key, value, options, w)
+}
+
+/// Options for [`encode_with_options`].
+///
+/// New options may be added in minor releases, so the struct can only be
+/// constructed via [`Options::default`] and the builder methods.
+#[derive(Debug, Copy, Clone, Default)]
+#[non_exhaustive]
+pub struct Options {
+    /// The parameter syntax to produce.
+    pub mode: Mode,
+    /// The index of the first continuation section to be written.
+    ///
+    /// Setting this to anything other than `0` allows appending
+    /// sections to a parameter which was partially written earlier:
+    /// the value is always written as continuations, and extended
+    /// sections omit the charset, which only the section `0` may carry.
+    ///
+    /// Ignored by [`Mode::Rfc5987`], which doesn't support continuations.
+    pub first_section: usize,
+    /// Chars which must not appear inside of a quoted-string.
+    ///
+    /// ASCII values containing any of them are written using the
+    /// extended syntax with an empty charset (`key*=''value%2A`)
+    /// instead of being quoted. [`Mode::Rfc5987`] requires a charset,
+    /// so `utf-8` is used in that case.
+    pub avoid_quoting: Option<fn(char) -> bool>,
+}
+
+impl Options {
+    /// Set the parameter syntax to produce.
+    ///
+    /// Defaults to [`Mode::Rfc2231`].
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the index of the first continuation section to be written.
+    ///
+    /// Defaults to `0`. See [`Options::first_section`](#structfield.first_section).
+    pub fn first_section(mut self, first_section: usize) -> Self {
+        self.first_section = first_section;
+        self
+    }
+
+    /// Set the chars which must not appear inside of a quoted-string.
+    ///
+    /// Defaults to none. See [`Options::avoid_quoting`](#structfield.avoid_quoting).
+    pub fn avoid_quoting(mut self, avoid_quoting: fn(char) -> bool) -> Self {
+        self.avoid_quoting = Some(avoid_quoting);
+        self
+    }
+
+    fn can_quote(&self, value: &str, allow_utf8: bool) -> bool {
+        let printable = if allow_utf8 && self.mode == Mode::Rfc2231 {
+            !value.contains(|c: char| c.is_control())
+        } else {
+            utils::str_is_ascii_printable(value)
+        };
+        printable && !self.must_avoid_quoting(value)
+    }
+
+    fn charset(&self, value: &str) -> &'static str {
+        if self.mode == Mode::Rfc2231 && value.is_ascii() && self.must_avoid_quoting(value) {
+            ""
+        } else {
+            "utf-8"
+        }
+    }
+
+    /// Get the length of `value` written as a single extended parameter
+    fn extended_len(&self, key: &str, value: &str) -> usize {
+        key.len()
+            + "*=".len()
+            + self.charset(value).len()
+            + "''".len()
+            + percent::encoded_len(value, self.mode.is_safe_char())
+            + "\r\n".len()
+    }
+
+    fn must_avoid_quoting(&self, value: &str) -> bool {
+        self.avoid_quoting
+            .map_or(false, |avoid_quoting| value.chars().any(avoid_quoting))
+    }
+}
+
+/// Encode a string via RFC 2231 or RFC 5987, as configured by `options`.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidKey`] if `key` is empty or isn't only composed
+/// of ascii alphanumeric chars, [`Error::KeyTooLong`] if `key` is too long to fit
+/// the max line length of `w` along with the encoding markers, and
+/// [`Error::Fmt`] if writing to `w` fails.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{rfc2231::Options, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::Error> {
+/// let mut output = "Content-Disposition: attachment;\r\n".to_owned();
+/// output.push_str(" filename*0*=utf-8''fakt%C3%BAra;");
+/// {
+///     let line_len = " filename*0*=utf-8''fakt%C3%BAra;".len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, true);
+///     let options = Options::default().first_section(1);
+///     email_encoding::headers::rfc2231::encode_with_options(
+///         "filename",
+///         "_2022.pdf",
+///         options,
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "Content-Disposition: attachment;\r\n",
+///         " filename*0*=utf-8''fakt%C3%BAra;\r\n",
+///         " filename*1=\"_2022.pdf\""
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::InvalidKey`]: crate::Error::InvalidKey
+/// [`Error::KeyTooLong`]: crate::Error::KeyTooLong
+/// [`Error::Fmt`]: crate::Error::Fmt
+pub fn encode_with_options<W: ?Sized + Write>(
+    key: &str,
+    value: &str,
+    options: Options,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), EncodeError> {
+    check_key(key, w.max_line_len())?;
+
+    let syntax = classify(key, value, options, w);
+    write_syntax(key, value, syntax, options, w)?;
+    Ok(())
+}
+
+fn check_key(key: &str, max_line_len: usize) -> Result<(), EncodeError> {
+    if key.is_empty() || !utils::str_is_ascii_alphanumeric(key) {
+        return Err(EncodeError::InvalidKey);
+    }
+    // Leave room for the longest prefix the encoder may write after the key
+    if key.len() + "*12*=utf-8'';".len() >= max_line_len {
+        return Err(EncodeError::KeyTooLong);
+    }
+
+    Ok(())
+}
+
+/// Encode the `name` parameter of `Content-Type` and the `filename`
+/// parameter of `Content-Disposition` from the same `filename`.
+///
+/// Both parameters get written with the same [`Syntax`]: if either of
+/// them needs to be percent encoded or split into continuations, so
+/// does the other one. Some email clients get confused when the two
+/// are encoded differently.
+///
+/// # Errors
+///
+/// Returns [`Error::KeyTooLong`] if `filename=` is too long to fit the
+/// max line length of either writer along with the encoding markers, and
+/// [`Error::Fmt`] if writing to a writer fails.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{rfc2231::Options, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::Error> {
+/// let mut content_type = String::from("Content-Type: application/pdf;");
+/// let mut content_disposition = String::from("Content-Disposition: attachment;");
+/// {
+///     let line_len = content_type.len();
+///     let mut content_type_writer = EmailWriter::new(&mut content_type, line_len, 1, true);
+///     let line_len = content_disposition.len();
+///     let mut content_disposition_writer =
+///         EmailWriter::new(&mut content_disposition, line_len, 1, true);
+///     email_encoding::headers::rfc2231::encode_name_and_filename(
+///         "faktúra.pdf",
+///         Options::default(),
+///         &mut content_type_writer,
+///         &mut content_disposition_writer,
+///     )?;
 /// }
+/// assert_eq!(
+///     content_type,
+///     "Content-Type: application/pdf; name*=utf-8''fakt%C3%BAra.pdf"
+/// );
+/// assert_eq!(
+///     content_disposition,
+///     "Content-Disposition: attachment; filename*=utf-8''fakt%C3%BAra.pdf"
+/// );
 /// # Ok(())
 /// # }
 /// ```
-pub fn encode(key: &str, mut value: &str, w: &mut EmailWriter<'_>) -> fmt::Result {
-    assert!(
-        utils::str_is_ascii_alphanumeric(key),
-        "`key` must only be composed of ascii alphanumeric chars"
-    );
-    assert!(
-        key.len() + "*12*=utf-8'';".len() < MAX_LINE_LEN,
-        "`key` must not be too long to cause the encoder to overflow the max line length"
-    );
-
-    if utils::str_is_ascii_printable(value) {
-        // Can be written normally (Parameter Value Continuations)
-
-        let quoted_plain_combined_len = key.len() + "=\"".len() + value.len() + "\"\r\n".len();
-        if w.line_len() + quoted_plain_combined_len <= MAX_LINE_LEN {
+///
+/// [`Error::KeyTooLong`]: crate::Error::KeyTooLong
+/// [`Error::Fmt`]: crate::Error::Fmt
+pub fn encode_name_and_filename<W1, W2>(
+    filename: &str,
+    options: Options,
+    content_type: &mut EmailWriter<'_, W1>,
+    content_disposition: &mut EmailWriter<'_, W2>,
+) -> Result<(), EncodeError>
+where
+    W1: ?Sized + Write,
+    W2: ?Sized + Write,
+{
+    // Both parameters get the same syntax, so the longest key must fit both lines
+    check_key("filename", content_type.max_line_len())?;
+    check_key("filename", content_disposition.max_line_len())?;
+
+    let name_syntax = classify("name", filename, options, content_type);
+    let filename_syntax = classify("filename", filename, options, content_disposition);
+    let syntax = match (
+        name_syntax.is_encoded() || filename_syntax.is_encoded(),
+        name_syntax.is_continued() || filename_syntax.is_continued(),
+    ) {
+        (false, false) => Syntax::Quoted,
+        (false, true) => Syntax::QuotedContinued,
+        (true, false) => Syntax::Extended,
+        (true, true) => Syntax::ExtendedContinued,
+    };
+
+    write_syntax("name", filename, syntax, options, content_type)?;
+    write_syntax("filename", filename, syntax, options, content_disposition)?;
+    Ok(())
+}
+
+fn write_syntax<W: ?Sized + Write>(
+    key: &str,
+    mut value: &str,
+    syntax: Syntax,
+    options: Options,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    match syntax {
+        Syntax::Quoted => {
             // Fits line
 
             w.write_str(key)?;
@@ -85,17 +383,26 @@ pub fn encode(key: &str, mut value: &str, w: &mut EmailWriter<'_>) -> fmt::Resul
             w.write_char('"')?;
             utils::write_escaped(value, w)?;
             w.write_char('"')?;
-        } else {
-            // Doesn't fit line
+        }
+        Syntax::QuotedContinued => {
+            // Doesn't fit line (Parameter Value Continuations)
 
+            debug!(
+                key,
+                "value doesn't fit the line, switching to continuations"
+            );
             w.new_line()?;
             w.forget_spaces();
 
-            let mut i = 0_usize;
+            let mut i = options.first_section;
             loop {
-                write!(w, " {}*{}=\"", key, i)?;
+                w.space();
+                write!(w, "{}*{}=\"", key, i)?;
 
-                let remaining_len = MAX_LINE_LEN - w.line_len() - "\"\r\n".len();
+                let remaining_len = w
+                    .max_line_len()
+                    .saturating_sub(w.line_len() + "\"\r\n".len())
+                    .max(1);
 
                 let value_ =
                     utils::truncate_to_char_boundary(value, remaining_len.min(value.len()));
@@ -117,114 +424,1390 @@ pub fn encode(key: &str, mut value: &str, w: &mut EmailWriter<'_>) -> fmt::Resul
                 i += 1;
             }
         }
-    } else {
-        // Needs encoding (Parameter Value Character Set and Language Information)
+        Syntax::Extended => {
+            // Needs encoding, fits line (Parameter Value Character Set and Language Information)
 
-        w.new_line()?;
-        w.forget_spaces();
+            write!(w, "{}*={}''", key, options.charset(value))?;
+            for c in value.chars() {
+                percent::encode_char(c, options.mode.is_safe_char(), w)?;
+            }
+        }
+        Syntax::ExtendedContinued => {
+            // Needs encoding, doesn't fit line
 
-        let mut i = 0_usize;
-        loop {
-            write!(w, " {}*{}*=", key, i)?;
+            debug!(
+                key,
+                "value doesn't fit the line, switching to continuations"
+            );
+            w.new_line()?;
+            w.forget_spaces();
 
-            if i == 0 {
-                w.write_str("utf-8''")?;
-            }
+            let mut i = options.first_section;
+            loop {
+                w.space();
+                write!(w, "{}*{}*=", key, i)?;
 
-            let mut chars = value.chars();
-            while w.line_len() < MAX_LINE_LEN - "=xx=xx=xx=xx;\r\n".len() {
-                match chars.next() {
-                    Some(c) => {
-                        hex_encoding::percent_encode_char(w, c)?;
-                        value = chars.as_str();
-                    }
-                    None => {
+                if i == 0 {
+                    write!(w, "{}''", options.charset(value))?;
+                }
+
+                let max_line_len = w.max_line_len().saturating_sub("=xx=xx=xx=xx;\r\n".len());
+                let mut chars = value.chars();
+                while let Some(c) = chars.next() {
+                    percent::encode_char(c, options.mode.is_safe_char(), w)?;
+                    value = chars.as_str();
+
+                    // Always write at least one char per line, so that we can't get stuck
+                    if w.line_len() >= max_line_len {
                         break;
                     }
                 }
-            }
 
-            if value.is_empty() {
-                // End of value
-                break;
+                if value.is_empty() {
+                    // End of value
+                    break;
+                }
+
+                // End of line
+                w.write_char(';')?;
+                w.new_line()?;
+
+                i += 1;
             }
+        }
+    }
 
-            // End of line
-            w.write_char(';')?;
-            w.new_line()?;
+    Ok(())
+}
+
+/// Encode a string via RFC 2231 or RFC 5987 into any [`Write`]r, without folding.
+///
+/// Useful outside of email headers, for example when building
+/// `multipart/form-data` bodies, where lines don't have a maximum
+/// length. The value is never split into continuations, so
+/// [`Options::first_section`] is ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::rfc2231::Options;
+/// # fn main() -> Result<(), email_encoding::Error> {
+/// let mut output = String::new();
+/// email_encoding::headers::rfc2231::encode_unfolded(
+///     "filename",
+///     &"faktúra".repeat(10),
+///     Options::default(),
+///     &mut output,
+/// )?;
+/// assert_eq!(output, format!("filename*=utf-8''{}", "fakt%C3%BAra".repeat(10)));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Write`]: std::fmt::Write
+pub fn encode_unfolded<W: ?Sized + Write>(
+    key: &str,
+    value: &str,
+    options: Options,
+    w: &mut W,
+) -> Result<(), EncodeError> {
+    let mut w = EmailWriter::new_generic(w, 0, 0, false);
+    w.set_max_line_len(usize::MAX);
+
+    let options = Options {
+        first_section: 0,
+        ..options
+    };
+    encode_with_options(key, value, options, &mut w)
+}
+
+/// The syntax a parameter gets written with.
+///
+/// Returned by [`classify`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Syntax {
+    /// A single quoted-string (`key="value"`)
+    Quoted,
+    /// Quoted-strings split into multiple sections (`key*0="val"; key*1="ue"`)
+    QuotedContinued,
+    /// A single percent encoded value (`key*=utf-8''v%C3%A0lue`)
+    Extended,
+    /// Percent encoded values split into multiple sections
+    /// (`key*0*=utf-8''v%C3%A0; key*1*=lue`)
+    ExtendedContinued,
+}
+
+impl Syntax {
+    /// Whether the value gets percent encoded.
+    ///
+    /// Some old email clients aren't able to decode such values.
+    pub fn is_encoded(self) -> bool {
+        matches!(self, Self::Extended | Self::ExtendedContinued)
+    }
+
+    /// Whether the value gets split into multiple sections.
+    pub fn is_continued(self) -> bool {
+        matches!(self, Self::QuotedContinued | Self::ExtendedContinued)
+    }
+}
+
+/// Find out which [`Syntax`] [`encode_with_options`] would use to
+/// encode `value`, if called now with `w`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{
+/// #     rfc2231::{classify, Options, Syntax},
+/// #     writer::EmailWriter,
+/// # };
+/// let mut output = String::new();
+/// let writer = EmailWriter::new(&mut output, 0, 0, false);
+///
+/// let options = Options::default();
+/// assert_eq!(
+///     classify("filename", "invoice.pdf", options, &writer),
+///     Syntax::Quoted
+/// );
+/// assert_eq!(
+///     classify("filename", "faktúra.pdf", options, &writer),
+///     Syntax::Extended
+/// );
+/// assert_eq!(
+///     classify("filename", &"faktúra".repeat(10), options, &writer),
+///     Syntax::ExtendedContinued
+/// );
+/// ```
+pub fn classify<W: ?Sized + Write>(
+    key: &str,
+    value: &str,
+    options: Options,
+    w: &EmailWriter<'_, W>,
+) -> Syntax {
+    let continuations = options.mode == Mode::Rfc2231;
 
-            i += 1;
+    if options.can_quote(value, w.allows_utf8()) {
+        if !continuations
+            || (options.first_section == 0
+                && w.line_len() + quoted_len(key, value) <= w.max_line_len())
+        {
+            Syntax::Quoted
+        } else {
+            Syntax::QuotedContinued
         }
+    } else if !continuations
+        || (options.first_section == 0
+            && w.projected_line_len() + options.extended_len(key, value) <= w.max_line_len())
+    {
+        Syntax::Extended
+    } else {
+        Syntax::ExtendedContinued
+    }
+}
+
+/// Encode a string via RFC 2231 or RFC 5987, validating the output.
+///
+/// Behaves like [`encode_with_options`], but first encodes `value` into
+/// a scratch buffer and checks the produced text via [`validate`].
+/// Nothing is written to `w` if validation fails.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{rfc2231::Options, writer::EmailWriter};
+/// # fn main() -> Result<(), email_encoding::headers::rfc2231::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+///     email_encoding::headers::rfc2231::encode_validated(
+///         "filename",
+///         "faktúra.pdf",
+///         Options::default(),
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(output, "filename*=utf-8''fakt%C3%BAra.pdf");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_validated<W: ?Sized + Write>(
+    key: &str,
+    value: &str,
+    options: Options,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), Error> {
+    let mut output = String::new();
+    {
+        let mut scratch = w.scratch(&mut output);
+        encode_with_options(key, value, options, &mut scratch)?;
     }
+    Validator {
+        s: output.as_bytes(),
+        pos: 0,
+        mode: options.mode,
+        allow_utf8: w.allows_utf8(),
+    }
+    .params()?;
 
+    encode_with_options(key, value, options, w)?;
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use pretty_assertions::assert_eq;
+/// Validate a list of encoded parameters against the RFC 2231 (or RFC 5987) grammar.
+///
+/// `params` is a sequence of `;` separated parameters, which may
+/// be surrounded and folded by whitespace, as produced by the
+/// encoders of this module.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::rfc2231::{validate, Mode};
+/// assert!(validate("filename=\"invoice.pdf\"; size=4096", Mode::Rfc2231).is_ok());
+/// assert!(validate("filename*0*=utf-8''fakt%C3%BAra;\r\n filename*1*=.pdf", Mode::Rfc2231).is_ok());
+/// assert!(validate("filename*=utf-8''fakt%C3%BAra.pdf", Mode::Rfc5987).is_ok());
+///
+/// assert!(validate("filename*=utf-8''fakt%C3%Z", Mode::Rfc2231).is_err());
+/// assert!(validate("filename=\"invoice.pdf", Mode::Rfc2231).is_err());
+/// assert!(validate("filename*0*=''fakt%C3%BAra.pdf", Mode::Rfc5987).is_err());
+/// ```
+pub fn validate(params: &str, mode: Mode) -> Result<(), Error> {
+    Validator {
+        s: params.as_bytes(),
+        pos: 0,
+        mode,
+        allow_utf8: false,
+    }
+    .params()
+}
 
-    use super::*;
+/// Error returned by [`encode_validated`] and [`validate`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The parameter couldn't be encoded
+    Encode(EncodeError),
+    /// The output doesn't conform to the grammar
+    Invalid {
+        /// Byte offset at which the error was found
+        position: usize,
+        /// What was expected at `position`
+        expected: &'static str,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(err: EncodeError) -> Self {
+        match err {
+            EncodeError::Fmt(err) => Self::Fmt(err),
+            err => Self::Encode(err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::Encode(err) => err.fmt(f),
+            Self::Invalid { position, expected } => {
+                write!(
+                    f,
+                    "invalid parameter at byte {}: expected {}",
+                    position, expected
+                )
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+struct Validator<'a> {
+    s: &'a [u8],
+    pos: usize,
+    mode: Mode,
+    allow_utf8: bool,
+}
+
+impl<'a> Validator<'a> {
+    fn params(mut self) -> Result<(), Error> {
+        self.skip_fws();
+        while self.pos < self.s.len() {
+            self.param()?;
+            self.skip_fws();
+
+            if self.pos < self.s.len() {
+                self.expect(b';', "`;`")?;
+                self.skip_fws();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn param(&mut self) -> Result<(), Error> {
+        let is_attribute_char = match self.mode {
+            Mode::Rfc2231 => utils::char_is_attribute_char,
+            Mode::Rfc5987 => percent::is_attr_char,
+        };
+        self.many1(is_attribute_char, "an attribute")?;
+
+        let mut section = None;
+        let mut extended = false;
+        if self.eat(b'*') {
+            if self.mode == Mode::Rfc2231 && self.peek().map_or(false, |b| b.is_ascii_digit()) {
+                section = Some(self.section()?);
+                extended = self.eat(b'*');
+            } else {
+                extended = true;
+            }
+        }
+        self.expect(b'=', "`=`")?;
+
+        if extended {
+            if section.map_or(true, |section| section == 0) {
+                self.charset_and_language()?;
+            }
+            self.extended_value(is_attribute_char)
+        } else if self.peek() == Some(b'"') {
+            self.quoted_string()
+        } else {
+            self.many1(utils::char_is_token, "a token or quoted-string")
+        }
+    }
+
+    fn section(&mut self) -> Result<usize, Error> {
+        let start = self.pos;
+        while self.peek().map_or(false, |b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        let digits = &self.s[start..self.pos];
+        if digits.len() > 1 && digits[0] == b'0' {
+            return Err(self.error_at(start, "a section number without leading zeros"));
+        }
+
+        digits.iter().try_fold(0_usize, |section, &digit| {
+            section
+                .checked_mul(10)
+                .and_then(|section| section.checked_add(usize::from(digit - b'0')))
+                .ok_or_else(|| self.error_at(start, "a section number"))
+        })
+    }
+
+    fn charset_and_language(&mut self) -> Result<(), Error> {
+        let charset = self.many0(utils::char_is_attribute_char);
+        if charset == 0 && self.mode == Mode::Rfc5987 {
+            return Err(self.error("a charset"));
+        }
+        self.expect(b'\'', "`'`")?;
+
+        self.many0(|c| c.is_ascii_alphanumeric() || c == '-');
+        self.expect(b'\'', "`'`")
+    }
+
+    fn extended_value(&mut self, is_attribute_char: fn(char) -> bool) -> Result<(), Error> {
+        while let Some(b) = self.peek() {
+            if b == b'%' {
+                let start = self.pos;
+                let is_hex = |b: Option<&u8>| b.map_or(false, u8::is_ascii_hexdigit);
+                if !is_hex(self.s.get(start + 1)) || !is_hex(self.s.get(start + 2)) {
+                    return Err(self.error("a percent encoded byte"));
+                }
+                self.pos += "%XX".len();
+            } else if is_attribute_char(char::from(b)) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn quoted_string(&mut self) -> Result<(), Error> {
+        self.expect(b'"', "`\"`")?;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    if !self
+                        .peek()
+                        .map_or(false, |b| matches!(b, b'\t' | b' '..=b'~'))
+                    {
+                        return Err(self.error("a quoted-pair"));
+                    }
+                    self.pos += 1;
+                }
+                Some(b'\t' | b' '..=b'~') => {
+                    self.pos += 1;
+                }
+                Some(0x80..=0xff) if self.allow_utf8 => {
+                    self.pos += 1;
+                }
+                _ => return Err(self.error("a closing `\"`")),
+            }
+        }
+    }
+
+    fn skip_fws(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t') => self.pos += 1,
+                Some(b'\r')
+                    if self.s.get(self.pos + 1) == Some(&b'\n')
+                        && matches!(self.s.get(self.pos + 2), Some(b' ' | b'\t')) =>
+                {
+                    self.pos += "\r\n".len();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn many0(&mut self, f: impl Fn(char) -> bool) -> usize {
+        let start = self.pos;
+        while self.peek().map_or(false, |b| f(char::from(b))) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn many1(&mut self, f: impl Fn(char) -> bool, expected: &'static str) -> Result<(), Error> {
+        if self.many0(f) == 0 {
+            return Err(self.error(expected));
+        }
+        Ok(())
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        let found = self.peek() == Some(b);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, b: u8, expected: &'static str) -> Result<(), Error> {
+        if !self.eat(b) {
+            return Err(self.error(expected));
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn error(&self, expected: &'static str) -> Error {
+        self.error_at(self.pos, expected)
+    }
+
+    fn error_at(&self, position: usize, expected: &'static str) -> Error {
+        Error::Invalid { position, expected }
+    }
+}
+
+/// Encode a list of parameters via RFC 2231.
+///
+/// Every `(key, value)` pair is preceded by a `;` and encoded
+/// via [`encode`]. Parameters which don't fit the current line,
+/// but would fit a new one, are moved to the next line instead
+/// of being split into continuations.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> Result<(), email_encoding::Error> {
+/// let mut output = "Content-Disposition: attachment".to_owned();
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, true);
+///     email_encoding::headers::rfc2231::encode_params(
+///         [("filename", "invoice.pdf"), ("size", "4096")],
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     "Content-Disposition: attachment; filename=\"invoice.pdf\"; size=\"4096\""
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_params<'a, I, W: ?Sized + Write>(
+    params: I,
+    w: &mut EmailWriter<'_, W>,
+) -> Result<(), EncodeError>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    for (key, value) in params {
+        check_key(key, w.max_line_len())?;
+
+        w.write_char(';')?;
+        w.space();
+
+        let options = Options::default();
+        let len = if options.can_quote(value, w.allows_utf8()) {
+            quoted_len(key, value)
+        } else {
+            options.extended_len(key, value)
+        };
+        if w.projected_line_len() + len > w.max_line_len() && " ".len() + len <= w.max_line_len() {
+            // Fits a new line, but not the current one
+            w.new_line()?;
+        }
+
+        encode(key, value, w)?;
+    }
+
+    Ok(())
+}
+
+fn quoted_len(key: &str, value: &str) -> usize {
+    key.len() + "=\"".len() + value.len() + "\"\r\n".len()
+}
+
+/// Decode a list of RFC 2231 parameters.
+///
+/// `params` is a list of `(key, value)` pairs, as found in the header,
+/// with quoted-string values already unquoted. Sections of continued
+/// parameters (`key*0`, `key*1`, ...) are joined back together, in any
+/// order, and percent encoded values (`key*=utf-8''v%C3%A0lue`) are
+/// decoded. Extended and regular sections may be mixed.
+/// Parameters are returned in the order of their first appearance,
+/// with the trailing `*` and section numbers removed from their keys.
+/// Keys are borrowed from `params`, and so are values which don't need to
+/// be decoded.
+///
+/// A parameter may also be given both with and without RFC 2231
+/// encoding (`filename="invoice.pdf"; filename*=utf-8''...`), for the
+/// sake of clients which don't support it: the RFC 2231 value is returned.
+/// Parameters, or sections of them, appearing more than once are rejected.
+/// See [`decode_with_options`] for keeping the last one instead.
+///
+/// Percent encoded values may use the `utf-8`, `us-ascii` and
+/// `iso-8859-1` charsets. Enabling the `encoding_rs` feature adds
+/// support for all the charsets known to [`encoding_rs`], such as
+/// `iso-8859-2`, `windows-1252`, `koi8-r`, `gb2312` and `shift_jis`.
+///
+/// Requires the `decode` feature.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidParameter`] if a key is malformed, if a
+/// parameter or section is duplicated or if the sections of a parameter
+/// are missing, and
+/// [`Error::UnsupportedCharset`] or [`Error::InvalidCharsetData`] if a value
+/// can't be converted to UTF-8. The position of the latter errors is
+/// the index of the parameter which declares the charset.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let params = email_encoding::headers::rfc2231::decode([
+///     ("name", "invoice.pdf"),
+///     ("filename*0*", "utf-8''fakt%C3%BA"),
+///     ("filename*1", "ra.pdf"),
+/// ])?;
+/// assert_eq!(
+///     params,
+///     [
+///         ("name", "invoice.pdf".into()),
+///         ("filename", "faktúra.pdf".into()),
+///     ]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::InvalidParameter`]: crate::decode::Error::InvalidParameter
+/// [`Error::UnsupportedCharset`]: crate::decode::Error::UnsupportedCharset
+/// [`Error::InvalidCharsetData`]: crate::decode::Error::InvalidCharsetData
+/// [`encoding_rs`]: https://docs.rs/encoding_rs
+#[cfg(feature = "decode")]
+pub fn decode<'a, I>(params: I) -> Result<Vec<(&'a str, Cow<'a, str>)>, DecodeError>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    decode::strict(|on_error| decode_params(params, DecodeOptions::default(), on_error))
+}
+
+/// How [`decode_with_options`] handles a parameter, or a section of it,
+/// appearing more than once.
+#[cfg(feature = "decode")]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Duplicates {
+    /// Return [`Error::InvalidParameter`]
+    ///
+    /// [`decode_lossy`] reports the error and keeps the first one.
+    ///
+    /// [`Error::InvalidParameter`]: crate::decode::Error::InvalidParameter
+    #[default]
+    Reject,
+    /// Keep the last one, like many email clients do
+    LastWins,
+}
+
+/// Options for [`decode_with_options`].
+///
+/// New options may be added in minor releases, so the struct can only be
+/// constructed via [`DecodeOptions::default`] and the builder methods.
+#[cfg(feature = "decode")]
+#[derive(Debug, Copy, Clone, Default)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// How to handle duplicate parameters and sections.
+    pub duplicates: Duplicates,
+    /// Resolve charset labels unknown to [`canonical_charset`].
+    ///
+    /// [`canonical_charset`]: crate::decode::canonical_charset
+    pub charset_alias: Option<decode::CharsetAlias>,
+    /// Limit the number of sections of each parameter and the total
+    /// length of the decoded values.
+    pub limits: decode::Limits,
+}
+
+#[cfg(feature = "decode")]
+impl DecodeOptions {
+    /// Set how duplicate parameters and sections are handled.
+    ///
+    /// Defaults to [`Duplicates::Reject`].
+    pub fn duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Set the resolver of charset labels unknown to [`canonical_charset`].
+    ///
+    /// Defaults to none.
+    ///
+    /// [`canonical_charset`]: crate::decode::canonical_charset
+    pub fn charset_alias(mut self, charset_alias: decode::CharsetAlias) -> Self {
+        self.charset_alias = Some(charset_alias);
+        self
+    }
+
+    /// Set the limits of the decoder.
+    ///
+    /// Defaults to [`Limits::UNLIMITED`].
+    ///
+    /// [`Limits::UNLIMITED`]: crate::decode::Limits::UNLIMITED
+    pub fn limits(mut self, limits: decode::Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+/// Decode a list of RFC 2231 parameters, as configured by `options`.
+///
+/// See [`decode()`].
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::rfc2231::{DecodeOptions, Duplicates};
+/// # fn main() -> Result<(), email_encoding::decode::Error> {
+/// let options = DecodeOptions::default().duplicates(Duplicates::LastWins);
+/// let params = email_encoding::headers::rfc2231::decode_with_options(
+///     [
+///         ("filename*1", "ra.pdf"),
+///         ("filename*0*", "utf-8''fakt%C3%BA"),
+///         ("charset", "us-ascii"),
+///         ("charset", "utf-8"),
+///     ],
+///     options,
+/// )?;
+/// assert_eq!(
+///     params,
+///     [
+///         ("filename", "faktúra.pdf".into()),
+///         ("charset", "utf-8".into()),
+///     ]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "decode")]
+pub fn decode_with_options<'a, I>(
+    params: I,
+    options: DecodeOptions,
+) -> Result<Vec<(&'a str, Cow<'a, str>)>, DecodeError>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    decode::strict(|on_error| decode_params(params, options, on_error))
+}
+
+/// Decode a list of MIME parameters, recovering from errors.
+///
+/// Like [`decode()`], except that malformed parameters and sections are
+/// skipped, and that values which can't be converted from their charset are
+/// decoded as UTF-8, with invalid sequences replaced by U+FFFD REPLACEMENT
+/// CHARACTER. `on_error` is called with the nature of every problem found
+/// in `params`.
+///
+/// Requires the `decode` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::decode::Error;
+/// let mut errors = Vec::new();
+/// let params = email_encoding::headers::rfc2231::decode_lossy(
+///     [
+///         ("filename*0*", "utf-8''fakt%C3%BA"),
+///         ("filename*2", ".pdf"),
+///         ("name*", "iso-8859-1''%E1"),
+///     ],
+///     |err| errors.push(err),
+/// );
+/// assert_eq!(
+///     params,
+///     [
+///         ("filename", "faktú".into()),
+///         ("name", "á".into()),
+///     ]
+/// );
+/// assert_eq!(errors, [Error::InvalidParameter { index: 1 }]);
+/// ```
+#[cfg(feature = "decode")]
+pub fn decode_lossy<'a, I>(
+    params: I,
+    mut on_error: impl FnMut(DecodeError),
+) -> Vec<(&'a str, Cow<'a, str>)>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    decode_params(params, DecodeOptions::default(), &mut on_error)
+}
+
+/// A parameter being put back together
+#[cfg(feature = "decode")]
+struct PartialParam<'a> {
+    name: &'a str,
+    /// Index and value of the parameter without RFC 2231 syntax
+    regular: Option<(usize, &'a str)>,
+    /// RFC 2231 sections of the parameter
+    sections: Vec<Section<'a>>,
+}
+
+/// A section of an RFC 2231 parameter
+#[cfg(feature = "decode")]
+struct Section<'a> {
+    /// Section number, `0` for non-continued parameters
+    number: u32,
+    index: usize,
+    extended: bool,
+    value: &'a str,
+}
+
+#[cfg(feature = "decode")]
+fn decode_params<'a, I>(
+    params: I,
+    options: DecodeOptions,
+    on_error: &mut dyn FnMut(DecodeError),
+) -> Vec<(&'a str, Cow<'a, str>)>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut partial_params: Vec<PartialParam<'_>> = Vec::new();
+
+    for (index, (key, value)) in params.into_iter().enumerate() {
+        let invalid = DecodeError::InvalidParameter { index };
+
+        let (name, number, extended) = match parse_key(key) {
+            Some(key) => key,
+            None => {
+                on_error(invalid);
+                continue;
+            }
+        };
+
+        let param = match partial_params
+            .iter_mut()
+            .position(|param| param.name.eq_ignore_ascii_case(name))
+        {
+            Some(i) => &mut partial_params[i],
+            None => {
+                partial_params.push(PartialParam {
+                    name,
+                    regular: None,
+                    sections: Vec::new(),
+                });
+                partial_params.last_mut().expect("a param was just pushed")
+            }
+        };
+
+        if number.is_none() && !extended {
+            if param.regular.is_none() || options.duplicates == Duplicates::LastWins {
+                param.regular = Some((index, value));
+            } else {
+                on_error(invalid);
+            }
+            continue;
+        }
+
+        let section = Section {
+            number: number.unwrap_or(0),
+            index,
+            extended,
+            value,
+        };
+        let sections = param.sections.len();
+        match param
+            .sections
+            .iter_mut()
+            .find(|existing| existing.number == section.number)
+        {
+            Some(existing) if options.duplicates == Duplicates::LastWins => *existing = section,
+            Some(_) => on_error(invalid),
+            None if sections >= options.limits.max_sections => {
+                on_error(DecodeError::LimitExceeded(decode::Limit::Sections));
+            }
+            None => param.sections.push(section),
+        }
+    }
+
+    let mut decoded = Vec::with_capacity(partial_params.len());
+    let mut output_len = 0_usize;
+    for param in partial_params {
+        let value = match join_sections(param.sections, options.charset_alias, on_error)
+            .or_else(|| param.regular.map(|(_, value)| Cow::Borrowed(value)))
+        {
+            Some(value) => value,
+            None => continue,
+        };
+
+        output_len = output_len.saturating_add(value.len());
+        if output_len > options.limits.max_output_len {
+            on_error(DecodeError::LimitExceeded(decode::Limit::OutputLen));
+            break;
+        }
+        decoded.push((param.name, value));
+    }
+    decoded
+}
+
+/// Split `key` into its name, section number and whether it's extended
+#[cfg(feature = "decode")]
+fn parse_key(key: &str) -> Option<(&str, Option<u32>, bool)> {
+    let (key, extended) = match key.strip_suffix('*') {
+        Some(key) => (key, true),
+        None => (key, false),
+    };
+    let (name, number) = match key.split_once('*') {
+        Some((name, number)) => {
+            if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            (name, Some(number.parse::<u32>().ok()?))
+        }
+        None => (key, None),
+    };
+    if name.is_empty() || !name.chars().all(utils::char_is_attribute_char) {
+        return None;
+    }
+
+    Some((name, number, extended))
+}
+
+/// Decode and join `sections`, up to the first missing or invalid one
+///
+/// Returns `None` if there's no usable section, and borrows the value
+/// of a single section which doesn't need to be decoded.
+#[cfg(feature = "decode")]
+fn join_sections<'a>(
+    mut sections: Vec<Section<'a>>,
+    charset_alias: Option<decode::CharsetAlias>,
+    on_error: &mut dyn FnMut(DecodeError),
+) -> Option<Cow<'a, str>> {
+    sections.sort_by_key(|section| section.number);
+
+    let mut charset = None;
+    let mut unchanged = None;
+    let mut bytes = Vec::new();
+    let mut joined = 0;
+    for (expected, section) in (0..).zip(&sections) {
+        let invalid = DecodeError::InvalidParameter {
+            index: section.index,
+        };
+        if section.number != expected {
+            on_error(invalid);
+            break;
+        }
+
+        let mut value = section.value;
+        if section.extended && section.number == 0 {
+            // `charset'language'value`
+            let mut parts = value.splitn(3, '\'');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(section_charset), Some(_language), Some(rest)) => {
+                    charset = Some(section_charset).filter(|charset| !charset.is_empty());
+                    value = rest;
+                }
+                _ => {
+                    on_error(invalid);
+                    break;
+                }
+            }
+        }
+
+        if joined == 0 && !(section.extended && value.contains('%')) {
+            unchanged = Some(value);
+        }
+        if section.extended {
+            match percent::decode(value) {
+                Ok(decoded) => bytes.extend_from_slice(&decoded),
+                Err(_) => {
+                    on_error(invalid);
+                    break;
+                }
+            }
+        } else {
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        joined += 1;
+    }
+    if joined == 0 {
+        return None;
+    }
+
+    let charset = decode::resolve_charset(charset.unwrap_or("utf-8"), charset_alias);
+    if let (1, Some(value)) = (joined, unchanged) {
+        let ascii_compatible = ["us-ascii", "iso-8859-1"]
+            .iter()
+            .any(|ascii_compatible| charset.eq_ignore_ascii_case(ascii_compatible));
+        if charset.eq_ignore_ascii_case("utf-8") || (ascii_compatible && value.is_ascii()) {
+            return Some(Cow::Borrowed(value));
+        }
+    }
+
+    let first_index = sections[0].index;
+    let text = decode::charset_to_string(charset, bytes, &mut |err| {
+        on_error(err.offset(first_index));
+    })
+    .unwrap_or_else(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+    Some(Cow::Owned(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    #[cfg(feature = "decode")]
+    use alloc::vec;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::headers::{writer::FoldingWhitespace, HARD_LINE_LIMIT, MAX_LINE_LEN};
+
+    #[test]
+    #[allow(unknown_lints, clippy::useless_concat)]
+    fn empty() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = 1;
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode("filename", "", &mut w).unwrap();
+        }
+
+        assert_eq!(s, concat!("Content-Disposition: attachment; filename=\"\""));
+    }
+
+    #[test]
+    #[allow(unknown_lints, clippy::useless_concat)]
+    fn parameter() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = 1;
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode("filename", "duck.txt", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!("Content-Disposition: attachment; filename=\"duck.txt\"")
+        );
+    }
+
+    #[test]
+    #[allow(unknown_lints, clippy::useless_concat)]
+    fn parameter_to_escape() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = 1;
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode("filename", "du\"ck\\.txt", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!("Content-Disposition: attachment; filename=\"du\\\"ck\\\\.txt\"")
+        );
+    }
+
+    #[test]
+    fn parameter_long() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode(
+                "filename",
+                "a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it-will-the-client-be-able-to-handle-it.txt",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Disposition: attachment;\r\n",
+                " filename*0=\"a-fairly-long-filename-just-to-see-what-happens-when-we-enco\";\r\n",
+                " filename*1=\"de-it-will-the-client-be-able-to-handle-it.txt\""
+            )
+        );
+    }
+
+    #[test]
+    fn parameter_long_custom_max_line_len() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.set_max_line_len(HARD_LINE_LIMIT);
+            w.space();
+            encode(
+                "filename",
+                "a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it-will-the-client-be-able-to-handle-it.txt",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "Content-Disposition: attachment; filename=\"a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it-will-the-client-be-able-to-handle-it.txt\""
+        );
+    }
+
+    #[test]
+    fn parameter_special_short_max_line_len() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.set_max_line_len(40);
+            w.space();
+            encode("filename", "caffè-caffè-caffè.txt", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Disposition: attachment;\r\n",
+                " filename*0*=utf-8''caff%C3%A8;\r\n",
+                " filename*1*=-caff%C3%A8-;\r\n",
+                " filename*2*=caff%C3%A8.t;\r\n",
+                " filename*3*=xt"
+            )
+        );
+    }
+
+    #[test]
+    fn parameter_special() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode("filename", "caffè.txt", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "Content-Disposition: attachment; filename*=utf-8''caff%C3%A8.txt"
+        );
+    }
+
+    #[test]
+    fn parameter_special_first_section() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            let options = Options {
+                first_section: 2,
+                ..Options::default()
+            };
+            encode_with_options("filename", "caffè.txt", options, &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Disposition: attachment;\r\n",
+                " filename*2*=caff%C3%A8.txt"
+            )
+        );
+    }
+
+    #[test]
+    fn parameter_special_long() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode(
+                "filename",
+                "testing-to-see-what-happens-when-📕📕📕📕📕📕📕📕📕📕📕-are-placed-on-the-boundary.txt",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Disposition: attachment;\r\n",
+                " filename*0*=utf-8''testing-to-see-what-happens-when-%F0%9F%93%95;\r\n",
+                " filename*1*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
+                " filename*2*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
+                " filename*3*=%F0%9F%93%95%F0%9F%93%95-are-placed-on-the-bound;\r\n",
+                " filename*4*=ary.txt"
+            )
+        );
+    }
+
+    #[test]
+    fn parameter_special_long_part2() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            encode(
+                "filename",
+                "testing-to-see-what-happens-when-books-are-placed-in-the-second-part-📕📕📕📕📕📕📕📕📕📕📕.txt",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Disposition: attachment;\r\n",
+                " filename*0*=utf-8''testing-to-see-what-happens-when-books-ar;\r\n",
+                " filename*1*=e-placed-in-the-second-part-%F0%9F%93%95%F0%9F%93%95;\r\n",
+                " filename*2*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
+                " filename*3*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
+                " filename*4*=%F0%9F%93%95.txt"
+            )
+        );
+    }
+
+    #[test]
+    fn parameter_dont_split_on_hex_boundary() {
+        let base_header = "Content-Disposition: attachment;".to_string();
+        let line_len = base_header.len();
+
+        for start_offset in &["", "x", "xx", "xxx"] {
+            let mut filename = start_offset.to_string();
+
+            for i in 1..256 {
+                // 'Ü' results in two hex chars %C3%9C
+                filename.push('Ü');
+
+                let mut output = base_header.clone();
+                {
+                    let mut w = EmailWriter::new(&mut output, line_len, 0, true);
+                    encode("filename", &filename, &mut w).unwrap();
+                }
+
+                // look for all hex encoded chars
+                let output_len = output.len();
+                let mut found_hex_count = 0;
+                for (percent_sign_idx, _) in output.match_indices('%') {
+                    assert!(percent_sign_idx + 3 <= output_len);
+
+                    // verify we get the expected hex sequence for an 'Ü'
+                    let must_be_hex = &output[percent_sign_idx + 1..percent_sign_idx + 3];
+                    assert!(
+                        must_be_hex == "C3" || must_be_hex == "9C",
+                        "unexpected hex char: {}",
+                        must_be_hex
+                    );
+                    found_hex_count += 1;
+                }
+                // verify the number of hex encoded chars adds up
+                let number_of_chars_in_hex = 2;
+                assert_eq!(found_hex_count, i * number_of_chars_in_hex);
+
+                // verify max line length
+                let mut last_newline_pos = 0;
+                for (newline_idx, _) in output.match_indices("\r\n") {
+                    let line_length = newline_idx - last_newline_pos;
+                    assert!(
+                        line_length < MAX_LINE_LEN,
+                        "expected line length exceeded: {} > {}",
+                        line_length,
+                        MAX_LINE_LEN
+                    );
+                    last_newline_pos = newline_idx;
+                }
+                // ensure there was at least one newline, unless the value fits the first line
+                if last_newline_pos == 0 {
+                    assert!(
+                        output.len() <= MAX_LINE_LEN,
+                        "expected line length exceeded: {} > {}",
+                        output.len(),
+                        MAX_LINE_LEN
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn params() {
+        let mut s = "Content-Type: text/plain".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            encode_params([("charset", "utf-8"), ("name", "duck.txt")], &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "Content-Type: text/plain; charset=\"utf-8\"; name=\"duck.txt\""
+        );
+    }
+
+    #[test]
+    fn params_fold_between() {
+        let mut s = "Content-Disposition: attachment".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            encode_params(
+                [
+                    ("filename", "a-fairly-long-filename-to-fill-the-line.txt"),
+                    ("size", "1234567890"),
+                    ("name", "caffè.txt"),
+                ],
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Content-Disposition: attachment;\r\n",
+                " filename=\"a-fairly-long-filename-to-fill-the-line.txt\"; size=\"1234567890\";\r\n",
+                " name*=utf-8''caff%C3%A8.txt"
+            )
+        );
+    }
 
     #[test]
-    fn empty() {
-        let mut s = "Content-Disposition: attachment;".to_string();
-        let line_len = 1;
+    fn params_empty() {
+        let mut s = "Content-Type: text/plain".to_string();
+        let line_len = s.len();
 
         {
             let mut w = EmailWriter::new(&mut s, line_len, 0, true);
-            w.space();
-            encode("filename", "", &mut w).unwrap();
+            encode_params([], &mut w).unwrap();
         }
 
-        assert_eq!(s, concat!("Content-Disposition: attachment; filename=\"\""));
+        assert_eq!(s, "Content-Type: text/plain");
     }
 
     #[test]
-    fn parameter() {
+    fn rfc5987_parameter() {
         let mut s = "Content-Disposition: attachment;".to_string();
-        let line_len = 1;
+        let line_len = s.len();
 
         {
             let mut w = EmailWriter::new(&mut s, line_len, 0, true);
             w.space();
-            encode("filename", "duck.txt", &mut w).unwrap();
+            encode_with_mode("filename", "du\"ck.txt", Mode::Rfc5987, &mut w).unwrap();
         }
 
         assert_eq!(
             s,
-            concat!("Content-Disposition: attachment; filename=\"duck.txt\"")
+            "Content-Disposition: attachment; filename=\"du\\\"ck.txt\""
         );
     }
 
     #[test]
-    fn parameter_to_escape() {
+    fn rfc5987_parameter_special() {
         let mut s = "Content-Disposition: attachment;".to_string();
-        let line_len = 1;
+        let line_len = s.len();
 
         {
             let mut w = EmailWriter::new(&mut s, line_len, 0, true);
             w.space();
-            encode("filename", "du\"ck\\.txt", &mut w).unwrap();
+            encode_with_mode("filename", "caffè!#$&+^`|~.txt", Mode::Rfc5987, &mut w).unwrap();
         }
 
         assert_eq!(
             s,
-            concat!("Content-Disposition: attachment; filename=\"du\\\"ck\\\\.txt\"")
+            "Content-Disposition: attachment; filename*=utf-8''caff%C3%A8!#$&+^`|~.txt"
         );
     }
 
     #[test]
-    fn parameter_long() {
+    fn rfc5987_parameter_special_long() {
         let mut s = "Content-Disposition: attachment;".to_string();
         let line_len = s.len();
 
         {
             let mut w = EmailWriter::new(&mut s, line_len, 0, true);
             w.space();
-            encode(
+            encode_with_mode(
                 "filename",
-                "a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it-will-the-client-be-able-to-handle-it.txt",
+                "testing-to-see-what-happens-when-📕📕📕📕📕📕📕📕-are-placed-on-the-boundary.txt",
+                Mode::Rfc5987,
                 &mut w,
             )
             .unwrap();
@@ -233,44 +1816,242 @@ mod tests {
         assert_eq!(
             s,
             concat!(
-                "Content-Disposition: attachment;\r\n",
-                " filename*0=\"a-fairly-long-filename-just-to-see-what-happens-when-we-enco\";\r\n",
-                " filename*1=\"de-it-will-the-client-be-able-to-handle-it.txt\""
+                "Content-Disposition: attachment; filename*=utf-8''testing-to-see-what-happens-when-",
+                "%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95",
+                "-are-placed-on-the-boundary.txt"
             )
         );
     }
 
     #[test]
-    fn parameter_special() {
+    fn parameter_avoid_quoting() {
         let mut s = "Content-Disposition: attachment;".to_string();
         let line_len = s.len();
 
         {
             let mut w = EmailWriter::new(&mut s, line_len, 0, true);
             w.space();
-            encode("filename", "caffè.txt", &mut w).unwrap();
+            let options = Options {
+                avoid_quoting: Some(|c| matches!(c, '"' | '\\')),
+                ..Options::default()
+            };
+            encode_with_options("filename", "du\"ck*.txt", options, &mut w).unwrap();
         }
 
         assert_eq!(
             s,
-            concat!(
-                "Content-Disposition: attachment;\r\n",
-                " filename*0*=utf-8''caff%C3%A8.txt"
-            )
+            "Content-Disposition: attachment; filename*=''du%22ck%2A.txt"
         );
     }
 
     #[test]
-    fn parameter_special_long() {
+    fn parameter_avoid_quoting_unaffected() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            let options = Options {
+                avoid_quoting: Some(|c| matches!(c, '"' | '\\')),
+                ..Options::default()
+            };
+            encode_with_options("filename", "duck*.txt", options, &mut w).unwrap();
+        }
+
+        assert_eq!(s, "Content-Disposition: attachment; filename=\"duck*.txt\"");
+    }
+
+    #[test]
+    fn rfc5987_parameter_avoid_quoting() {
         let mut s = "Content-Disposition: attachment;".to_string();
         let line_len = s.len();
 
         {
             let mut w = EmailWriter::new(&mut s, line_len, 0, true);
             w.space();
+            let options = Options {
+                mode: Mode::Rfc5987,
+                avoid_quoting: Some(|c| c == '"'),
+                ..Options::default()
+            };
+            encode_with_options("filename", "du\"ck.txt", options, &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "Content-Disposition: attachment; filename*=utf-8''du%22ck.txt"
+        );
+    }
+
+    #[test]
+    fn unfolded() {
+        let mut s = "Content-Disposition: form-data; name=\"file\"; ".to_string();
+
+        encode_unfolded(
+            "filename",
+            "a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it-will-the-client-be-able-to-handle-it.txt",
+            Options::default(),
+            &mut s,
+        )
+        .unwrap();
+
+        assert_eq!(
+            s,
+            "Content-Disposition: form-data; name=\"file\"; filename=\"a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it-will-the-client-be-able-to-handle-it.txt\""
+        );
+    }
+
+    #[test]
+    fn classify_syntax() {
+        let mut s = "Content-Disposition: attachment;".to_string();
+        let line_len = s.len();
+        let w = EmailWriter::new(&mut s, line_len, 1, true);
+
+        let long = "a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it.txt";
+        let cases = [
+            ("duck.txt", Mode::Rfc2231, Syntax::Quoted),
+            (long, Mode::Rfc2231, Syntax::QuotedContinued),
+            ("caffè.txt", Mode::Rfc2231, Syntax::Extended),
+            (
+                "caffè-caffè-caffè.txt",
+                Mode::Rfc2231,
+                Syntax::ExtendedContinued,
+            ),
+            (long, Mode::Rfc5987, Syntax::Quoted),
+            ("caffè-caffè-caffè.txt", Mode::Rfc5987, Syntax::Extended),
+        ];
+        for (value, mode, syntax) in cases {
+            let options = Options {
+                mode,
+                ..Options::default()
+            };
+            assert_eq!(
+                classify("filename", value, options, &w),
+                syntax,
+                "{}",
+                value
+            );
+        }
+
+        let options = Options {
+            first_section: 1,
+            ..Options::default()
+        };
+        assert_eq!(
+            classify("filename", "duck.txt", options, &w),
+            Syntax::QuotedContinued
+        );
+    }
+
+    #[test]
+    fn validated() {
+        let values = [
+            "",
+            "duck.txt",
+            "du\"ck\\.txt",
+            "caffè.txt",
+            "a-fairly-long-filename-just-to-see-what-happens-when-we-encode-it-will-the-client-be-able-to-handle-it.txt",
+            "testing-to-see-what-happens-when-📕📕📕📕📕📕📕📕📕📕📕-are-placed-on-the-boundary.txt",
+        ];
+
+        for mode in [Mode::Rfc2231, Mode::Rfc5987] {
+            for value in values {
+                let mut s = "Content-Disposition: attachment;".to_string();
+                let line_len = s.len();
+
+                {
+                    let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+                    w.space();
+                    let options = Options {
+                        mode,
+                        ..Options::default()
+                    };
+                    encode_validated("filename", value, options, &mut w).unwrap();
+                }
+
+                validate(&s["Content-Disposition: attachment;".len()..], mode).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_key() {
+        let mut s = "Content-Disposition: attachment".to_string();
+        let line_len = s.len();
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            assert_eq!(
+                encode("file name", "duck.txt", &mut w),
+                Err(EncodeError::InvalidKey)
+            );
+            assert_eq!(
+                encode_params([("a", "b"), ("c;d", "e")], &mut w),
+                Err(EncodeError::InvalidKey)
+            );
+            assert_eq!(
+                encode(&"a".repeat(MAX_LINE_LEN), "duck.txt", &mut w),
+                Err(EncodeError::KeyTooLong)
+            );
+            assert_eq!(
+                encode_validated("", "duck.txt", Options::default(), &mut w),
+                Err(Error::Encode(EncodeError::InvalidKey))
+            );
+        }
+        assert_eq!(s, "Content-Disposition: attachment; a=\"b\"");
+
+        let mut s = String::new();
+        assert_eq!(
+            encode_unfolded("filé", "duck.txt", Options::default(), &mut s),
+            Err(EncodeError::InvalidKey)
+        );
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn validate_invalid() {
+        let cases = [
+            ("=\"duck.txt\"", Mode::Rfc2231, 0),
+            ("filename=\"duck.txt\" size=1", Mode::Rfc2231, 20),
+            ("filename*01=\"duck.txt\"", Mode::Rfc2231, 9),
+            ("filename*0*=utf-8'caff%C3%A8.txt", Mode::Rfc2231, 22),
+            ("filename*0*=utf-8''caff%C3%A.txt", Mode::Rfc2231, 26),
+            ("filename=\"du\"ck.txt\"", Mode::Rfc2231, 13),
+            ("filename=\"duck\r\n.txt\"", Mode::Rfc2231, 14),
+            ("filename=duck/txt", Mode::Rfc2231, 13),
+            ("filename*0=\"duck.txt\"", Mode::Rfc5987, 9),
+            ("filename*=''caff%C3%A8.txt", Mode::Rfc5987, 10),
+        ];
+
+        for (params, mode, position) in cases {
+            assert!(
+                matches!(validate(params, mode), Err(Error::Invalid { position: p, .. }) if p == position),
+                "{:?} {:?}",
+                params,
+                validate(params, mode)
+            );
+        }
+    }
+
+    #[test]
+    fn non_ascii_key() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, true);
+        assert_eq!(encode("📬", "", &mut w), Err(EncodeError::InvalidKey));
+    }
+
+    #[test]
+    fn continuations_folding_tab() {
+        let mut s = String::new();
+        s.push_str("Content-Disposition: attachment;");
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 1, false);
+            w.set_folding_whitespace(FoldingWhitespace::Tab);
             encode(
                 "filename",
-                "testing-to-see-what-happens-when-📕📕📕📕📕📕📕📕📕📕📕-are-placed-on-the-boundary.txt",
+                "a very long file name which needs to be split on multiple lines.txt",
                 &mut w,
             )
             .unwrap();
@@ -280,104 +2061,301 @@ mod tests {
             s,
             concat!(
                 "Content-Disposition: attachment;\r\n",
-                " filename*0*=utf-8''testing-to-see-what-happens-when-%F0%9F%93%95;\r\n",
-                " filename*1*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
-                " filename*2*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
-                " filename*3*=%F0%9F%93%95%F0%9F%93%95-are-placed-on-the-bound;\r\n",
-                " filename*4*=ary.txt"
+                "\tfilename*0=\"a very long file name which needs to be split on multiple li\";\r\n",
+                "\tfilename*1=\"nes.txt\""
             )
         );
     }
 
     #[test]
-    fn parameter_special_long_part2() {
+    fn utf8() {
         let mut s = "Content-Disposition: attachment;".to_string();
         let line_len = s.len();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
-            w.space();
-            encode(
-                "filename",
-                "testing-to-see-what-happens-when-books-are-placed-in-the-second-part-📕📕📕📕📕📕📕📕📕📕📕.txt",
-                &mut w,
+            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
+            w.set_allow_utf8(true);
+            encode_validated("filename", "faktúra.pdf", Options::default(), &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "Content-Disposition: attachment; filename=\"faktúra.pdf\""
+        );
+    }
+
+    #[test]
+    fn name_and_filename_continued() {
+        let filename = "quarterly_report_2022_final_version.pdf";
+        let mut content_type =
+            "Content-Type: application/vnd.openxmlformats-officedocument;".to_string();
+        let mut content_disposition = "Content-Disposition: attachment;".to_string();
+
+        {
+            let line_len = content_type.len();
+            let mut content_type_w = EmailWriter::new(&mut content_type, line_len, 1, true);
+            let line_len = content_disposition.len();
+            let mut content_disposition_w =
+                EmailWriter::new(&mut content_disposition, line_len, 1, true);
+            encode_name_and_filename(
+                filename,
+                Options::default(),
+                &mut content_type_w,
+                &mut content_disposition_w,
             )
             .unwrap();
         }
 
         assert_eq!(
-            s,
+            content_type,
+            concat!(
+                "Content-Type: application/vnd.openxmlformats-officedocument;\r\n",
+                " name*0=\"quarterly_report_2022_final_version.pdf\""
+            )
+        );
+        assert_eq!(
+            content_disposition,
             concat!(
                 "Content-Disposition: attachment;\r\n",
-                " filename*0*=utf-8''testing-to-see-what-happens-when-books-ar;\r\n",
-                " filename*1*=e-placed-in-the-second-part-%F0%9F%93%95%F0%9F%93%95;\r\n",
-                " filename*2*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
-                " filename*3*=%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95%F0%9F%93%95;\r\n",
-                " filename*4*=%F0%9F%93%95.txt"
+                " filename*0=\"quarterly_report_2022_final_version.pdf\""
             )
         );
     }
 
     #[test]
-    fn parameter_dont_split_on_hex_boundary() {
-        let base_header = "Content-Disposition: attachment;".to_string();
-        let line_len = base_header.len();
+    fn name_and_filename_key_too_long() {
+        let mut content_type = String::new();
+        let mut content_disposition = String::new();
 
-        for start_offset in &["", "x", "xx", "xxx"] {
-            let mut filename = start_offset.to_string();
+        {
+            let mut content_type_w = EmailWriter::new(&mut content_type, 0, 0, false);
+            let mut content_disposition_w = EmailWriter::new(&mut content_disposition, 0, 0, false);
+            content_disposition_w.set_max_line_len(20);
+            assert_eq!(
+                encode_name_and_filename(
+                    "faktúra.pdf",
+                    Options::default(),
+                    &mut content_type_w,
+                    &mut content_disposition_w,
+                ),
+                Err(EncodeError::KeyTooLong)
+            );
+        }
 
-            for i in 1..256 {
-                // 'Ü' results in two hex chars %C3%9C
-                filename.push('Ü');
+        assert_eq!(content_type, "");
+        assert_eq!(content_disposition, "");
+    }
 
-                let mut output = base_header.clone();
-                {
-                    let mut w = EmailWriter::new(&mut output, line_len, 0, true);
-                    encode("filename", &filename, &mut w).unwrap();
-                }
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_round_trip() {
+        let value = "faktúra_2022_06_04_letshaveaverylongfilenamewhynotemailcanhandleit.pdf";
+        let mut s = String::new();
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            encode("filename", value, &mut w).unwrap();
+        }
 
-                // look for all hex encoded chars
-                let output_len = output.len();
-                let mut found_hex_count = 0;
-                for (percent_sign_idx, _) in output.match_indices('%') {
-                    assert!(percent_sign_idx + 3 <= output_len);
+        let params = s
+            .split(';')
+            .map(|param| param.trim().split_once('=').unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(decode(params).unwrap(), [("filename", value.into())]);
+    }
 
-                    // verify we get the expected hex sequence for an 'Ü'
-                    let must_be_hex = &output[percent_sign_idx + 1..percent_sign_idx + 3];
-                    assert!(
-                        must_be_hex == "C3" || must_be_hex == "9C",
-                        "unexpected hex char: {}",
-                        must_be_hex
-                    );
-                    found_hex_count += 1;
-                }
-                // verify the number of hex encoded chars adds up
-                let number_of_chars_in_hex = 2;
-                assert_eq!(found_hex_count, i * number_of_chars_in_hex);
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_invalid() {
+        assert_eq!(
+            decode([("a*0", "b"), ("a*2", "c")]),
+            Err(DecodeError::InvalidParameter { index: 1 })
+        );
+        assert_eq!(
+            decode([("a*1", "b")]),
+            Err(DecodeError::InvalidParameter { index: 0 })
+        );
+        assert_eq!(
+            decode([("a*x", "b")]),
+            Err(DecodeError::InvalidParameter { index: 0 })
+        );
+        assert_eq!(
+            decode([("a*", "utf-8''%ZZ")]),
+            Err(DecodeError::InvalidParameter { index: 0 })
+        );
+        assert_eq!(
+            decode([("b", "c"), ("a*", "x-unknown''%C1")]),
+            Err(DecodeError::UnsupportedCharset { position: 1 })
+        );
+    }
 
-                // verify max line length
-                let mut last_newline_pos = 0;
-                for (newline_idx, _) in output.match_indices("\r\n") {
-                    let line_length = newline_idx - last_newline_pos;
-                    assert!(
-                        line_length < MAX_LINE_LEN,
-                        "expected line length exceeded: {} > {}",
-                        line_length,
-                        MAX_LINE_LEN
-                    );
-                    last_newline_pos = newline_idx;
-                }
-                // ensure there was at least one newline
-                assert_ne!(0, last_newline_pos);
-            }
-        }
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_lossy() {
+        let mut errors = Vec::new();
+        let params = super::decode_lossy(
+            [
+                ("a*1", "x"),
+                ("b*", "x-unknown''%C1b"),
+                ("c*0*", "utf-8''%FF"),
+                ("c*1", "c"),
+                ("d*", "utf-8''%Z"),
+            ],
+            |err| errors.push(err),
+        );
+        assert_eq!(
+            params,
+            [("b", "\u{FFFD}b".into()), ("c", "\u{FFFD}c".into()),]
+        );
+        assert_eq!(
+            errors,
+            [
+                DecodeError::InvalidParameter { index: 0 },
+                DecodeError::UnsupportedCharset { position: 1 },
+                DecodeError::InvalidCharsetData { position: 2 },
+                DecodeError::InvalidParameter { index: 4 },
+            ]
+        );
     }
 
+    #[cfg(feature = "decode")]
     #[test]
-    #[should_panic(expected = "`key` must only be composed of ascii alphanumeric chars")]
-    fn non_ascii_key() {
-        let mut s = String::new();
-        let mut w = EmailWriter::new(&mut s, 0, 0, true);
-        let _ = encode("📬", "", &mut w);
+    fn decode_reassembly() {
+        assert_eq!(
+            decode([
+                ("filename*2", "pdf"),
+                ("filename", "faktura.pdf"),
+                ("filename*0*", "utf-8''fakt%C3%BA"),
+                ("filename*1", "ra."),
+                ("size", "1024"),
+            ]),
+            Ok(vec![
+                ("filename", "faktúra.pdf".into()),
+                ("size", "1024".into()),
+            ])
+        );
+        assert_eq!(
+            decode([("a*1*", "%C3%A0"), ("a*0", "v")]),
+            Ok(vec![("a", "và".into())])
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_duplicates() {
+        let params = [
+            ("a", "1"),
+            ("a*0", "x"),
+            ("a*", "utf-8''y"),
+            ("a", "2"),
+            ("b", "3"),
+        ];
+        assert_eq!(
+            decode(params),
+            Err(DecodeError::InvalidParameter { index: 2 })
+        );
+
+        let options = DecodeOptions {
+            duplicates: Duplicates::LastWins,
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options(params, options),
+            Ok(vec![("a", "y".into()), ("b", "3".into()),])
+        );
+
+        let mut errors = Vec::new();
+        assert_eq!(
+            super::decode_lossy(params, |err| errors.push(err)),
+            [("a", "x".into()), ("b", "3".into()),]
+        );
+        assert_eq!(
+            errors,
+            [
+                DecodeError::InvalidParameter { index: 2 },
+                DecodeError::InvalidParameter { index: 3 },
+            ]
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_charset_aliases() {
+        assert_eq!(
+            decode([
+                ("a*", "latin1''%E1"),
+                ("b*0*", "UTF8''%C3"),
+                ("b*1*", "%A1")
+            ]),
+            Ok(vec![("a", "á".into()), ("b", "á".into()),])
+        );
+
+        let options = DecodeOptions {
+            charset_alias: Some(|label| (label == "x-mine").then_some("iso-8859-1")),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options([("a*", "x-mine''%E1")], options),
+            Ok(vec![("a", "á".into())])
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_limits() {
+        let params = [("a*0", "1"), ("a*1", "2"), ("a*2", "3"), ("b", "45")];
+        let limits = |max_output_len, max_sections| DecodeOptions {
+            limits: decode::Limits {
+                max_output_len,
+                max_sections,
+                ..decode::Limits::default()
+            },
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options(params, limits(5, 3)),
+            Ok(vec![("a", "123".into()), ("b", "45".into()),])
+        );
+        assert_eq!(
+            decode_with_options(params, limits(5, 2)),
+            Err(DecodeError::LimitExceeded(decode::Limit::Sections))
+        );
+        assert_eq!(
+            decode_with_options(params, limits(4, 3)),
+            Err(DecodeError::LimitExceeded(decode::Limit::OutputLen))
+        );
+
+        let mut errors = Vec::new();
+        assert_eq!(
+            decode_params(params, limits(3, 2), &mut |err| errors.push(err)),
+            [("a", "12".into())]
+        );
+        assert_eq!(
+            errors,
+            [
+                DecodeError::LimitExceeded(decode::Limit::Sections),
+                DecodeError::LimitExceeded(decode::Limit::OutputLen),
+            ]
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_borrowed() {
+        let params = decode([
+            ("a", "1"),
+            ("b*", "utf-8''2"),
+            ("c*", "us-ascii'en'3"),
+            ("d*0", "4"),
+            ("e*", "utf-8''%35"),
+            ("f*0", "6"),
+            ("f*1", "7"),
+        ])
+        .unwrap();
+        let borrowed = params
+            .iter()
+            .map(|(_, value)| matches!(value, Cow::Borrowed(_)))
+            .collect::<Vec<_>>();
+        assert_eq!(borrowed, [true, true, true, true, false, false]);
+        assert_eq!(params[4], ("e", "5".into()));
     }
 }
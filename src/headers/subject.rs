@@ -0,0 +1,217 @@
+//! `Subject` reply and forward prefixes.
+//!
+//! Replies and forwards reuse the subject of the original message,
+//! which may already contain encoded-words, behind a `Re:` or `Fwd:`
+//! prefix.
+
+use alloc::format;
+use core::fmt::{self, Write};
+
+use super::{encoded_words, unstructured, utils, writer::EmailWriter};
+
+/// The prefix added by [`encode_with_prefix`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Prefix {
+    /// `Re:`, for replies
+    Re,
+    /// `Fwd:`, for forwards
+    Fwd,
+}
+
+impl Prefix {
+    /// Get the prefix as a string, including its colon.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Re => "Re:",
+            Self::Fwd => "Fwd:",
+        }
+    }
+
+    /// The prefixes, in lowercase, which are considered to be the same as `self`
+    fn aliases(self) -> &'static [&'static str] {
+        match self {
+            Self::Re => &["re:"],
+            Self::Fwd => &["fwd:", "fw:"],
+        }
+    }
+}
+
+/// Encode `subject` preceded by `prefix`.
+///
+/// `subject` is the raw value of the `Subject` header of the original
+/// message, which may be folded and contain encoded-words. Existing
+/// occurrences of `prefix` at the start of the subject, in any case,
+/// are removed so that prefixes don't pile up, while other prefixes
+/// are kept: replying to `Fwd: Hello` gives `Re: Fwd: Hello`.
+///
+/// The subject is decoded, and the result is encoded again via
+/// [`unstructured::encode`]. Subjects using charsets other than
+/// `utf-8`, `us-ascii` and `iso-8859-1` can only be decoded if the
+/// `encoding_rs` feature is enabled. Otherwise the encoded-words which
+/// can't be decoded are written unchanged, the rest of the subject is
+/// encoded via [`unstructured::encode`], and only the text preceding
+/// them is looked at for existing prefixes.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::{subject::Prefix, writer::EmailWriter};
+/// # fn main() -> std::fmt::Result {
+/// let mut output = String::from("Subject: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::subject::encode_with_prefix(
+///         Prefix::Re,
+///         "RE: =?utf-8?b?wqFIb2xhIQ==?=",
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(output, "Subject: Re: =?utf-8?b?wqFIb2xhIQ==?=");
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode_with_prefix<W: ?Sized + Write>(
+    prefix: Prefix,
+    subject: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    let subject = utils::unfold(subject);
+
+    match encoded_words::decode_words(&subject) {
+        Ok(decoded) => {
+            let rest = strip_prefix(prefix, &decoded);
+            if rest.is_empty() {
+                w.write_str(prefix.as_str())
+            } else {
+                unstructured::encode(&format!("{} {}", prefix.as_str(), rest), w)
+            }
+        }
+        Err(_) => {
+            let rest = strip_prefix(prefix, &subject);
+            w.write_str(prefix.as_str())?;
+            w.space();
+
+            // The encoded-words are kept as they are, while the text
+            // around them gets encoded again
+            let mut start = 0;
+            let mut word_start = 0;
+            for word in rest.split(' ') {
+                if is_encoded_word(word) {
+                    unstructured::encode(&rest[start..word_start], w)?;
+                    w.folding().write_str(word)?;
+                    start = word_start + word.len();
+                }
+                word_start += word.len() + " ".len();
+            }
+            unstructured::encode(&rest[start..], w)
+        }
+    }
+}
+
+/// Whether `word` looks like an encoded-word which can be written as is.
+fn is_encoded_word(word: &str) -> bool {
+    word.len() > "=??=".len()
+        && word.starts_with("=?")
+        && word.ends_with("?=")
+        && utils::str_is_ascii_printable(word)
+}
+
+/// Remove the leading occurrences of `prefix` from `subject`.
+fn strip_prefix(prefix: Prefix, mut subject: &str) -> &str {
+    loop {
+        subject = subject.trim_start_matches([' ', '\t']);
+        let stripped = prefix.aliases().iter().find_map(|alias| {
+            subject
+                .get(..alias.len())
+                .filter(|start| start.eq_ignore_ascii_case(alias))
+                .map(|_| &subject[alias.len()..])
+        });
+        match stripped {
+            Some(rest) => subject = rest,
+            None => return subject.trim_end_matches([' ', '\t']),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn with_prefix(prefix: Prefix, subject: &str) -> String {
+        let mut s = "Subject: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode_with_prefix(prefix, subject, &mut w).unwrap();
+        }
+
+        s
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(with_prefix(Prefix::Re, "Hello"), "Subject: Re: Hello");
+        assert_eq!(
+            with_prefix(Prefix::Re, "re: Re:RE:  Hello"),
+            "Subject: Re: Hello"
+        );
+        assert_eq!(
+            with_prefix(Prefix::Re, "Fwd: Hello"),
+            "Subject: Re: Fwd: Hello"
+        );
+        assert_eq!(
+            with_prefix(Prefix::Fwd, "FW: Fwd: Hello"),
+            "Subject: Fwd: Hello"
+        );
+        assert_eq!(with_prefix(Prefix::Re, ""), "Subject: Re:");
+    }
+
+    #[test]
+    fn encoded_prefix() {
+        assert_eq!(
+            with_prefix(Prefix::Re, "=?utf-8?q?Re=3A_Adri=C3=A1n?="),
+            "Subject: Re: =?utf-8?b?QWRyacOhbg==?="
+        );
+    }
+
+    #[test]
+    fn folded() {
+        assert_eq!(
+            with_prefix(
+                Prefix::Fwd,
+                "Re: =?utf-8?b?wqFIb2xh?=\r\n =?utf-8?b?IQ==?= world"
+            ),
+            "Subject: Fwd: Re: =?utf-8?b?wqFIb2xhIQ==?= world"
+        );
+    }
+
+    #[test]
+    fn unknown_charset() {
+        assert_eq!(
+            with_prefix(Prefix::Re, "Re: =?x-unknown?b?8NLJ18XU?="),
+            "Subject: Re: =?x-unknown?b?8NLJ18XU?="
+        );
+        assert_eq!(
+            with_prefix(Prefix::Re, "=?x-unknown?b?8NLJ18XU?="),
+            "Subject: Re: =?x-unknown?b?8NLJ18XU?="
+        );
+    }
+
+    #[test]
+    fn unknown_charset_encodes_the_rest() {
+        assert_eq!(
+            with_prefix(Prefix::Re, "Réponse =?x-unknown?b?QQ==?="),
+            "Subject: Re: =?utf-8?b?UsOpcG9uc2U=?= =?x-unknown?b?QQ==?="
+        );
+        assert_eq!(
+            with_prefix(Prefix::Re, "Hi\rBcc: x =?x-unknown?b?QQ==?="),
+            "Subject: Re: Hi Bcc: x =?x-unknown?b?QQ==?="
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! [RFC 5322] unstructured header value encoder.
+//!
+//! [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.5
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+use super::{rfc2047, writer::EmailWriter};
+
+/// Encode arbitrary text as an unstructured header value, such as
+/// `Subject`, `Comments` or any custom `X-` header.
+///
+/// This is the safe way of putting user supplied text into a header:
+///
+/// * line breaks are replaced by a single space, so that they can't
+///   be used to inject headers;
+/// * words containing non-ASCII or control chars, or which could be
+///   mistaken for encoded-words, are encoded via RFC 2047, grouping
+///   adjacent words together, while the other words are written as is;
+/// * lines are folded at spaces.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::EmailWriter;
+/// # fn main() -> std::fmt::Result {
+/// let mut output = String::from("X-Comment: ");
+/// {
+///     let line_len = output.len();
+///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+///     email_encoding::headers::unstructured::encode(
+///         "Thanks Adrián!\r\nBcc: attacker@example.com",
+///         &mut writer,
+///     )?;
+/// }
+/// assert_eq!(
+///     output,
+///     "X-Comment: Thanks =?utf-8?b?QWRyacOhbiE=?= Bcc: attacker@example.com"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode<W: ?Sized + Write>(value: &str, w: &mut EmailWriter<'_, W>) -> fmt::Result {
+    let mut sanitized;
+    let value = if value.contains(['\r', '\n']) {
+        sanitized = String::with_capacity(value.len());
+        for (i, line) in value
+            .split(['\r', '\n'])
+            .filter(|line| !line.is_empty())
+            .enumerate()
+        {
+            if i > 0 {
+                sanitized.push(' ');
+            }
+            sanitized.push_str(line);
+        }
+        sanitized.as_str()
+    } else {
+        value
+    };
+
+    // Byte range of the words waiting to be encoded together
+    let mut run: Option<(usize, usize)> = None;
+    let mut spaces = 0;
+    let mut start = 0;
+    for (i, word) in value.split(' ').enumerate() {
+        let end = start + word.len();
+        if i > 0 {
+            spaces += 1;
+        }
+
+        if word.is_empty() {
+            // Consecutive spaces
+        } else if needs_encoding(word) {
+            run = match run {
+                // The spaces in between get encoded too, since the ones
+                // between two encoded-words are ignored when decoding
+                Some((run_start, _)) => Some((run_start, end)),
+                None => {
+                    write_spaces(spaces, w);
+                    Some((start, end))
+                }
+            };
+            spaces = 0;
+        } else {
+            if let Some((run_start, run_end)) = run.take() {
+                rfc2047::encode(&value[run_start..run_end], w)?;
+            }
+            write_spaces(spaces, w);
+            spaces = 0;
+            w.folding().write_str(word)?;
+        }
+
+        start = end + 1;
+    }
+
+    if let Some((run_start, run_end)) = run {
+        rfc2047::encode(&value[run_start..run_end], w)?;
+    }
+    write_spaces(spaces, w);
+
+    Ok(())
+}
+
+fn needs_encoding(word: &str) -> bool {
+    word.contains("=?") || !word.bytes().all(|b| matches!(b, b'\t' | b'!'..=b'~'))
+}
+
+fn write_spaces<W: ?Sized + Write>(spaces: usize, w: &mut EmailWriter<'_, W>) {
+    for _ in 0..spaces {
+        w.space();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn ascii() {
+        let mut s = "X-Note: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode(
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor",
+                &mut w,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "X-Note: Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do\r\n",
+                " eiusmod tempor"
+            )
+        );
+    }
+
+    #[test]
+    fn grouped_words() {
+        let mut s = "X-Note: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("Hi  Adrián  García  =?x?=  bye", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            "X-Note: Hi  =?utf-8?b?QWRyacOhbiAgR2FyY8OtYSAgPT94Pz0=?=  bye"
+        );
+    }
+
+    #[test]
+    fn line_breaks() {
+        let mut s = "X-Note: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("\r\nfirst\r\n\r\nsecond\nthird\r", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "X-Note: first second third");
+    }
+
+    #[test]
+    fn control_chars() {
+        let mut s = "X-Note: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            encode("nul\0 byte", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "X-Note: =?utf-8?b?bnVsAA==?= byte");
+    }
+}
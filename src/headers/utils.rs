@@ -1,11 +1,89 @@
-use std::fmt::{self, Write};
+use alloc::{borrow::Cow, string::String};
+use core::fmt::{self, Write};
+
+use super::writer::EmailWriter;
 
 pub(super) fn str_is_ascii_alphanumeric(s: &str) -> bool {
     s.bytes().all(|c| c.is_ascii_alphanumeric())
 }
 
-pub(super) const fn char_is_ascii_alphanumeric_plus(c: char) -> bool {
-    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+/// `atext` as defined by RFC 5322
+pub(super) const fn char_is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '!' | '#'
+                | '$'
+                | '%'
+                | '&'
+                | '\''
+                | '*'
+                | '+'
+                | '-'
+                | '/'
+                | '='
+                | '?'
+                | '^'
+                | '_'
+                | '`'
+                | '{'
+                | '|'
+                | '}'
+                | '~'
+        )
+}
+
+/// `tspecials` as defined by RFC 2045
+pub(super) const fn char_is_tspecial(c: char) -> bool {
+    matches!(
+        c,
+        '(' | ')' | '<' | '>' | '@' | ',' | ';' | ':' | '\\' | '"' | '/' | '[' | ']' | '?' | '='
+    )
+}
+
+/// `token` chars as defined by RFC 2045
+pub(super) const fn char_is_token(c: char) -> bool {
+    c.is_ascii_graphic() && !char_is_tspecial(c)
+}
+
+/// `attribute-char` as defined by RFC 2231
+pub(super) const fn char_is_attribute_char(c: char) -> bool {
+    char_is_token(c) && !matches!(c, '*' | '\'' | '%')
+}
+
+/// `ftext` as defined by RFC 5322
+pub(super) const fn char_is_ftext(c: char) -> bool {
+    matches!(c, '!'..='9' | ';'..='~')
+}
+
+/// Find the first char of `s` which isn't allowed in a `dot-atom-text`,
+/// including misplaced dots
+pub(super) fn find_invalid_dot_atom_char(s: &str, allow_utf8: bool) -> Option<(usize, char)> {
+    let bytes = s.as_bytes();
+    s.char_indices().find(|&(i, c)| {
+        if c == '.' {
+            i == 0 || i + 1 == bytes.len() || bytes[i - 1] == b'.'
+        } else {
+            !char_is_atext(c) && (c.is_ascii() || !allow_utf8)
+        }
+    })
+}
+
+/// Find the first char of `domain` which isn't allowed in a `dot-atom-text`
+/// or, if `domain` is enclosed in brackets, in a domain literal
+pub(super) fn find_invalid_domain_char(domain: &str, allow_utf8: bool) -> Option<(usize, char)> {
+    if let Some(literal) = domain
+        .strip_prefix('[')
+        .and_then(|domain| domain.strip_suffix(']'))
+    {
+        // `dtext`
+        literal
+            .char_indices()
+            .find(|&(_, c)| !matches!(c, '!'..='Z' | '^'..='~'))
+            .map(|(position, c)| (position + 1, c))
+    } else {
+        find_invalid_dot_atom_char(domain, allow_utf8)
+    }
 }
 
 pub(super) fn str_is_ascii_printable(s: &str) -> bool {
@@ -17,18 +95,16 @@ const fn char_is_ascii_printable(c: u8) -> bool {
 }
 
 pub(super) fn write_escaped(s: &str, w: &mut impl Write) -> fmt::Result {
-    debug_assert!(s.is_ascii());
-
-    for b in s.bytes() {
-        match b {
-            b'\\' => {
+    for c in s.chars() {
+        match c {
+            '\\' => {
                 w.write_str("\\\\")?;
             }
-            b'"' => {
+            '"' => {
                 w.write_str("\\\"")?;
             }
-            b => {
-                w.write_char(char::from(b))?;
+            c => {
+                w.write_char(c)?;
             }
         }
     }
@@ -36,9 +112,81 @@ pub(super) fn write_escaped(s: &str, w: &mut impl Write) -> fmt::Result {
     Ok(())
 }
 
-pub(super) fn truncate_to_char_boundary(s: &str, mut max: usize) -> &str {
-    assert!(max <= s.len());
+/// Write `s` without folding it, moving it to a new line if it fits
+/// a new line but not the current one.
+pub(super) fn write_unfolded<W: ?Sized + Write>(
+    s: &str,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    let can_go_to_new_line = w.can_go_to_new_line_now();
+    new_line_if_needed(s, can_go_to_new_line, w)?;
+    w.write_str(s)
+}
+
+/// Write `s` preceded by a space unless it's the `first` word, moving it
+/// to a new line if it fits a new line but not the current one,
+/// otherwise folding it at spaces.
+pub(super) fn write_folded<W: ?Sized + Write>(
+    s: &str,
+    first: bool,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    if !first {
+        w.space();
+    }
+
+    let can_go_to_new_line = !first || w.can_go_to_new_line_now();
+    new_line_if_needed(s, can_go_to_new_line, w)?;
+    w.folding().write_str(s)
+}
+
+/// Go to a new line if `s` fits a new line but not the current one.
+fn new_line_if_needed<W: ?Sized + Write>(
+    s: &str,
+    can_go_to_new_line: bool,
+    w: &mut EmailWriter<'_, W>,
+) -> fmt::Result {
+    if can_go_to_new_line
+        && w.projected_line_len() + s.len() > w.max_line_len()
+        && " ".len() + s.len() <= w.max_line_len()
+    {
+        // Fits a new line, but not the current one
+        w.new_line()?;
+        if !w.has_spaces() {
+            w.space();
+        }
+    }
+
+    Ok(())
+}
+
+/// Unfold a raw header value.
+///
+/// Folds, a `CRLF` or `LF` followed by a space or a tab, are removed.
+/// Every other `CR` or `LF` is replaced by a space, so that it can't be
+/// used to inject headers.
+pub(super) fn unfold(value: &str) -> Cow<'_, str> {
+    if !value.contains(['\r', '\n']) {
+        return Cow::Borrowed(value);
+    }
 
+    let mut unfolded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(i) = rest.find(['\r', '\n']) {
+        unfolded.push_str(&rest[..i]);
+        let after = &rest[i..];
+        let line_break = if after.starts_with("\r\n") { 2 } else { 1 };
+        rest = &after[line_break..];
+        if !rest.starts_with([' ', '\t']) {
+            unfolded.push(' ');
+        }
+    }
+    unfolded.push_str(rest);
+    Cow::Owned(unfolded)
+}
+
+pub(super) fn truncate_to_char_boundary(s: &str, max: usize) -> &str {
+    let mut max = max.min(s.len());
     while !s.is_char_boundary(max) {
         max -= 1;
     }
@@ -51,6 +199,13 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn unfold_line_breaks() {
+        assert_eq!(unfold("Hello"), "Hello");
+        assert_eq!(unfold("Hello,\r\n world\n\tagain"), "Hello, world\tagain");
+        assert_eq!(unfold("a\r\nBcc: b\rc\nd\r"), "a Bcc: b c d ");
+    }
+
     #[test]
     fn truncate_ascii() {
         assert_eq!(truncate_to_char_boundary("12345678", 4), "1234");
@@ -77,8 +232,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn truncate_out_of_bounds() {
-        let _ = truncate_to_char_boundary("12345678", 16);
+        assert_eq!(truncate_to_char_boundary("12345678", 16), "12345678");
     }
 }
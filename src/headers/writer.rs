@@ -2,19 +2,39 @@
 //!
 //! [`Write`]: std::fmt::Write
 
-use std::fmt::{self, Write};
+use alloc::{boxed::Box, string::String};
+use core::fmt::{self, Write};
 
-use super::MAX_LINE_LEN;
+use super::{utils, FieldNameError, MAX_LINE_LEN};
 
 /// Wrapper around [`Write`] that remembers the length of the
 /// last line written to it.
 ///
+/// The inner writer is a `dyn Write` by default, which is what the
+/// encoders of this crate accept unless told otherwise. Use
+/// [`EmailWriter::new_generic`] to get calls to the inner writer
+/// statically dispatched instead.
+///
 /// [`Write`]: std::fmt::Write
-pub struct EmailWriter<'a> {
-    writer: &'a mut dyn Write,
+pub struct EmailWriter<'a, W: ?Sized + Write = dyn Write + 'a> {
+    writer: &'a mut W,
     line_len: usize,
     spaces: usize,
     can_go_to_new_line_now: bool,
+    max_line_len: usize,
+    folding_whitespace: FoldingWhitespace,
+    folded: bool,
+    trailing_spaces: TrailingSpaces,
+    breakpoints: &'static [char],
+    after_breakpoint: bool,
+    no_fold: bool,
+    line_width: LineWidth,
+    reject_stray_line_breaks: bool,
+    found_stray_line_break: bool,
+    strict: bool,
+    violation: Option<Violation>,
+    line_ending: LineEnding,
+    allow_utf8: bool,
 }
 
 impl<'a> EmailWriter<'a> {
@@ -30,20 +50,701 @@ impl<'a> EmailWriter<'a> {
         line_len: usize,
         spaces: usize,
         can_go_to_new_line_now: bool,
+    ) -> Self {
+        Self::new_generic(writer, line_len, spaces, can_go_to_new_line_now)
+    }
+
+    /// Construct a new [`EmailWriterBuilder`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::{EmailWriter, FoldingWhitespace};
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("Subject: ");
+    /// {
+    ///     let mut writer = EmailWriter::builder()
+    ///         .line_len(output.len())
+    ///         .max_line_len(78)
+    ///         .folding_whitespace(FoldingWhitespace::Tab)
+    ///         .build(&mut output);
+    ///     email_encoding::headers::rfc2047::encode("Adrián", &mut writer)?;
+    /// }
+    /// assert_eq!(output, "Subject: =?utf-8?b?QWRyacOhbg==?=");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> EmailWriterBuilder {
+        EmailWriterBuilder::new()
+    }
+}
+
+impl<'a, W: ?Sized + Write> EmailWriter<'a, W> {
+    /// Construct a new `EmailWriter`, statically dispatching
+    /// calls to `writer`.
+    ///
+    /// See [`EmailWriter::new`] for the meaning of the arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("Subject: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer: EmailWriter<'_, String> =
+    ///         EmailWriter::new_generic(&mut output, line_len, 0, false);
+    ///     email_encoding::headers::rfc2047::encode("Adrián", &mut writer)?;
+    /// }
+    /// assert_eq!(output, "Subject: =?utf-8?b?QWRyacOhbg==?=");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_generic(
+        writer: &'a mut W,
+        line_len: usize,
+        spaces: usize,
+        can_go_to_new_line_now: bool,
     ) -> Self {
         Self {
             writer,
             line_len,
             spaces,
             can_go_to_new_line_now,
+            max_line_len: MAX_LINE_LEN,
+            folding_whitespace: FoldingWhitespace::default(),
+            folded: false,
+            trailing_spaces: TrailingSpaces::default(),
+            breakpoints: &[],
+            after_breakpoint: false,
+            no_fold: false,
+            line_width: LineWidth::default(),
+            reject_stray_line_breaks: false,
+            found_stray_line_break: false,
+            strict: false,
+            violation: None,
+            line_ending: LineEnding::default(),
+            allow_utf8: false,
         }
     }
 
+    /// Get the maximum length a line should have.
+    ///
+    /// Defaults to [`MAX_LINE_LEN`]. Lengths are measured according to [`EmailWriter::line_width`].
+    pub fn max_line_len(&self) -> usize {
+        self.max_line_len
+    }
+
+    /// Set the maximum length a line should have.
+    ///
+    /// Encoders writing to this `EmailWriter` fold their output
+    /// so that lines don't exceed `max_line_len`, whenever possible.
+    pub fn set_max_line_len(&mut self, max_line_len: usize) {
+        self.max_line_len = max_line_len;
+    }
+
+    /// Get whether the current line can be wrapped now or not.
+    ///
+    /// Lines become wrappable as soon as something other than spaces
+    /// gets written to them, and stop being so after going to a new line.
+    pub fn can_go_to_new_line_now(&self) -> bool {
+        self.can_go_to_new_line_now
+    }
+
+    /// Set whether the current line can be wrapped now or not.
+    ///
+    /// Custom encoders writing to the inner writer by other means than
+    /// this `EmailWriter` can use this to tell whether a fold is allowed
+    /// before the next write.
+    pub fn set_can_go_to_new_line_now(&mut self, can_go_to_new_line_now: bool) {
+        self.can_go_to_new_line_now = can_go_to_new_line_now;
+    }
+
+    /// Get the whitespace char which starts folded lines.
+    ///
+    /// Defaults to [`FoldingWhitespace::Space`].
+    pub fn folding_whitespace(&self) -> FoldingWhitespace {
+        self.folding_whitespace
+    }
+
+    /// Set the whitespace char which starts folded lines.
+    ///
+    /// Only the first space written after going to a new line
+    /// is affected.
+    pub fn set_folding_whitespace(&mut self, folding_whitespace: FoldingWhitespace) {
+        self.folding_whitespace = folding_whitespace;
+    }
+
+    /// Get what happens to the spaces pending when the writer ends.
+    ///
+    /// Defaults to [`TrailingSpaces::Flush`].
+    pub fn trailing_spaces(&self) -> TrailingSpaces {
+        self.trailing_spaces
+    }
+
+    /// Set what happens to the spaces pending when the writer ends,
+    /// either by being [finished](EmailWriter::finish) or dropped.
+    pub fn set_trailing_spaces(&mut self, trailing_spaces: TrailingSpaces) {
+        self.trailing_spaces = trailing_spaces;
+    }
+
+    /// Get how the length of lines is measured.
+    ///
+    /// Defaults to [`LineWidth::Bytes`].
+    pub fn line_width(&self) -> LineWidth {
+        self.line_width
+    }
+
+    /// Set how the length of lines is measured.
+    ///
+    /// The length of the line the `EmailWriter` was constructed with
+    /// must have been measured the same way.
+    pub fn set_line_width(&mut self, line_width: LineWidth) {
+        self.line_width = line_width;
+    }
+
+    /// Get whether writing CR or LF chars fails.
+    ///
+    /// Defaults to `false`.
+    pub fn rejects_stray_line_breaks(&self) -> bool {
+        self.reject_stray_line_breaks
+    }
+
+    /// Set whether writing CR or LF chars fails.
+    ///
+    /// Line breaks must only be written by [`EmailWriter::new_line`].
+    /// Enabling this makes any attempt at writing them some other way
+    /// fail with [`fmt::Error`], without writing anything, which catches
+    /// header injection bugs early. [`EmailWriter::found_stray_line_break`]
+    /// then tells why writing failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # use std::fmt::Write;
+    /// let mut output = String::from("Subject: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     writer.set_reject_stray_line_breaks(true);
+    ///
+    ///     assert!(writer.write_str("Hello\r\nBcc: attacker@example.com").is_err());
+    ///     assert!(writer.found_stray_line_break());
+    /// }
+    /// assert_eq!(output, "Subject: ");
+    /// ```
+    pub fn set_reject_stray_line_breaks(&mut self, reject_stray_line_breaks: bool) {
+        self.reject_stray_line_breaks = reject_stray_line_breaks;
+    }
+
+    /// Get whether a write failed because of a CR or LF char.
+    ///
+    /// See [`EmailWriter::set_reject_stray_line_breaks`].
+    pub fn found_stray_line_break(&self) -> bool {
+        self.found_stray_line_break
+    }
+
+    /// Get whether the writer is in strict mode.
+    ///
+    /// Defaults to `false`.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Set whether the writer is in strict mode.
+    ///
+    /// By default the writer accepts anything it is given, and lines
+    /// which can't be folded may get longer than
+    /// [`EmailWriter::max_line_len`]. In strict mode, writes fail with
+    /// [`fmt::Error`], without writing anything, if they would produce
+    /// a header which doesn't conform to [RFC 5322]:
+    ///
+    /// * CR or LF chars, like with
+    ///   [`EmailWriter::set_reject_stray_line_breaks`]
+    /// * control chars other than tab
+    /// * lines longer than [`EmailWriter::max_line_len`]
+    ///
+    /// [`EmailWriter::violation`] then tells why writing failed. Set the
+    /// maximum line length to [`HARD_LINE_LIMIT`] to only reject lines
+    /// which must not be sent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::{EmailWriter, Violation};
+    /// # use std::fmt::Write;
+    /// let mut output = String::from("Subject: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     writer.set_strict(true);
+    ///
+    ///     assert!(writer.write_str("Hello\x07").is_err());
+    ///     assert_eq!(writer.violation(), Some(Violation::ControlChar('\x07')));
+    /// }
+    /// assert_eq!(output, "Subject: ");
+    /// ```
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-2.2
+    /// [`HARD_LINE_LIMIT`]: super::HARD_LINE_LIMIT
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Get why writing failed, if a write was rejected because of
+    /// [`EmailWriter::set_strict`] or
+    /// [`EmailWriter::set_reject_stray_line_breaks`].
+    pub fn violation(&self) -> Option<Violation> {
+        self.violation
+    }
+
+    /// Check whether `s`, which mustn't end with spaces, may be written
+    fn check_write(&mut self, s: &str) -> fmt::Result {
+        let violation =
+            if (self.reject_stray_line_breaks || self.strict) && s.contains(['\r', '\n']) {
+                self.found_stray_line_break = true;
+                Violation::LineBreak
+            } else if !self.strict {
+                return Ok(());
+            } else if let Some(c) = s.chars().find(|&c| c.is_ascii_control() && c != '\t') {
+                Violation::ControlChar(c)
+            } else if self.projected_line_len() + self.line_width.measure(s) > self.max_line_len {
+                Violation::LineTooLong
+            } else {
+                return Ok(());
+            };
+
+        debug!(?violation, "rejecting write");
+        self.violation = Some(violation);
+        Err(fmt::Error)
+    }
+
+    /// Get the chars after which [`FoldingEmailWriter`] may fold,
+    /// besides spaces.
+    ///
+    /// Defaults to none.
+    pub fn breakpoints(&self) -> &'static [char] {
+        self.breakpoints
+    }
+
+    /// Set the chars after which [`FoldingEmailWriter`] may fold,
+    /// besides spaces.
+    ///
+    /// Folding after a breakpoint inserts whitespace which wasn't there,
+    /// so breakpoints must only be used where [RFC 5322] allows `CFWS`,
+    /// such as after the commas separating addresses.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # use std::fmt::Write;
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("To: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     writer.set_breakpoints(&[',']);
+    ///     writer.folding().write_str(
+    ///         "first.person@example.com,second.person@example.com,third.person@example.com",
+    ///     )?;
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     concat!(
+    ///         "To: first.person@example.com,second.person@example.com,\r\n",
+    ///         " third.person@example.com"
+    ///     )
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.2
+    pub fn set_breakpoints(&mut self, breakpoints: &'static [char]) {
+        self.breakpoints = breakpoints;
+    }
+
+    /// Get the line terminator written by [`EmailWriter::new_line`].
+    ///
+    /// Defaults to [`LineEnding::CrLf`].
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Set the line terminator written by [`EmailWriter::new_line`].
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// Get whether encoders may write non-ASCII chars as raw UTF-8.
+    ///
+    /// Defaults to `false`.
+    pub fn allows_utf8(&self) -> bool {
+        self.allow_utf8
+    }
+
+    /// Set whether encoders may write non-ASCII chars as raw UTF-8.
+    ///
+    /// Only enable this if the message is going to be sent to a server
+    /// supporting the `SMTPUTF8` extension, as defined by [RFC 6532].
+    /// The [`rfc2047`], [`quoted_string`], [`rfc2231`] and [`address`]
+    /// encoders then write non-ASCII text as is, instead of using
+    /// encoded-words or extended parameter values.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("Subject: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     writer.set_allow_utf8(true);
+    ///     email_encoding::headers::rfc2047::encode("¡Hola, señor!", &mut writer)?;
+    /// }
+    /// assert_eq!(output, "Subject: ¡Hola, señor!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [RFC 6532]: https://datatracker.ietf.org/doc/html/rfc6532
+    /// [`rfc2047`]: super::rfc2047
+    /// [`quoted_string`]: super::quoted_string
+    /// [`rfc2231`]: super::rfc2231
+    /// [`address`]: super::address
+    pub fn set_allow_utf8(&mut self, allow_utf8: bool) {
+        self.allow_utf8 = allow_utf8;
+    }
+
     /// Go to a new line and reset the `line_len` to `0`.
     pub fn new_line(&mut self) -> fmt::Result {
-        self.writer.write_str("\r\n")?;
+        debug!(line_len = self.line_len, "going to a new line");
+        self.writer.write_str(self.line_ending.as_str())?;
         self.line_len = 0;
         self.can_go_to_new_line_now = false;
+        self.folded = true;
+
+        Ok(())
+    }
+
+    /// Start a new header by writing `name` followed by `: `.
+    ///
+    /// If something has already been written to the current line,
+    /// the previous header gets terminated by going to a new line first.
+    /// The state of the writer is then reset, so that the header value
+    /// can be written right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyFieldName`] or [`Error::InvalidFieldName`] if `name`
+    /// isn't a valid [RFC 5322] field name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # fn main() -> Result<(), email_encoding::headers::writer::Error> {
+    /// let mut output = String::new();
+    /// {
+    ///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+    ///     writer.start_header("Subject")?;
+    ///     email_encoding::headers::rfc2047::encode("Adrián", &mut writer)?;
+    ///     writer.start_header("X-Mailer")?;
+    ///     email_encoding::headers::quoted_string::encode("lettre", &mut writer)?;
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     "Subject: =?utf-8?b?QWRyacOhbg==?=\r\nX-Mailer: lettre"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.8
+    pub fn start_header(&mut self, name: &str) -> Result<(), Error> {
+        match super::validate_field_name(name) {
+            Ok(()) => {}
+            Err(FieldNameError::Empty) => return Err(Error::EmptyFieldName),
+            Err(FieldNameError::InvalidByte { position, .. }) => {
+                // Non-ASCII chars are reported at their first byte
+                let c = name[position..]
+                    .chars()
+                    .next()
+                    .expect("`position` is in bounds");
+                return Err(Error::InvalidFieldName { position, c });
+            }
+        }
+
+        self.spaces = 0;
+        if self.line_len > 0 {
+            self.new_line()?;
+        }
+
+        self.writer.write_str(name)?;
+        self.writer.write_str(": ")?;
+        self.line_len = self.line_width.measure(name) + ": ".len();
+        self.can_go_to_new_line_now = false;
+        self.folded = false;
+
+        Ok(())
+    }
+
+    /// Write an [RFC 5322] `atom`, folding before it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Empty`] or [`Error::InvalidChar`] if `atom`
+    /// isn't a valid atom.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::{EmailWriter, Error};
+    /// let mut output = String::new();
+    /// {
+    ///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+    ///     writer.write_atom("John").unwrap();
+    ///     assert_eq!(
+    ///         writer.write_atom("J. Smith"),
+    ///         Err(Error::InvalidChar {
+    ///             position: 1,
+    ///             c: '.'
+    ///         })
+    ///     );
+    /// }
+    /// assert_eq!(output, "John");
+    /// ```
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.3
+    pub fn write_atom(&mut self, atom: &str) -> Result<(), Error> {
+        validate_chars(atom, utils::char_is_atext)?;
+        self.folding().write_str(atom)?;
+        Ok(())
+    }
+
+    /// Write an [RFC 5322] `dot-atom`, folding before it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Empty`] or [`Error::InvalidChar`] if `dot_atom`
+    /// isn't a valid dot-atom. Dots are invalid at the start, at the end
+    /// and right after another dot.
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.3
+    pub fn write_dot_atom(&mut self, dot_atom: &str) -> Result<(), Error> {
+        validate_chars(dot_atom, |c| utils::char_is_atext(c) || c == '.')?;
+        let bytes = dot_atom.as_bytes();
+        let misplaced_dot = (0..bytes.len()).find(|&i| {
+            bytes[i] == b'.' && (i == 0 || i + 1 == bytes.len() || bytes[i - 1] == b'.')
+        });
+        if let Some(position) = misplaced_dot {
+            return Err(Error::InvalidChar { position, c: '.' });
+        }
+
+        self.folding().write_str(dot_atom)?;
+        Ok(())
+    }
+
+    /// Write an [RFC 2045] `token`, folding before it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Empty`] or [`Error::InvalidChar`] if `token`
+    /// isn't a valid token.
+    ///
+    /// [RFC 2045]: https://datatracker.ietf.org/doc/html/rfc2045#section-5.1
+    pub fn write_token(&mut self, token: &str) -> Result<(), Error> {
+        validate_chars(token, utils::char_is_token)?;
+        self.folding().write_str(token)?;
+        Ok(())
+    }
+
+    /// Write an [RFC 5322] `quoted-pair`, escaping `c` with a backslash.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidChar`] if `c` isn't a visible ASCII char,
+    /// a space or a tab.
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.1
+    pub fn write_quoted_pair(&mut self, c: char) -> Result<(), Error> {
+        if !matches!(c, ' '..='~' | '\t') {
+            return Err(Error::InvalidChar { position: 0, c });
+        }
+
+        self.write_char('\\')?;
+        // Keep the space from being buffered
+        self.write_char_inner(c, false)?;
+        Ok(())
+    }
+
+    /// Write [RFC 5322] folding whitespace, which is a space which
+    /// may get wrapped to a new line on the next write.
+    ///
+    /// This is the same as [`EmailWriter::space`].
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.2
+    pub fn write_fws(&mut self) {
+        self.space();
+    }
+
+    /// Write folding whitespace followed by an [RFC 5322] `comment`.
+    ///
+    /// `comment` gets surrounded by parentheses, and its parentheses and
+    /// backslashes get escaped. The comment may get folded at its spaces.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidChar`] if `comment` contains ASCII control chars
+    /// other than tab, or non-ASCII chars unless [`EmailWriter::allows_utf8`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # use std::fmt::Write;
+    /// # fn main() -> Result<(), email_encoding::headers::writer::Error> {
+    /// let mut output = String::from("Date: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     writer.write_str("Thu, 13 Feb 1969 23:32:54 -0330")?;
+    ///     writer.write_cfws("Newfoundland (Canada) time")?;
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     "Date: Thu, 13 Feb 1969 23:32:54 -0330 (Newfoundland \\(Canada\\) time)"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.2
+    pub fn write_cfws(&mut self, comment: &str) -> Result<(), Error> {
+        let allow_utf8 = self.allows_utf8();
+        if let Some((position, c)) = comment
+            .char_indices()
+            .find(|&(_, c)| (c.is_ascii_control() && c != '\t') || (!allow_utf8 && !c.is_ascii()))
+        {
+            return Err(Error::InvalidChar { position, c });
+        }
+
+        let mut escaped = String::with_capacity(comment.len() + 2);
+        escaped.push('(');
+        for c in comment.chars() {
+            if matches!(c, '(' | ')' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped.push(')');
+
+        self.space();
+        self.folding().write_str(&escaped)?;
+        Ok(())
+    }
+
+    /// Write an already folded value verbatim.
+    ///
+    /// This is meant for values which must be preserved byte for byte,
+    /// such as headers covered by a DKIM signature, while continuing to
+    /// write to the same header with this `EmailWriter`. Pending spaces
+    /// are written first. The line breaks of `value` are kept as they are,
+    /// and the line length is updated from the last line of `value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidLineBreak`] if `value` contains a line break
+    /// which isn't a fold: a line break which isn't the
+    /// [line ending](EmailWriter::line_ending) of the writer, like a lone `CR`
+    /// or `LF`, or a line break which isn't followed by a space or a tab. Returns
+    /// [`Error::LineTooLong`] if a line of `value`, including what has
+    /// already been written to the current line, is longer than
+    /// [`EmailWriter::max_line_len`]. In [strict mode](EmailWriter::set_strict),
+    /// also returns [`Error::InvalidChar`] if `value` contains a control char
+    /// other than tab. Nothing gets written on error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # fn main() -> Result<(), email_encoding::headers::writer::Error> {
+    /// let mut output = String::new();
+    /// {
+    ///     let mut writer = EmailWriter::new(&mut output, 0, 0, false);
+    ///     writer.start_header("Subject")?;
+    ///     writer.write_prefolded("Hello,\r\n\tworld!")?;
+    ///     writer.space();
+    ///     email_encoding::headers::rfc2047::encode("Adrián", &mut writer)?;
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     "Subject: Hello,\r\n\tworld! =?utf-8?b?QWRyacOhbg==?="
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_prefolded(&mut self, value: &str) -> Result<(), Error> {
+        let bytes = value.as_bytes();
+        let crlf = self.line_ending == LineEnding::CrLf;
+        for (position, &b) in bytes.iter().enumerate() {
+            let is_fold = match b {
+                b'\r' => crlf && bytes.get(position + 1) == Some(&b'\n'),
+                b'\n' => {
+                    (crlf == (position > 0 && bytes[position - 1] == b'\r'))
+                        && matches!(bytes.get(position + 1), Some(b' ' | b'\t'))
+                }
+                _ => true,
+            };
+            if !is_fold {
+                return Err(Error::InvalidLineBreak { position });
+            }
+        }
+        if self.strict {
+            if let Some((position, c)) = value
+                .char_indices()
+                .find(|&(_, c)| c.is_ascii_control() && !matches!(c, '\t' | '\r' | '\n'))
+            {
+                return Err(Error::InvalidChar { position, c });
+            }
+        }
+
+        let mut line_len = self.projected_line_len();
+        let mut position = 0;
+        for line in value.split('\n') {
+            line_len += self
+                .line_width
+                .measure(line.strip_suffix('\r').unwrap_or(line));
+            if line_len > self.max_line_len {
+                return Err(Error::LineTooLong { position });
+            }
+
+            position += line.len() + "\n".len();
+            line_len = 0;
+        }
+
+        if value.is_empty() {
+            return Ok(());
+        }
+
+        self.write_spaces()?;
+        self.writer.write_str(value)?;
+        match value.rfind('\n') {
+            Some(i) => {
+                self.line_len = self.line_width.measure(&value[i + 1..]);
+                self.folded = true;
+            }
+            None => {
+                self.line_len += self.line_width.measure(value);
+                self.folded = false;
+            }
+        }
+        self.can_go_to_new_line_now = true;
+        self.after_breakpoint = false;
 
         Ok(())
     }
@@ -62,12 +763,12 @@ impl<'a> EmailWriter<'a> {
         self.spaces >= 1
     }
 
-    /// Get the length in bytes of the last line written to the inner writer.
+    /// Get the length of the last line written to the inner writer.
     pub fn line_len(&self) -> usize {
         self.line_len
     }
 
-    /// Get the length in bytes of the last line written to the inner writer
+    /// Get the length of the last line written to the inner writer
     /// plus the spaces which might be written to in on the next write call.
     pub fn projected_line_len(&self) -> usize {
         self.line_len + self.spaces
@@ -76,286 +777,2283 @@ impl<'a> EmailWriter<'a> {
     /// Get a [`Write`]r which automatically line folds text written to it.
     ///
     /// [`Write`]: std::fmt::Write
-    pub fn folding<'b>(&'b mut self) -> FoldingEmailWriter<'a, 'b> {
+    pub fn folding<'b>(&'b mut self) -> FoldingEmailWriter<'a, 'b, W> {
         FoldingEmailWriter { writer: self }
     }
 
+    /// Handle the pending spaces and return the final state of the writer.
+    ///
+    /// Pending spaces are handled according to [`EmailWriter::trailing_spaces`].
+    /// Dropping the `EmailWriter` handles them too, but ignores
+    /// errors and doesn't say where the header ended. The returned
+    /// [`EndState`] can be used to construct the `EmailWriter` continuing
+    /// from where this one stopped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("Subject: ");
+    /// let line_len = output.len();
+    ///
+    /// let state = {
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     email_encoding::headers::quoted_string::encode("Hello ", &mut writer)?;
+    ///     writer.finish()?
+    /// };
+    /// assert_eq!(state.line_len, "Subject: \"Hello \"".len());
+    /// assert!(!state.trailing_whitespace);
+    ///
+    /// {
+    ///     let mut writer = EmailWriter::new(
+    ///         &mut output,
+    ///         state.line_len,
+    ///         0,
+    ///         state.can_go_to_new_line_now,
+    ///     );
+    ///     email_encoding::headers::rfc2047::encode(" Adrián", &mut writer)?;
+    /// }
+    /// assert_eq!(output, "Subject: \"Hello \"=?utf-8?b?IEFkcmnDoW4=?=");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn finish(mut self) -> Result<EndState, fmt::Error> {
+        let trailing_whitespace = self.spaces > 0 && self.trailing_spaces == TrailingSpaces::Flush;
+        let spaces = match self.trailing_spaces {
+            TrailingSpaces::Keep => self.spaces,
+            TrailingSpaces::Flush | TrailingSpaces::Trim => 0,
+        };
+        self.end()?;
+
+        Ok(EndState {
+            line_len: self.line_len,
+            trailing_whitespace,
+            spaces,
+            can_go_to_new_line_now: self.can_go_to_new_line_now,
+        })
+    }
+
+    fn end(&mut self) -> fmt::Result {
+        match self.trailing_spaces {
+            TrailingSpaces::Flush => self.write_spaces(),
+            TrailingSpaces::Trim | TrailingSpaces::Keep => {
+                self.spaces = 0;
+                Ok(())
+            }
+        }
+    }
+
+    /// Call `f` with automatic folding disabled.
+    ///
+    /// Text written to [`FoldingEmailWriter`]s from within `f` doesn't
+    /// get folded, which is useful for constructs which must never be split,
+    /// such as msg-ids or URIs. Folding is restored once `f` returns.
+    /// Explicit calls to [`EmailWriter::new_line`] aren't affected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # use std::fmt::Write;
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("List-Unsubscribe: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     writer.write_str("<https://example.com/unsubscribe?")?;
+    ///     writer.no_fold(|w| {
+    ///         w.folding()
+    ///             .write_str("list=announcements id=0123456789abcdef0123456789abcdef>")
+    ///     })?;
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     "List-Unsubscribe: <https://example.com/unsubscribe?list=announcements id=0123456789abcdef0123456789abcdef>"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn no_fold<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let no_fold = self.no_fold;
+        self.no_fold = true;
+        let result = f(self);
+        self.no_fold = no_fold;
+        result
+    }
+
+    /// Call `f` with the maximum line length reduced by `reserved`.
+    ///
+    /// This keeps room for what is going to be written after `f` returns,
+    /// such as closing delimiters. Calls can be nested, each one further
+    /// reducing the maximum line length. It is restored once `f` returns.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # use std::fmt::Write;
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("Subject: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+    ///     writer.write_char('(')?;
+    ///     writer.reserve_line_len(")".len(), |w| {
+    ///         assert_eq!(w.max_line_len(), 75);
+    ///         w.folding().write_str(
+    ///             "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod",
+    ///         )
+    ///     })?;
+    ///     writer.write_char(')')?;
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     concat!(
+    ///         "Subject: (Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do\r\n",
+    ///         " eiusmod)"
+    ///     )
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reserve_line_len<R>(&mut self, reserved: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        let max_line_len = self.max_line_len;
+        self.max_line_len = max_line_len.saturating_sub(reserved);
+        let result = f(self);
+        self.max_line_len = max_line_len;
+        result
+    }
+
+    /// Construct a new `EmailWriter` writing to `writer` which
+    /// starts from the same state as `self`.
+    pub(super) fn scratch<'b>(&self, writer: &'b mut dyn Write) -> EmailWriter<'b> {
+        EmailWriter {
+            writer,
+            line_len: self.line_len,
+            spaces: self.spaces,
+            can_go_to_new_line_now: self.can_go_to_new_line_now,
+            max_line_len: self.max_line_len,
+            folding_whitespace: self.folding_whitespace,
+            folded: self.folded,
+            trailing_spaces: self.trailing_spaces,
+            breakpoints: self.breakpoints,
+            after_breakpoint: self.after_breakpoint,
+            no_fold: self.no_fold,
+            line_width: self.line_width,
+            reject_stray_line_breaks: self.reject_stray_line_breaks,
+            found_stray_line_break: false,
+            strict: self.strict,
+            violation: None,
+            line_ending: self.line_ending,
+            allow_utf8: self.allow_utf8,
+        }
+    }
+
     fn write_spaces(&mut self) -> fmt::Result {
         while self.spaces > 0 {
-            self.writer.write_char(' ')?;
+            let c = if self.folded {
+                self.folding_whitespace.as_char()
+            } else {
+                ' '
+            };
+            self.writer.write_char(c)?;
             self.line_len += 1;
             self.spaces -= 1;
+            self.folded = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, W: ?Sized + Truncate> EmailWriter<'a, W> {
+    /// Take a snapshot of the position and of the state of the writer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use email_encoding::headers::writer::EmailWriter;
+    /// # use std::fmt::Write;
+    /// # fn main() -> std::fmt::Result {
+    /// let mut output = String::from("Subject: ");
+    /// {
+    ///     let line_len = output.len();
+    ///     let mut writer = EmailWriter::new_generic(&mut output, line_len, 0, false);
+    ///
+    ///     let checkpoint = writer.checkpoint();
+    ///     writer.folding().write_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod")?;
+    ///     if writer.line_len() < checkpoint.line_len() {
+    ///         // Got folded, write something shorter instead
+    ///         writer.rollback(checkpoint);
+    ///         writer.write_str("Lorem ipsum")?;
+    ///     }
+    /// }
+    /// assert_eq!(output, "Subject: Lorem ipsum");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.writer.position(),
+            line_len: self.line_len,
+            spaces: self.spaces,
+            can_go_to_new_line_now: self.can_go_to_new_line_now,
+            folded: self.folded,
+            after_breakpoint: self.after_breakpoint,
+        }
+    }
+
+    /// Go back to a snapshot taken via [`EmailWriter::checkpoint`],
+    /// discarding everything written since then.
+    ///
+    /// Rolling back to a checkpoint taken from a different writer,
+    /// or after having already rolled back further, gives a meaningless
+    /// state.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.writer.truncate(checkpoint.position);
+        self.line_len = checkpoint.line_len;
+        self.spaces = checkpoint.spaces;
+        self.can_go_to_new_line_now = checkpoint.can_go_to_new_line_now;
+        self.folded = checkpoint.folded;
+        self.after_breakpoint = checkpoint.after_breakpoint;
+    }
+}
+
+impl<'a, W: ?Sized + Write> Write for EmailWriter<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let s_after = s.trim_end_matches(' ');
+        self.check_write(s_after)?;
+        self.write_spaces()?;
+
+        self.spaces += s.len() - s_after.len();
+
+        if !s_after.is_empty() {
+            self.writer.write_str(s_after)?;
+            self.line_len += self.line_width.measure(s_after);
+            self.can_go_to_new_line_now = true;
+            self.folded = false;
+            self.after_breakpoint = false;
+        }
+
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.write_char_inner(c, true)
+    }
+}
+
+impl<'a, W: ?Sized + Write> EmailWriter<'a, W> {
+    /// Write `c`, buffering it as a pending space if it's a space
+    /// and `buffer_space` is `true`.
+    fn write_char_inner(&mut self, c: char, buffer_space: bool) -> fmt::Result {
+        if c == ' ' && buffer_space {
+            self.spaces += 1;
+        } else {
+            self.check_write(c.encode_utf8(&mut [0u8; 4]))?;
+            self.write_spaces()?;
+            self.can_go_to_new_line_now = true;
+            self.folded = false;
+            self.after_breakpoint = false;
+
+            self.writer.write_char(c)?;
+            self.line_len += self.line_width.measure(c.encode_utf8(&mut [0u8; 4]));
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: ?Sized + Write> fmt::Debug for EmailWriter<'_, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmailWriter")
+            .field("line_len", &self.line_len)
+            .field("spaces", &self.spaces)
+            .field("can_go_to_new_line_now", &self.can_go_to_new_line_now)
+            .field("max_line_len", &self.max_line_len)
+            .field("folding_whitespace", &self.folding_whitespace)
+            .field("folded", &self.folded)
+            .field("trailing_spaces", &self.trailing_spaces)
+            .field("breakpoints", &self.breakpoints)
+            .field("after_breakpoint", &self.after_breakpoint)
+            .field("no_fold", &self.no_fold)
+            .field("line_width", &self.line_width)
+            .field("reject_stray_line_breaks", &self.reject_stray_line_breaks)
+            .field("found_stray_line_break", &self.found_stray_line_break)
+            .field("strict", &self.strict)
+            .field("violation", &self.violation)
+            .field("line_ending", &self.line_ending)
+            .field("allow_utf8", &self.allow_utf8)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, W: ?Sized + Write> Drop for EmailWriter<'a, W> {
+    fn drop(&mut self) {
+        let _ = self.end();
+    }
+}
+
+/// [`Write`]r which can go back to a previous position.
+///
+/// Required by [`EmailWriter::checkpoint`] and [`EmailWriter::rollback`].
+///
+/// [`Write`]: std::fmt::Write
+pub trait Truncate: Write {
+    /// Get the current position, in bytes.
+    fn position(&self) -> usize;
+
+    /// Discard everything written after `position`.
+    fn truncate(&mut self, position: usize);
+}
+
+impl Truncate for String {
+    fn position(&self) -> usize {
+        self.len()
+    }
+
+    fn truncate(&mut self, position: usize) {
+        String::truncate(self, position);
+    }
+}
+
+/// Snapshot of an [`EmailWriter`], taken via [`EmailWriter::checkpoint`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    position: usize,
+    line_len: usize,
+    spaces: usize,
+    can_go_to_new_line_now: bool,
+    folded: bool,
+    after_breakpoint: bool,
+}
+
+impl Checkpoint {
+    /// Get the length of the last line at the time of the snapshot.
+    pub fn line_len(&self) -> usize {
+        self.line_len
+    }
+}
+
+/// The state of an [`EmailWriter`] after it has been [finished](EmailWriter::finish).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EndState {
+    /// The length of the last line.
+    pub line_len: usize,
+    /// Whether the last line ends with spaces.
+    pub trailing_whitespace: bool,
+    /// The number of spaces which haven't been written,
+    /// when using [`TrailingSpaces::Keep`].
+    pub spaces: usize,
+    /// Whether the last line could be wrapped now.
+    pub can_go_to_new_line_now: bool,
+}
+
+/// What happens to the spaces still pending when an [`EmailWriter`] ends.
+///
+/// Headers ending with whitespace are valid, but are flagged by some
+/// strict validators.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum TrailingSpaces {
+    /// Write them
+    #[default]
+    Flush,
+    /// Discard them
+    Trim,
+    /// Don't write them, but report them through [`EndState::spaces`]
+    /// so that they can be passed to the next `EmailWriter`.
+    ///
+    /// They are discarded if the `EmailWriter` gets dropped
+    /// instead of being [finished](EmailWriter::finish).
+    Keep,
+}
+
+/// The line terminator written by an [`EmailWriter`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    /// `\r\n`, as required by [RFC 5322]
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-2.1
+    #[default]
+    CrLf,
+    /// `\n`, for local storage or APIs normalizing line endings
+    Lf,
+}
+
+impl LineEnding {
+    /// Get the line terminator as a string.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::CrLf => "\r\n",
+            Self::Lf => "\n",
+        }
+    }
+}
+
+/// How the length of lines is measured.
+///
+/// Folds never split chars, regardless of how lines are measured.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum LineWidth {
+    /// Count bytes, as required by [RFC 5322]
+    ///
+    /// [RFC 5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-2.1.1
+    #[default]
+    Bytes,
+    /// Count chars, which gives lines of a more even visual width
+    /// when writing raw UTF-8 headers, as allowed by [RFC 6532]
+    ///
+    /// [RFC 6532]: https://datatracker.ietf.org/doc/html/rfc6532#section-3.4
+    Chars,
+}
+
+impl LineWidth {
+    fn measure(self, s: &str) -> usize {
+        match self {
+            Self::Bytes => s.len(),
+            Self::Chars => s.chars().count(),
+        }
+    }
+}
+
+/// The whitespace char written at the start of folded lines.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum FoldingWhitespace {
+    /// A space (`' '`)
+    #[default]
+    Space,
+    /// A horizontal tab (`'\t'`)
+    Tab,
+}
+
+impl FoldingWhitespace {
+    fn as_char(self) -> char {
+        match self {
+            Self::Space => ' ',
+            Self::Tab => '\t',
+        }
+    }
+}
+
+/// Why a write was rejected, returned by [`EmailWriter::violation`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Violation {
+    /// A CR or LF char, which must only be written by [`EmailWriter::new_line`]
+    LineBreak,
+    /// A control char other than tab
+    ControlChar(char),
+    /// The line would get longer than [`EmailWriter::max_line_len`]
+    LineTooLong,
+}
+
+/// Wrapper around [`Write`] that remembers the length of the
+/// last line and automatically line folds text written to it.
+///
+/// [`Write`]: std::fmt::Write
+pub struct FoldingEmailWriter<'a, 'b, W: ?Sized + Write = dyn Write + 'a> {
+    writer: &'b mut EmailWriter<'a, W>,
+}
+
+impl<W: ?Sized + Write> fmt::Debug for FoldingEmailWriter<'_, '_, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FoldingEmailWriter")
+            .field("writer", &self.writer)
+            .finish()
+    }
+}
+
+impl<'a, 'b, W: ?Sized + Write> Write for FoldingEmailWriter<'a, 'b, W> {
+    fn write_str(&mut self, mut s: &str) -> fmt::Result {
+        while !s.is_empty() {
+            if s.starts_with(' ') {
+                self.writer.space();
+                s = &s[1..];
+                continue;
+            }
+
+            let breakpoints = self.writer.breakpoints;
+            let (start, end) = s
+                .char_indices()
+                .find_map(|(i, c)| {
+                    if c == ' ' {
+                        Some(i)
+                    } else if breakpoints.contains(&c) {
+                        Some(i + c.len_utf8())
+                    } else {
+                        None
+                    }
+                })
+                .map_or((s, ""), |i| s.split_at(i));
+
+            if self.writer.can_go_to_new_line_now
+                && !self.writer.no_fold
+                && (self.writer.spaces >= 1 || self.writer.after_breakpoint)
+                && (self.writer.projected_line_len() + self.writer.line_width.measure(start))
+                    > self.writer.max_line_len
+            {
+                self.writer.new_line()?;
+                if !self.writer.has_spaces() {
+                    self.writer.space();
+                }
+            }
+
+            self.writer.write_str(start)?;
+            self.writer.after_breakpoint = start.ends_with(breakpoints);
+            s = end;
+        }
+
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        if c == ' ' {
+            self.writer.spaces += 1;
+        } else {
+            self.write_str(c.encode_utf8(&mut [0u8; 4]))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder for [`EmailWriter`].
+///
+/// Constructed via [`EmailWriter::builder`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EmailWriterBuilder {
+    line_len: usize,
+    spaces: usize,
+    can_go_to_new_line_now: bool,
+    max_line_len: usize,
+    folding_whitespace: FoldingWhitespace,
+    trailing_spaces: TrailingSpaces,
+    breakpoints: &'static [char],
+    line_width: LineWidth,
+    reject_stray_line_breaks: bool,
+    strict: bool,
+    line_ending: LineEnding,
+    allow_utf8: bool,
+}
+
+impl EmailWriterBuilder {
+    /// Construct a new `EmailWriterBuilder`, starting from an empty line.
+    pub fn new() -> Self {
+        Self {
+            line_len: 0,
+            spaces: 0,
+            can_go_to_new_line_now: false,
+            max_line_len: MAX_LINE_LEN,
+            folding_whitespace: FoldingWhitespace::default(),
+            trailing_spaces: TrailingSpaces::default(),
+            breakpoints: &[],
+            line_width: LineWidth::default(),
+            reject_stray_line_breaks: false,
+            strict: false,
+            line_ending: LineEnding::default(),
+            allow_utf8: false,
+        }
+    }
+
+    /// Set the length of the last line in the writer.
+    ///
+    /// Defaults to `0`.
+    pub fn line_len(mut self, line_len: usize) -> Self {
+        self.line_len = line_len;
+        self
+    }
+
+    /// Set the number of spaces that must be written before the next write.
+    ///
+    /// Defaults to `0`.
+    pub fn spaces(mut self, spaces: usize) -> Self {
+        self.spaces = spaces;
+        self
+    }
+
+    /// Set whether the current line can be wrapped now or not.
+    ///
+    /// Defaults to `false`.
+    pub fn can_go_to_new_line_now(mut self, can_go_to_new_line_now: bool) -> Self {
+        self.can_go_to_new_line_now = can_go_to_new_line_now;
+        self
+    }
+
+    /// Set the maximum length in bytes a line should have.
+    ///
+    /// Defaults to [`MAX_LINE_LEN`]. See [`EmailWriter::set_max_line_len`].
+    pub fn max_line_len(mut self, max_line_len: usize) -> Self {
+        self.max_line_len = max_line_len;
+        self
+    }
+
+    /// Set the whitespace char which starts folded lines.
+    ///
+    /// Defaults to [`FoldingWhitespace::Space`].
+    pub fn folding_whitespace(mut self, folding_whitespace: FoldingWhitespace) -> Self {
+        self.folding_whitespace = folding_whitespace;
+        self
+    }
+
+    /// Set what happens to the spaces pending when the writer ends.
+    ///
+    /// Defaults to [`TrailingSpaces::Flush`].
+    pub fn trailing_spaces(mut self, trailing_spaces: TrailingSpaces) -> Self {
+        self.trailing_spaces = trailing_spaces;
+        self
+    }
+
+    /// Set the chars after which [`FoldingEmailWriter`] may fold, besides spaces.
+    ///
+    /// Defaults to none. See [`EmailWriter::set_breakpoints`].
+    pub fn breakpoints(mut self, breakpoints: &'static [char]) -> Self {
+        self.breakpoints = breakpoints;
+        self
+    }
+
+    /// Set how the length of lines is measured.
+    ///
+    /// Defaults to [`LineWidth::Bytes`].
+    pub fn line_width(mut self, line_width: LineWidth) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    /// Set whether writing CR or LF chars fails.
+    ///
+    /// Defaults to `false`. See [`EmailWriter::set_reject_stray_line_breaks`].
+    pub fn reject_stray_line_breaks(mut self, reject_stray_line_breaks: bool) -> Self {
+        self.reject_stray_line_breaks = reject_stray_line_breaks;
+        self
+    }
+
+    /// Set whether the writer is in strict mode.
+    ///
+    /// Defaults to `false`. See [`EmailWriter::set_strict`].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the line terminator written by [`EmailWriter::new_line`].
+    ///
+    /// Defaults to [`LineEnding::CrLf`].
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Set whether encoders may write non-ASCII chars as raw UTF-8.
+    ///
+    /// Defaults to `false`. See [`EmailWriter::set_allow_utf8`].
+    pub fn allow_utf8(mut self, allow_utf8: bool) -> Self {
+        self.allow_utf8 = allow_utf8;
+        self
+    }
+
+    /// Construct the [`EmailWriter`] writing to `writer`.
+    pub fn build(self, writer: &mut dyn Write) -> EmailWriter<'_> {
+        self.build_generic(writer)
+    }
+
+    /// Construct the [`EmailWriter`] writing to `writer`,
+    /// statically dispatching calls to it.
+    ///
+    /// See [`EmailWriter::new_generic`].
+    pub fn build_generic<W: ?Sized + Write>(self, writer: &mut W) -> EmailWriter<'_, W> {
+        let mut w = EmailWriter::new_generic(
+            writer,
+            self.line_len,
+            self.spaces,
+            self.can_go_to_new_line_now,
+        );
+        w.set_max_line_len(self.max_line_len);
+        w.set_folding_whitespace(self.folding_whitespace);
+        w.set_trailing_spaces(self.trailing_spaces);
+        w.set_breakpoints(self.breakpoints);
+        w.set_line_width(self.line_width);
+        w.set_reject_stray_line_breaks(self.reject_stray_line_breaks);
+        w.set_strict(self.strict);
+        w.set_line_ending(self.line_ending);
+        w.set_allow_utf8(self.allow_utf8);
+        w
+    }
+}
+
+impl Default for EmailWriterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes a sequence of headers, followed by the blank line
+/// separating them from the body.
+///
+/// Each header gets its own [`EmailWriter`], starting right after the
+/// `Name: ` prefix, and gets terminated by the line ending
+/// configured via [`EmailWriterBuilder::line_ending`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::HeaderMapWriter;
+/// # use std::fmt::Write;
+/// # fn main() -> Result<(), email_encoding::headers::writer::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut headers = HeaderMapWriter::new(&mut output);
+///     headers.header("From", |w| {
+///         email_encoding::headers::quoted_string::encode("John Smith", w)?;
+///         w.write_str(" <john@example.com>")
+///     })?;
+///     headers.header("Subject", |w| email_encoding::headers::rfc2047::encode("Adrián", w))?;
+///     headers.finish()?;
+/// }
+/// assert_eq!(
+///     output,
+///     concat!(
+///         "From: \"John Smith\" <john@example.com>\r\n",
+///         "Subject: =?utf-8?b?QWRyacOhbg==?=\r\n",
+///         "\r\n"
+///     )
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct HeaderMapWriter<'a> {
+    writer: &'a mut dyn Write,
+    builder: EmailWriterBuilder,
+}
+
+impl<'a> HeaderMapWriter<'a> {
+    /// Construct a new `HeaderMapWriter` writing to `writer`.
+    pub fn new(writer: &'a mut dyn Write) -> Self {
+        Self::with_builder(writer, EmailWriterBuilder::new())
+    }
+
+    /// Construct a new `HeaderMapWriter` writing to `writer`,
+    /// configuring the [`EmailWriter`] of each header via `builder`.
+    ///
+    /// The line length and the spaces set on `builder` are ignored,
+    /// since each header starts on a new line.
+    pub fn with_builder(writer: &'a mut dyn Write, builder: EmailWriterBuilder) -> Self {
+        Self { writer, builder }
+    }
+
+    /// Write the header called `name`, letting `f` write its value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyFieldName`] or [`Error::InvalidFieldName`] if `name`
+    /// isn't a valid field name, and [`Error::Fmt`] if `f` fails.
+    pub fn header<F>(&mut self, name: &str, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut EmailWriter<'_>) -> fmt::Result,
+    {
+        let mut w = self
+            .builder
+            .line_len(0)
+            .spaces(0)
+            .can_go_to_new_line_now(false)
+            .build(&mut *self.writer);
+        w.start_header(name)?;
+        f(&mut w)?;
+        w.finish()?;
+
+        self.writer.write_str(self.builder.line_ending.as_str())?;
+        Ok(())
+    }
+
+    /// Write the header called `name`, letting `value` encode itself.
+    ///
+    /// See [`HeaderMapWriter::header`].
+    pub fn header_value<T>(&mut self, name: &str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + HeaderEncode,
+    {
+        self.header(name, |w| value.encode(w))
+    }
+
+    /// Write the headers yielded by `headers`.
+    ///
+    /// Stops at the first error. See [`HeaderMapWriter::header`].
+    pub fn headers<'n, I, F>(&mut self, headers: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (&'n str, F)>,
+        F: FnOnce(&mut EmailWriter<'_>) -> fmt::Result,
+    {
+        for (name, f) in headers {
+            self.header(name, f)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the blank line separating the headers from the body.
+    pub fn finish(self) -> fmt::Result {
+        self.writer.write_str(self.builder.line_ending.as_str())
+    }
+}
+
+/// A structured header value which knows how to encode itself.
+///
+/// Implementing this trait allows custom header types to be written
+/// via [`HeaderMapWriter::header_value`], or any other API accepting
+/// them, while folding the same way the encoders of this crate do.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::{EmailWriter, HeaderEncode, HeaderMapWriter};
+/// # use std::fmt::{self, Write};
+/// struct Priority(u8);
+///
+/// impl HeaderEncode for Priority {
+///     fn encode(&self, w: &mut EmailWriter<'_>) -> fmt::Result {
+///         write!(w, "{}", self.0)?;
+///         w.space();
+///         w.write_str("(Highest)")
+///     }
+/// }
+///
+/// # fn main() -> Result<(), email_encoding::headers::writer::Error> {
+/// let mut output = String::new();
+/// {
+///     let mut headers = HeaderMapWriter::new(&mut output);
+///     headers.header_value("X-Priority", &Priority(1))?;
+///     headers.finish()?;
+/// }
+/// assert_eq!(output, "X-Priority: 1 (Highest)\r\n\r\n");
+/// # Ok(())
+/// # }
+/// ```
+pub trait HeaderEncode {
+    /// Encode the header value, starting right after the `Name: ` prefix.
+    fn encode(&self, w: &mut EmailWriter<'_>) -> fmt::Result;
+}
+
+impl<T: ?Sized + HeaderEncode> HeaderEncode for &T {
+    fn encode(&self, w: &mut EmailWriter<'_>) -> fmt::Result {
+        (**self).encode(w)
+    }
+}
+
+impl<T: ?Sized + HeaderEncode> HeaderEncode for Box<T> {
+    fn encode(&self, w: &mut EmailWriter<'_>) -> fmt::Result {
+        (**self).encode(w)
+    }
+}
+
+impl fmt::Debug for HeaderMapWriter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeaderMapWriter")
+            .field("builder", &self.builder)
+            .finish_non_exhaustive()
+    }
+}
+
+fn validate_chars(s: &str, is_valid: impl Fn(char) -> bool) -> Result<(), Error> {
+    if s.is_empty() {
+        return Err(Error::Empty);
+    }
+    if let Some((position, c)) = s.char_indices().find(|&(_, c)| !is_valid(c)) {
+        return Err(Error::InvalidChar { position, c });
+    }
+
+    Ok(())
+}
+
+/// Error returned by [`EmailWriter::start_header`] and the
+/// [`EmailWriter::write_atom`] family of methods.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Writing to the inner writer failed
+    Fmt(fmt::Error),
+    /// The header field name is empty
+    EmptyFieldName,
+    /// The header field name contains a char which isn't allowed
+    InvalidFieldName {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The value is empty
+    Empty,
+    /// The value contains a char which isn't allowed
+    InvalidChar {
+        /// Byte offset of the char
+        position: usize,
+        /// The invalid char
+        c: char,
+    },
+    /// The prefolded value contains a line break which isn't a fold
+    InvalidLineBreak {
+        /// Byte offset of the line break
+        position: usize,
+    },
+    /// A line of the prefolded value is too long
+    LineTooLong {
+        /// Byte offset of the start of the line
+        position: usize,
+    },
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fmt(err) => err.fmt(f),
+            Self::EmptyFieldName => f.write_str("header field name is empty"),
+            Self::InvalidFieldName { position, c } => {
+                write!(
+                    f,
+                    "invalid char {:?} in header field name at byte {}",
+                    c, position
+                )
+            }
+            Self::Empty => f.write_str("value is empty"),
+            Self::InvalidChar { position, c } => {
+                write!(f, "invalid char {:?} at byte {}", c, position)
+            }
+            Self::InvalidLineBreak { position } => {
+                write!(f, "line break at byte {} isn't a fold", position)
+            }
+            Self::LineTooLong { position } => {
+                write!(f, "line starting at byte {} is too long", position)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl crate::error::StdError for Error {}
+
+/// [`Write`]r adapter writing to a [`BufMut`].
+///
+/// Allows encoding headers straight into a `BytesMut`, instead of going
+/// through a `String` first.
+///
+/// # Examples
+///
+/// ```rust
+/// # use bytes::BytesMut;
+/// # use email_encoding::headers::writer::{BufMutWriter, EmailWriter};
+/// # fn main() -> std::fmt::Result {
+/// let mut buf = BytesMut::new();
+/// {
+///     let mut adapter = BufMutWriter::new(&mut buf);
+///     let mut writer = EmailWriter::new(&mut adapter, 0, 0, false);
+///     writer.start_header("Subject").map_err(|_| std::fmt::Error)?;
+///     email_encoding::headers::rfc2047::encode("Adrián", &mut writer)?;
+/// }
+/// assert_eq!(&buf[..], b"Subject: =?utf-8?b?QWRyacOhbg==?=");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Write`]: std::fmt::Write
+/// [`BufMut`]: bytes::BufMut
+#[cfg(feature = "bytes")]
+#[derive(Debug)]
+pub struct BufMutWriter<B> {
+    buf: B,
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::BufMut> BufMutWriter<B> {
+    /// Construct a new `BufMutWriter` writing to `buf`.
+    pub fn new(buf: B) -> Self {
+        Self { buf }
+    }
+
+    /// Get a reference to the inner buffer.
+    pub fn get_ref(&self) -> &B {
+        &self.buf
+    }
+
+    /// Get a mutable reference to the inner buffer.
+    pub fn get_mut(&mut self) -> &mut B {
+        &mut self.buf
+    }
+
+    /// Get back the inner buffer.
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::BufMut> Write for BufMutWriter<B> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // `put_slice` panics instead of failing if `buf` is full
+        if self.buf.remaining_mut() < s.len() {
+            return Err(fmt::Error);
+        }
+
+        self.buf.put_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Truncate for BufMutWriter<bytes::BytesMut> {
+    fn position(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn truncate(&mut self, position: usize) {
+        self.buf.truncate(position);
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Truncate for BufMutWriter<&mut bytes::BytesMut> {
+    fn position(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn truncate(&mut self, position: usize) {
+        self.buf.truncate(position);
+    }
+}
+
+/// [`Write`]r which discards everything written to it, while
+/// measuring it.
+///
+/// Wrap it into an [`EmailWriter`] in order to find out how large an encoded
+/// header is going to be, before writing it to the real buffer.
+///
+/// # Examples
+///
+/// ```rust
+/// # use email_encoding::headers::writer::{CountingWriter, EmailWriter};
+/// # fn main() -> std::fmt::Result {
+/// let input = "Adrián";
+///
+/// let mut counter = CountingWriter::new("Subject: ".len());
+/// {
+///     let mut writer = EmailWriter::new(&mut counter, "Subject: ".len(), 0, false);
+///     email_encoding::headers::rfc2047::encode(input, &mut writer)?;
+/// }
+/// assert_eq!(counter.len(), "=?utf-8?b?QWRyacOhbg==?=".len());
+/// assert_eq!(counter.folds(), 0);
+/// assert_eq!(counter.longest_line_len(), "Subject: =?utf-8?b?QWRyacOhbg==?=".len());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Write`]: std::fmt::Write
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CountingWriter {
+    len: usize,
+    folds: usize,
+    line_len: usize,
+    longest_line_len: usize,
+    pending_cr: bool,
+}
+
+impl CountingWriter {
+    /// Construct a new `CountingWriter`.
+    ///
+    /// `line_len` is the length of the current line before anything
+    /// gets written, just like in [`EmailWriter::new`].
+    pub fn new(line_len: usize) -> Self {
+        Self {
+            len: 0,
+            folds: 0,
+            line_len,
+            longest_line_len: line_len,
+            pending_cr: false,
+        }
+    }
+
+    /// Get the number of bytes written, including line breaks.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get whether nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the number of line breaks written, either `\r\n` or `\n`.
+    pub fn folds(&self) -> usize {
+        self.folds
+    }
+
+    /// Get the length in bytes of the last line, excluding the line break.
+    pub fn line_len(&self) -> usize {
+        self.line_len + usize::from(self.pending_cr)
+    }
+
+    /// Get the length in bytes of the longest line, excluding line breaks.
+    pub fn longest_line_len(&self) -> usize {
+        self.longest_line_len.max(self.line_len())
+    }
+}
+
+impl Write for CountingWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+
+        for b in s.bytes() {
+            match (self.pending_cr, b) {
+                (_, b'\n') => {
+                    self.longest_line_len = self.longest_line_len.max(self.line_len);
+                    self.line_len = 0;
+                    self.folds += 1;
+                    self.pending_cr = false;
+                }
+                (pending_cr, b'\r') => {
+                    self.line_len += usize::from(pending_cr);
+                    self.pending_cr = true;
+                }
+                (pending_cr, _) => {
+                    self.line_len += 1 + usize::from(pending_cr);
+                    self.pending_cr = false;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, format, string::ToString};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn wrap_immediate() {
+        let mut s =
+            "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            for _ in 0..16 {
+                w.folding().write_str("0123456789").unwrap();
+            }
+        }
+
+        assert_eq!(
+            s,
+            "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA0123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789",
+        );
+    }
+
+    #[test]
+    fn wrap_keeping_final_whitespace() {
+        let mut s = "Subject: AAAAAAAAAAAAAA".to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
+            w.folding().write_str("12345 ").unwrap();
+            w.new_line().unwrap();
+            w.folding().write_str("12345").unwrap();
+        }
+
+        assert_eq!(s, concat!("Subject: AAAAAAAAAAAAAA 12345\r\n", " 12345"));
+    }
+
+    #[test]
+    fn catch_space() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
+            w.folding().write_str("BBB ").unwrap();
+            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
+                " CCCCCCCCCCCCC"
+            )
+        );
+    }
+
+    #[test]
+    fn catch_spaces() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
+            w.folding().write_str("BBB   ").unwrap();
+            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
+                "   CCCCCCCCCCCCC"
+            )
+        );
+    }
+
+    #[test]
+    fn explicit_space() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
+            w.folding().write_str("BBB").unwrap();
+            w.space();
+            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
+                " CCCCCCCCCCCCC"
+            )
+        );
+    }
+
+    #[test]
+    fn explicit_spaces() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
+            w.folding().write_str("BBB").unwrap();
+            w.space();
+            w.write_char(' ').unwrap();
+            w.space();
+            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
+                "   CCCCCCCCCCCCC"
+            )
+        );
+    }
+
+    #[test]
+    fn optional_breakpoint() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+            .to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.space();
+            w.folding().write_str("BBBBBBBBBB").unwrap();
+            w.space();
+            w.folding().write_str("CCCCCCCCCC").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\r\n",
+                " BBBBBBBBBB CCCCCCCCCC",
+            )
+        );
+    }
+
+    #[test]
+    fn custom_max_line_len() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAA".to_owned();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
+            w.set_max_line_len(40);
+            w.folding().write_str("BBBBBBBBBB CCCCCCCCCC").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAA BBBBBBBBBB\r\n",
+                " CCCCCCCCCC"
+            )
+        );
+    }
+
+    #[test]
+    fn double_spaces_issue_949() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.folding().write_str("BBBBBBBBBBBBB ").unwrap();
+            crate::headers::rfc2047::encode("sélection", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBBBBBBBBBBBB\r\n",
+                " =?utf-8?b?c8OpbGVjdGlvbg==?=",
+            )
+        );
+    }
+
+    #[test]
+    fn double_spaces_issue_949_no_space() {
+        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
+            w.folding().write_str("BBBBBBBBBBBBBBB").unwrap();
+            crate::headers::rfc2047::encode("sélection", &mut w).unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBBBBBBBBBBBBBB=?utf-8?b?cw==?=\r\n",
+                " =?utf-8?b?w6lsZWN0aW9u?=",
+            )
+        );
+    }
+
+    #[test]
+    fn counting_writer() {
+        let mut counter = CountingWriter::new("Subject: ".len());
+
+        {
+            let mut w = EmailWriter::new(&mut counter, "Subject: ".len(), 0, false);
+            w.folding()
+                .write_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore")
+                .unwrap();
+        }
+
+        let mut s = String::new();
+        {
+            let mut w = EmailWriter::new(&mut s, "Subject: ".len(), 0, false);
+            w.folding()
+                .write_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore")
+                .unwrap();
+        }
+
+        assert_eq!(counter.len(), s.len());
+        assert_eq!(counter.folds(), 1);
+        assert_eq!(
+            counter.line_len(),
+            " eiusmod tempor incididunt ut labore".len()
+        );
+        assert_eq!(
+            counter.longest_line_len(),
+            "Subject: Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do".len()
+        );
+    }
+
+    #[test]
+    fn counting_writer_split_line_break() {
+        let mut counter = CountingWriter::new(0);
+        counter.write_str("abc\r").unwrap();
+        assert_eq!(counter.line_len(), 4);
+        counter.write_str("\ndefgh\r\r").unwrap();
+
+        assert_eq!(counter.len(), 12);
+        assert_eq!(counter.folds(), 1);
+        assert_eq!(counter.line_len(), 7);
+        assert_eq!(counter.longest_line_len(), 7);
+    }
+
+    #[test]
+    fn start_header() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            w.start_header("Subject").unwrap();
+            w.folding()
+                .write_str(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod ",
+                )
+                .unwrap();
+            w.start_header("X-Priority").unwrap();
+            w.write_str("1").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Subject: Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do\r\n",
+                " eiusmod\r\n",
+                "X-Priority: 1"
+            )
+        );
+    }
+
+    #[test]
+    fn start_header_invalid() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(w.start_header(""), Err(Error::EmptyFieldName));
+        assert_eq!(
+            w.start_header("Sub ject"),
+            Err(Error::InvalidFieldName {
+                position: 3,
+                c: ' '
+            })
+        );
+        assert_eq!(
+            w.start_header("Subject:"),
+            Err(Error::InvalidFieldName {
+                position: 7,
+                c: ':'
+            })
+        );
+        drop(w);
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn folding_whitespace_tab() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_folding_whitespace(FoldingWhitespace::Tab);
+            w.folding()
+                .write_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod  tempor")
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do\r\n",
+                "\teiusmod  tempor"
+            )
+        );
+    }
+
+    #[test]
+    fn finish_trailing_whitespace() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
+
+        let state = {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.folding().write_str("Hello  ").unwrap();
+            w.finish().unwrap()
+        };
+
+        assert_eq!(s, "Hello  ");
+        assert_eq!(
+            state,
+            EndState {
+                line_len: "Subject: Hello  ".len(),
+                trailing_whitespace: true,
+                spaces: 0,
+                can_go_to_new_line_now: true,
+            }
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let mut s = String::new();
+        let line_len = "Subject:".len();
+
+        {
+            let mut w = EmailWriter::builder()
+                .line_len(line_len)
+                .spaces(1)
+                .can_go_to_new_line_now(true)
+                .max_line_len(20)
+                .build(&mut s);
+            w.folding().write_str("Lorem ipsum dolor sit amet").unwrap();
+        }
+
+        assert_eq!(s, " Lorem ipsum\r\n dolor sit amet");
+    }
+
+    #[test]
+    fn can_go_to_new_line_now() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            assert!(!w.can_go_to_new_line_now());
+            w.write_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do")
+                .unwrap();
+            assert!(w.can_go_to_new_line_now());
+
+            w.set_can_go_to_new_line_now(false);
+            w.space();
+            w.folding().write_str("eiusmod").unwrap();
+            assert!(w.can_go_to_new_line_now());
+            w.space();
+            w.folding().write_str("tempor").unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod\r\n",
+                " tempor"
+            )
+        );
+    }
+
+    #[test]
+    fn trailing_spaces_trim() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_trailing_spaces(TrailingSpaces::Trim);
+            w.folding().write_str("Hello  ").unwrap();
+        }
+
+        assert_eq!(s, "Hello");
+    }
+
+    #[test]
+    fn trailing_spaces_keep() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
+
+        let state = {
+            let mut w = EmailWriter::builder()
+                .line_len(line_len)
+                .trailing_spaces(TrailingSpaces::Keep)
+                .build(&mut s);
+            w.folding().write_str("Hello  ").unwrap();
+            w.finish().unwrap()
+        };
+        assert_eq!(
+            state,
+            EndState {
+                line_len: "Subject: Hello".len(),
+                trailing_whitespace: false,
+                spaces: 2,
+                can_go_to_new_line_now: true,
+            }
+        );
+
+        {
+            let mut w = EmailWriter::new(
+                &mut s,
+                state.line_len,
+                state.spaces,
+                state.can_go_to_new_line_now,
+            );
+            w.folding().write_str("World").unwrap();
+        }
+
+        assert_eq!(s, "Hello  World");
+    }
+
+    #[test]
+    fn breakpoints_across_writes() {
+        let mut s = String::new();
+        let line_len = "Content-Type: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_breakpoints(&[';']);
+            w.folding().write_str("multipart/alternative;").unwrap();
+            w.folding()
+                .write_str("boundary=\"0123456789abcdef0123456789abcdef\"; charset=utf-8;")
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "multipart/alternative;\r\n",
+                " boundary=\"0123456789abcdef0123456789abcdef\"; charset=utf-8;"
+            )
+        );
+    }
+
+    #[test]
+    fn breakpoints_prefer_spaces() {
+        let mut s = String::new();
+        let line_len = "To: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_breakpoints(&[',']);
+            w.folding()
+                .write_str(
+                    "first.person@example.com, second.person@example.com, third.person@example.com",
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            s,
+            concat!(
+                "first.person@example.com, second.person@example.com,\r\n",
+                " third.person@example.com"
+            )
+        );
+    }
+
+    #[test]
+    fn no_fold_restored() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.folding()
+                .write_str("Lorem ipsum dolor sit amet,")
+                .unwrap();
+            w.space();
+            w.no_fold(|w| {
+                w.folding()
+                    .write_str("consectetur adipiscing elit, sed do eiusmod tempor")
+            })
+            .unwrap();
+            w.space();
+            w.folding().write_str("incididunt").unwrap();
         }
 
-        Ok(())
+        assert_eq!(
+            s,
+            concat!(
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor\r\n",
+                " incididunt"
+            )
+        );
     }
-}
 
-impl<'a> Write for EmailWriter<'a> {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.write_spaces()?;
+    #[test]
+    fn line_width_chars() {
+        let input = "Ça fait déjà très longtemps que nous n'avons pas été à la mer";
 
-        let s_after = s.trim_end_matches(' ');
-        self.spaces += s.len() - s_after.len();
+        let mut bytes = String::new();
+        {
+            let mut w = EmailWriter::new(&mut bytes, "Subject: ".len(), 0, false);
+            w.folding().write_str(input).unwrap();
+        }
 
-        if !s_after.is_empty() {
-            self.writer.write_str(s_after)?;
-            self.line_len += s_after.len();
-            self.can_go_to_new_line_now = true;
+        let mut chars = String::new();
+        {
+            let mut w = EmailWriter::builder()
+                .line_len("Subject: ".len())
+                .line_width(LineWidth::Chars)
+                .build(&mut chars);
+            w.folding().write_str(input).unwrap();
         }
 
-        Ok(())
+        assert_eq!(
+            bytes,
+            concat!(
+                "Ça fait déjà très longtemps que nous n'avons pas été à la\r\n",
+                " mer"
+            )
+        );
+        assert_eq!(
+            chars,
+            "Ça fait déjà très longtemps que nous n'avons pas été à la mer"
+        );
     }
 
-    fn write_char(&mut self, c: char) -> fmt::Result {
-        if c == ' ' {
-            self.spaces += 1;
-        } else {
-            self.write_spaces()?;
-            self.can_go_to_new_line_now = true;
+    #[test]
+    fn stray_line_breaks() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
 
-            self.writer.write_char(c)?;
-            self.line_len += c.len_utf8();
+        {
+            let mut w = EmailWriter::builder()
+                .line_len(line_len)
+                .max_line_len(20)
+                .reject_stray_line_breaks(true)
+                .build(&mut s);
+            w.folding().write_str("Lorem ipsum dolor").unwrap();
+            assert!(!w.found_stray_line_break());
+
+            assert_eq!(w.write_char('\n'), Err(fmt::Error));
+            assert_eq!(w.folding().write_str("sit\ramet"), Err(fmt::Error));
+            assert!(w.found_stray_line_break());
         }
 
-        Ok(())
+        assert_eq!(s, "Lorem ipsum\r\n dolor");
     }
-}
 
-impl<'a> Drop for EmailWriter<'a> {
-    fn drop(&mut self) {
-        let _ = self.write_spaces();
-    }
-}
+    #[test]
+    fn strict() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
 
-/// Wrapper around [`Write`] that remembers the length of the
-/// last line and automatically line folds text written to it.
-///
-/// [`Write`]: std::fmt::Write
-pub struct FoldingEmailWriter<'a, 'b> {
-    writer: &'b mut EmailWriter<'a>,
-}
+        {
+            let mut w = EmailWriter::builder()
+                .line_len(line_len)
+                .max_line_len(20)
+                .strict(true)
+                .build(&mut s);
+            w.folding().write_str("Lorem\tipsum dolor").unwrap();
+            assert_eq!(w.violation(), None);
 
-impl<'a, 'b> Write for FoldingEmailWriter<'a, 'b> {
-    fn write_str(&mut self, mut s: &str) -> fmt::Result {
-        while !s.is_empty() {
-            if s.starts_with(' ') {
-                self.writer.space();
-                s = &s[1..];
-                continue;
-            }
+            assert_eq!(w.write_str("sit\r\n"), Err(fmt::Error));
+            assert_eq!(w.violation(), Some(Violation::LineBreak));
+            assert!(w.found_stray_line_break());
 
-            let (start, end) = s.find(' ').map_or((s, ""), |i| s.split_at(i));
+            w.space();
+            assert_eq!(w.write_char('\0'), Err(fmt::Error));
+            assert_eq!(w.violation(), Some(Violation::ControlChar('\0')));
 
-            if self.writer.can_go_to_new_line_now
-                && self.writer.spaces >= 1
-                && (self.writer.projected_line_len() + start.len()) > MAX_LINE_LEN
-            {
-                self.writer.new_line()?;
-            }
+            assert_eq!(w.write_str("adipiscing elit  "), Err(fmt::Error));
+            assert_eq!(w.violation(), Some(Violation::LineTooLong));
 
-            self.writer.write_str(start)?;
-            s = end;
+            w.write_str("sit amet").unwrap();
+            assert_eq!(w.line_len(), 15);
         }
 
-        Ok(())
+        assert_eq!(s, "Lorem\tipsum\r\n dolor sit amet");
     }
 
-    fn write_char(&mut self, c: char) -> fmt::Result {
-        if c == ' ' {
-            self.writer.spaces += 1;
-        } else {
-            self.write_str(c.encode_utf8(&mut [0u8; 4]))?;
+    #[test]
+    fn strict_encoders() {
+        let mut s = "Subject: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::builder()
+                .line_len(line_len)
+                .strict(true)
+                .build(&mut s);
+            crate::headers::unstructured::encode(
+                "Ça fait déjà très longtemps que nous n'avons pas été à la mer, n'est-ce pas ?",
+                &mut w,
+            )
+            .unwrap();
+            assert_eq!(w.violation(), None);
         }
 
-        Ok(())
+        assert!(s.split("\r\n").all(|line| line.len() <= MAX_LINE_LEN));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use pretty_assertions::assert_eq;
-
-    use super::*;
 
     #[test]
-    fn wrap_immediate() {
-        let mut s =
-            "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
-        let line_len = s.len();
+    fn reserve_line_len_nested() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
-            for _ in 0..16 {
-                w.folding().write_str("0123456789").unwrap();
-            }
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.set_max_line_len(30);
+            w.reserve_line_len(2, |w| {
+                w.reserve_line_len(3, |w| {
+                    assert_eq!(w.max_line_len(), 25);
+                    w.folding().write_str("Lorem ipsum dolor sit").unwrap();
+                });
+                assert_eq!(w.max_line_len(), 28);
+                w.folding().write_str(" amet, consectetur").unwrap();
+            });
+            assert_eq!(w.max_line_len(), 30);
         }
 
+        assert_eq!(s, "Lorem ipsum\r\n dolor sit amet, consectetur");
+    }
+
+    #[test]
+    fn debug() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 10, 1, true);
+
         assert_eq!(
-            s,
-            "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA0123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789",
+            format!("{:?}", w.folding()),
+            concat!(
+                "FoldingEmailWriter { writer: EmailWriter { line_len: 10, spaces: 1, ",
+                "can_go_to_new_line_now: true, max_line_len: 76, folding_whitespace: Space, ",
+                "folded: false, trailing_spaces: Flush, breakpoints: [], after_breakpoint: false, ",
+                "no_fold: false, line_width: Bytes, reject_stray_line_breaks: false, ",
+                "found_stray_line_break: false, strict: false, violation: None, ",
+                "line_ending: CrLf, allow_utf8: false, .. } }"
+            )
         );
     }
 
+    #[cfg(feature = "bytes")]
     #[test]
-    fn wrap_keeping_final_whitespace() {
-        let mut s = "Subject: AAAAAAAAAAAAAA".to_owned();
-        let line_len = s.len();
+    fn buf_mut_writer_full() {
+        let mut buf = [0; 16];
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
-            w.folding().write_str("12345 ").unwrap();
-            w.new_line().unwrap();
-            w.folding().write_str("12345").unwrap();
+            let mut adapter = BufMutWriter::new(&mut buf[..]);
+            let mut w = EmailWriter::new(&mut adapter, 0, 0, false);
+            w.write_str("Lorem ipsum").unwrap();
+            assert_eq!(w.write_str(" dolor"), Err(fmt::Error));
         }
 
-        assert_eq!(s, concat!("Subject: AAAAAAAAAAAAAA 12345\r\n", " 12345"));
+        assert_eq!(&buf[..11], b"Lorem ipsum");
     }
 
     #[test]
-    fn catch_space() {
-        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
-        let line_len = s.len();
+    fn write_atoms() {
+        let mut s = String::new();
+        let line_len = "Message-ID: ".len();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
-            w.folding().write_str("BBB ").unwrap();
-            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.write_char('<').unwrap();
+            w.write_dot_atom("0123456789abcdef.0123456789abcdef.0123456789abcdef")
+                .unwrap();
+            w.write_char('@').unwrap();
+            w.write_dot_atom("mail.example.com").unwrap();
+            w.write_char('>').unwrap();
+            w.space();
+            w.write_token("iso-8859-1").unwrap();
+            w.space();
+            w.write_atom("O'Brien").unwrap();
         }
 
         assert_eq!(
             s,
             concat!(
-                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
-                " CCCCCCCCCCCCC"
+                "<0123456789abcdef.0123456789abcdef.0123456789abcdef@mail.example.com>\r\n",
+                " iso-8859-1 O'Brien"
             )
         );
     }
 
     #[test]
-    fn catch_spaces() {
-        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
-        let line_len = s.len();
+    fn write_atoms_invalid() {
+        let mut s = String::new();
+        let mut w = EmailWriter::new(&mut s, 0, 0, false);
+
+        assert_eq!(w.write_atom(""), Err(Error::Empty));
+        assert_eq!(
+            w.write_token("text/plain"),
+            Err(Error::InvalidChar {
+                position: 4,
+                c: '/'
+            })
+        );
+        assert_eq!(
+            w.write_dot_atom(".john"),
+            Err(Error::InvalidChar {
+                position: 0,
+                c: '.'
+            })
+        );
+        assert_eq!(
+            w.write_dot_atom("john..smith"),
+            Err(Error::InvalidChar {
+                position: 5,
+                c: '.'
+            })
+        );
+        assert_eq!(
+            w.write_dot_atom("john."),
+            Err(Error::InvalidChar {
+                position: 4,
+                c: '.'
+            })
+        );
+        assert_eq!(
+            w.write_dot_atom("adrián"),
+            Err(Error::InvalidChar {
+                position: 4,
+                c: 'á'
+            })
+        );
+        drop(w);
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn header_map_writer() {
+        let mut s = String::new();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
-            w.folding().write_str("BBB   ").unwrap();
-            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+            let mut headers = HeaderMapWriter::with_builder(
+                &mut s,
+                EmailWriter::builder().folding_whitespace(FoldingWhitespace::Tab),
+            );
+            type WriteValue = fn(&mut EmailWriter<'_>) -> fmt::Result;
+            let values: [(&str, WriteValue); 2] = [
+                ("Subject", |w| {
+                    w.folding().write_str(
+                        "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod",
+                    )
+                }),
+                ("X-Priority", |w| w.write_str("1")),
+            ];
+            headers.headers(values).unwrap();
+            assert_eq!(
+                headers.header("X Priority", |w| w.write_str("1")),
+                Err(Error::InvalidFieldName {
+                    position: 1,
+                    c: ' '
+                })
+            );
+            headers.finish().unwrap();
         }
 
         assert_eq!(
             s,
             concat!(
-                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
-                "   CCCCCCCCCCCCC"
+                "Subject: Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do\r\n",
+                "\teiusmod\r\n",
+                "X-Priority: 1\r\n",
+                "\r\n"
             )
         );
     }
 
     #[test]
-    fn explicit_space() {
-        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
-        let line_len = s.len();
+    fn line_ending_lf() {
+        let mut s = String::new();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
-            w.folding().write_str("BBB").unwrap();
-            w.space();
-            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+            let mut headers = HeaderMapWriter::with_builder(
+                &mut s,
+                EmailWriter::builder().line_ending(LineEnding::Lf),
+            );
+            headers
+                .header("Subject", |w| {
+                    w.folding().write_str(
+                        "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod",
+                    )
+                })
+                .unwrap();
+            headers.finish().unwrap();
         }
 
         assert_eq!(
             s,
             concat!(
-                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
-                " CCCCCCCCCCCCC"
+                "Subject: Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do\n",
+                " eiusmod\n",
+                "\n"
             )
         );
+
+        let mut counter = CountingWriter::new(0);
+        counter.write_str(&s).unwrap();
+        assert_eq!(counter.folds(), 3);
+        assert_eq!(
+            counter.longest_line_len(),
+            "Subject: Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do".len()
+        );
     }
 
     #[test]
-    fn explicit_spaces() {
-        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned();
-        let line_len = s.len();
+    fn generic_writer() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 1, true);
-            w.folding().write_str("BBB").unwrap();
-            w.space();
-            w.write_char(' ').unwrap();
+            let mut w: EmailWriter<'_, String> = EmailWriter::builder()
+                .line_len(line_len)
+                .build_generic(&mut s);
+            w.folding()
+                .write_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do")
+                .unwrap();
             w.space();
-            w.folding().write_str("CCCCCCCCCCCCC").unwrap();
+            crate::headers::quoted_string::encode("eiusmod tempor", &mut w).unwrap();
         }
 
         assert_eq!(
             s,
             concat!(
-                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBB\r\n",
-                "   CCCCCCCCCCCCC"
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do \"eiusmod\r\n",
+                " tempor\""
             )
         );
     }
 
     #[test]
-    fn optional_breakpoint() {
-        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
-            .to_owned();
-        let line_len = s.len();
+    fn rollback() {
+        let mut s = String::new();
+        let line_len = "Subject: ".len();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
-            w.space();
-            w.folding().write_str("BBBBBBBBBB").unwrap();
+            let mut w = EmailWriter::new_generic(&mut s, line_len, 0, false);
+            w.folding().write_str("Lorem ipsum").unwrap();
             w.space();
-            w.folding().write_str("CCCCCCCCCC").unwrap();
+
+            let checkpoint = w.checkpoint();
+            w.folding()
+                .write_str("dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor")
+                .unwrap();
+            assert_ne!(w.line_len(), checkpoint.line_len());
+            w.rollback(checkpoint);
+            assert_eq!(w.line_len(), checkpoint.line_len());
+
+            crate::headers::rfc2047::encode("Adrián", &mut w).unwrap();
+        }
+
+        assert_eq!(s, "Lorem ipsum =?utf-8?b?QWRyacOhbg==?=");
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn rollback_bytes_mut() {
+        let mut buf = bytes::BytesMut::new();
+
+        {
+            let mut adapter = BufMutWriter::new(&mut buf);
+            let mut w = EmailWriter::new_generic(&mut adapter, 0, 0, false);
+            w.write_str("Lorem").unwrap();
+            let checkpoint = w.checkpoint();
+            w.write_str(" ipsum").unwrap();
+            w.rollback(checkpoint);
+            w.write_str(" dolor").unwrap();
+        }
+
+        assert_eq!(&buf[..], b"Lorem dolor");
+    }
+
+    #[test]
+    fn quoted_pairs_and_comments() {
+        let mut s = String::new();
+        let line_len = "To: ".len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            w.write_char('"').unwrap();
+            w.write_str("John").unwrap();
+            w.write_quoted_pair(' ').unwrap();
+            w.write_quoted_pair('"').unwrap();
+            w.write_char('"').unwrap();
+            w.write_fws();
+            w.write_str("<john@example.com>").unwrap();
+            w.write_cfws("a rather long comment (with parentheses) which needs to be folded")
+                .unwrap();
+
+            assert_eq!(
+                w.write_quoted_pair('\n'),
+                Err(Error::InvalidChar {
+                    position: 0,
+                    c: '\n'
+                })
+            );
+            assert_eq!(
+                w.write_cfws("bad\r\ncomment"),
+                Err(Error::InvalidChar {
+                    position: 3,
+                    c: '\r'
+                })
+            );
         }
 
         assert_eq!(
             s,
             concat!(
-                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\r\n",
-                " BBBBBBBBBB CCCCCCCCCC",
+                "\"John\\ \\\"\" <john@example.com> (a rather long comment \\(with\r\n",
+                " parentheses\\) which needs to be folded)"
             )
         );
     }
 
     #[test]
-    fn double_spaces_issue_949() {
-        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA ".to_string();
-        let line_len = s.len();
+    fn cfws_tab_and_utf8() {
+        let mut s = String::new();
 
         {
-            let mut w = EmailWriter::new(&mut s, line_len, 0, true);
-            w.folding().write_str("BBBBBBBBBBBBB ").unwrap();
-            crate::headers::rfc2047::encode("sélection", &mut w).unwrap();
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            w.write_cfws("a\tcomment").unwrap();
+            assert_eq!(
+                w.write_cfws("café"),
+                Err(Error::InvalidChar {
+                    position: 3,
+                    c: 'é'
+                })
+            );
+
+            w.set_allow_utf8(true);
+            w.write_cfws("café").unwrap();
+        }
+
+        assert_eq!(s, " (a\tcomment) (café)");
+    }
+
+    #[test]
+    fn quoted_pair_strict() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::builder()
+                .line_len(3)
+                .max_line_len(6)
+                .strict(true)
+                .build(&mut s);
+            w.write_quoted_pair('\t').unwrap();
+            assert_eq!(w.line_len(), 5);
+
+            assert_eq!(w.write_quoted_pair(' '), Err(Error::Fmt(fmt::Error)));
+            assert_eq!(w.violation(), Some(Violation::LineTooLong));
+        }
+
+        assert_eq!(s, "\\\t\\");
+    }
+
+    #[test]
+    fn header_value_dyn() {
+        struct Unstructured(&'static str);
+
+        impl HeaderEncode for Unstructured {
+            fn encode(&self, w: &mut EmailWriter<'_>) -> fmt::Result {
+                w.folding().write_str(self.0)
+            }
+        }
+
+        let values: [(&str, Box<dyn HeaderEncode>); 2] = [
+            ("Subject", Box::new(Unstructured("Hello"))),
+            (
+                "Comments",
+                Box::new(Unstructured(
+                    "A comment long enough to get folded by the writer, like any other header value",
+                )),
+            ),
+        ];
+
+        let mut s = String::new();
+        {
+            let mut headers = HeaderMapWriter::new(&mut s);
+            for (name, value) in &values {
+                headers.header_value(name, value).unwrap();
+            }
         }
 
         assert_eq!(
             s,
             concat!(
-                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBBBBBBBBBBBB\r\n",
-                " =?utf-8?b?c8OpbGVjdGlvbg==?=",
+                "Subject: Hello\r\n",
+                "Comments: A comment long enough to get folded by the writer, like any other\r\n",
+                " header value\r\n"
             )
         );
     }
 
     #[test]
-    fn double_spaces_issue_949_no_space() {
-        let mut s = "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA ".to_string();
+    fn prefolded() {
+        let mut s = "DKIM-Signature: v=1;".to_string();
         let line_len = s.len();
 
         {
             let mut w = EmailWriter::new(&mut s, line_len, 0, true);
-            w.folding().write_str("BBBBBBBBBBBBBBB").unwrap();
-            crate::headers::rfc2047::encode("sélection", &mut w).unwrap();
+            w.space();
+            w.write_prefolded("a=rsa-sha256;\r\n d=example.com;")
+                .unwrap();
+            assert_eq!(w.line_len(), " d=example.com;".len());
+            w.space();
+            w.folding().write_str("s=selector;").unwrap();
         }
 
         assert_eq!(
             s,
-            concat!(
-                "Subject: AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA BBBBBBBBBBBBBBB=?utf-8?b?cw==?=\r\n",
-                " =?utf-8?b?w6lsZWN0aW9u?=",
-            )
+            "DKIM-Signature: v=1; a=rsa-sha256;\r\n d=example.com; s=selector;"
         );
     }
+
+    #[test]
+    fn prefolded_invalid_line_break() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                w.write_prefolded("a\r\nb"),
+                Err(Error::InvalidLineBreak { position: 2 })
+            );
+            assert_eq!(
+                w.write_prefolded("a\r b"),
+                Err(Error::InvalidLineBreak { position: 1 })
+            );
+            assert_eq!(
+                w.write_prefolded("a\r\n"),
+                Err(Error::InvalidLineBreak { position: 2 })
+            );
+        }
+
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn prefolded_mismatched_line_ending() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            assert_eq!(
+                w.write_prefolded("a\r\n b\n c"),
+                Err(Error::InvalidLineBreak { position: 5 })
+            );
+
+            w.set_line_ending(LineEnding::Lf);
+            assert_eq!(
+                w.write_prefolded("a\n b\r\n c"),
+                Err(Error::InvalidLineBreak { position: 4 })
+            );
+            w.write_prefolded("a\n b\n\tc").unwrap();
+        }
+
+        assert_eq!(s, "a\n b\n\tc");
+    }
+
+    #[test]
+    fn prefolded_too_long() {
+        let mut s = "Subject: ".to_string();
+        let line_len = s.len();
+
+        {
+            let mut w = EmailWriter::new(&mut s, line_len, 0, false);
+            let line = "a".repeat(70);
+            assert_eq!(w.write_prefolded(&format!("b\r\n {}", line)), Ok(()));
+            assert_eq!(
+                w.write_prefolded("bcdefg"),
+                Err(Error::LineTooLong { position: 0 })
+            );
+            assert_eq!(
+                w.write_prefolded(&format!("\r\n {}", "a".repeat(76))),
+                Err(Error::LineTooLong { position: 2 })
+            );
+        }
+
+        assert_eq!(s, format!("Subject: b\r\n {}", "a".repeat(70)));
+    }
+
+    #[test]
+    fn prefolded_strict() {
+        let mut s = String::new();
+
+        {
+            let mut w = EmailWriter::new(&mut s, 0, 0, false);
+            w.set_strict(true);
+            assert_eq!(
+                w.write_prefolded("a\r\n\tb\x1bc"),
+                Err(Error::InvalidChar {
+                    position: 5,
+                    c: '\x1b'
+                })
+            );
+        }
+
+        assert_eq!(s, "");
+    }
 }
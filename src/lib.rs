@@ -1,10 +1,40 @@
 //! Low-level crate implementing various RFCs for encoding emails.
 //! Used internally by [lettre].
 //!
+//! The crate supports `no_std` environments with `alloc` when the
+//! default `std` feature is disabled. Disabling the default `base64`
+//! feature replaces the [`base64`] crate with a built-in implementation
+//! of the standard alphabet.
+//!
+//! The error types implement `std::error::Error` when the `std` feature
+//! is enabled. Enabling the `core-error` feature, which requires
+//! Rust 1.81, makes them implement `core::error::Error` instead, which
+//! is also available in `no_std` environments.
+//!
+//! The `tracing` feature emits debug-level [`tracing`] events when the
+//! encoders make a significant decision, like falling back to RFC 2047,
+//! splitting a parameter into continuations, folding a line or choosing
+//! a body encoding.
+//!
 //! [lettre]: https://crates.io/crates/lettre
+//! [`base64`]: https://crates.io/crates/base64
+//! [`tracing`]: https://crates.io/crates/tracing
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 #![deny(rust_2018_idioms, missing_docs, rustdoc::broken_intra_doc_links)]
 
+extern crate alloc;
+
+#[macro_use]
+mod trace;
+
+mod base64_engine;
 pub mod body;
+#[cfg(feature = "decode")]
+pub mod decode;
+mod error;
 pub mod headers;
+pub mod prelude;
+
+pub use self::error::Error;
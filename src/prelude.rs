@@ -0,0 +1,41 @@
+//! Re-exports of the most commonly used items.
+//!
+//! The encoders are renamed after the encoding they implement, so that
+//! they can all be glob-imported without clashing.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use email_encoding::prelude::*;
+//!
+//! # fn main() -> Result<(), email_encoding::Error> {
+//! let mut output = String::from("Subject: ");
+//! {
+//!     let line_len = output.len();
+//!     let mut writer = EmailWriter::new(&mut output, line_len, 0, false);
+//!     encode_rfc2047("Adrián", &mut writer)?;
+//! }
+//! assert_eq!(output, "Subject: =?utf-8?b?QWRyacOhbg==?=");
+//!
+//! let mut output = String::from("Content-Disposition: attachment;");
+//! {
+//!     let line_len = output.len();
+//!     let mut writer = EmailWriter::new(&mut output, line_len, 1, false);
+//!     encode_rfc2231("filename", "faktúra.pdf", &mut writer)?;
+//! }
+//! assert_eq!(
+//!     output,
+//!     "Content-Disposition: attachment; filename*=utf-8''fakt%C3%BAra.pdf"
+//! );
+//!
+//! assert_eq!(Encoding::choose("Hello!", false), Encoding::SevenBit);
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::body::{base64::encode as encode_base64, Encoding};
+pub use crate::headers::{
+    quoted_string::encode as encode_quoted_string, rfc2047::encode as encode_rfc2047,
+    rfc2231::encode as encode_rfc2231, unstructured::encode as encode_unstructured,
+    writer::EmailWriter,
+};
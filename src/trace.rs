@@ -0,0 +1,15 @@
+//! Diagnostics emitted when the `tracing` feature is enabled.
+
+/// Emit a debug-level [`tracing`] event, or nothing if the `tracing`
+/// feature is disabled.
+///
+/// Accepts the same arguments as `tracing::debug!`, but can only be
+/// used as a statement.
+///
+/// [`tracing`]: https://docs.rs/tracing
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!($($arg)*);
+    };
+}